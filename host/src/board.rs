@@ -0,0 +1,226 @@
+//! Finding the board and talking to its firmware over the USB serial port.
+
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use nusb::MaybeFuture;
+use serialport::{SerialPort, SerialPortType};
+
+// Same as `usb::config` in the firmware
+const VID: u16 = 0x1209;
+const PID: u16 = 0x0001;
+
+const PROMPT: &str = "\r\n> ";
+
+// CDC-ACM serial ports have a control and a data interface
+const CLASS_CDC: u8 = 0x02;
+const SUBCLASS_ACM: u8 = 0x02;
+const CLASS_CDC_DATA: u8 = 0x0a;
+
+/// The firmwares with a serial port, told apart by USB product string
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Firmware {
+    /// `shell`: text commands
+    Shell,
+    /// `stream`: frames from the host
+    Stream,
+}
+
+impl Firmware {
+    fn product(self) -> &'static str {
+        match self {
+            Firmware::Shell => "LED matrix shell",
+            Firmware::Stream => "LED matrix display",
+        }
+    }
+
+    pub fn bin(self) -> &'static str {
+        match self {
+            Firmware::Shell => "shell",
+            Firmware::Stream => "stream",
+        }
+    }
+
+    /// Serial ports the firmware makes itself, besides the `usb-log` one
+    fn ports(product: Option<&str>) -> usize {
+        match product {
+            Some(p) if [Firmware::Shell, Firmware::Stream].iter().any(|f| f.product() == p) => 1,
+            _ => 0,
+        }
+    }
+}
+
+pub struct Board {
+    pub path: String,
+    pub product: Option<String>,
+    /// The flash chip's unique ID, different on every board
+    pub serial: Option<String>,
+    /// The `usb-log` port rather than the firmware's own
+    pub log: bool,
+}
+
+/// Every board plugged in, whatever it runs
+pub fn list() -> Result<Vec<Board>> {
+    let logs = log_interfaces()?;
+    let ports = serialport::available_ports().context("listing serial ports")?;
+    Ok(ports
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            SerialPortType::UsbPort(usb) if usb.vid == VID && usb.pid == PID => Some(Board {
+                path: port.port_name,
+                log: usb.interface.is_some_and(|i| {
+                    logs.iter()
+                        .any(|(serial, interfaces)| *serial == usb.serial_number && interfaces.contains(&i))
+                }),
+                product: usb.product,
+                serial: usb.serial_number,
+            }),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Serial number and interfaces of the `usb-log` port of every board built with it.
+///
+/// `usb::builder` in the firmware adds the log before the firmware's own
+/// ports, so a board with more serial ports than its firmware makes has the
+/// log on the first. Linux and Windows name a port by its control interface,
+/// macOS by its data interface.
+fn log_interfaces() -> Result<Vec<(Option<String>, [u8; 2])>> {
+    let devices = nusb::list_devices().wait().context("listing USB devices")?;
+    Ok(devices
+        .filter(|d| (d.vendor_id(), d.product_id()) == (VID, PID))
+        .filter_map(|d| {
+            let numbers = |class, subclass| {
+                d.interfaces()
+                    .filter(move |i| (i.class(), i.subclass()) == (class, subclass))
+                    .map(|i| i.interface_number())
+            };
+            if numbers(CLASS_CDC, SUBCLASS_ACM).count() <= Firmware::ports(d.product_string()) {
+                return None;
+            }
+            let interfaces = [
+                numbers(CLASS_CDC, SUBCLASS_ACM).min()?,
+                numbers(CLASS_CDC_DATA, 0).min()?,
+            ];
+            Some((d.serial_number().map(str::to_string), interfaces))
+        })
+        .collect())
+}
+
+/// Opens `port` if given, otherwise the first board running `firmware`
+pub fn open(port: Option<&str>, firmware: Firmware) -> Result<Box<dyn SerialPort>> {
+    let path = match port {
+        Some(path) => path.to_string(),
+        None => {
+            let boards = list()?;
+            match boards
+                .iter()
+                .find(|b| !b.log && b.product.as_deref() == Some(firmware.product()))
+            {
+                Some(board) => board.path.clone(),
+                None if boards.is_empty() => bail!("no board found, is it plugged in?"),
+                None => bail!(
+                    "no board running the `{}` firmware, flash it with `cargo run --release --bin {}`",
+                    firmware.bin(),
+                    firmware.bin()
+                ),
+            }
+        }
+    };
+    open_path(&path)
+}
+
+/// Opens `port` if given, otherwise the first board's `usb-log` port
+pub fn open_log(port: Option<&str>) -> Result<Box<dyn SerialPort>> {
+    let path = match port {
+        Some(path) => path.to_string(),
+        None => {
+            let boards = list()?;
+            match boards.iter().find(|b| b.log) {
+                Some(board) => board.path.clone(),
+                None if boards.is_empty() => bail!("no board found, is it plugged in?"),
+                None => bail!("no log port, build the firmware with `--features usb-log`"),
+            }
+        }
+    };
+    open_path(&path)
+}
+
+fn open_path(path: &str) -> Result<Box<dyn SerialPort>> {
+    // Baud rate means nothing over USB
+    let mut port = serialport::new(path, 115_200)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .with_context(|| format!("opening {path}"))?;
+    port.write_data_terminal_ready(true)?;
+    Ok(port)
+}
+
+/// Line based session with the `shell` firmware
+pub struct Shell {
+    port: Box<dyn SerialPort>,
+}
+
+impl Shell {
+    pub fn open(port: Option<&str>) -> Result<Shell> {
+        let mut shell = Shell {
+            port: open(port, Firmware::Shell)?,
+        };
+        // Clear whatever is typed and wait for a fresh prompt, the banner is
+        // only printed when the port is first opened
+        shell.port.write_all(b"\x15\r")?;
+        shell.read_until_prompt(Duration::from_secs(2))?;
+        shell.drain()?;
+        Ok(shell)
+    }
+
+    /// Runs one command, returns its output without the echo and prompt
+    pub fn command(&mut self, line: &str) -> Result<String> {
+        self.port.write_all(line.as_bytes())?;
+        self.port.write_all(b"\r")?;
+        let output = self.read_until_prompt(Duration::from_secs(5))?;
+        // The first line is our own command echoed back
+        let reply = output.split_once("\r\n").map_or("", |(_, rest)| rest);
+        let reply = reply.replace("\r\n", "\n");
+        if let Some(error) = reply.trim().strip_prefix("error: ") {
+            bail!("{line}: {error}");
+        }
+        Ok(reply)
+    }
+
+    /// Drops output until the board goes quiet, e.g. a second prompt after the banner
+    fn drain(&mut self) -> Result<()> {
+        let mut buf = [0; 256];
+        loop {
+            match self.port.read(&mut buf) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::TimedOut => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Gives up when the board is quiet for `idle`, long outputs like `record` keep going
+    fn read_until_prompt(&mut self, idle: Duration) -> Result<String> {
+        let mut deadline = Instant::now() + idle;
+        let mut output = Vec::new();
+        let mut buf = [0; 4096];
+        while !output.ends_with(PROMPT.as_bytes()) {
+            if Instant::now() > deadline {
+                bail!("no prompt from the board, got {:?}", String::from_utf8_lossy(&output));
+            }
+            match self.port.read(&mut buf) {
+                Ok(n) => {
+                    output.extend_from_slice(&buf[..n]);
+                    deadline = Instant::now() + idle;
+                }
+                Err(e) if e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        output.truncate(output.len() - PROMPT.len());
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
@@ -0,0 +1,108 @@
+//! Rebooting the board into the RP2040's ROM bootloader through the reset
+//! interface every USB firmware has, and flashing it once the bootloader's
+//! drive is mounted.
+
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use nusb::MaybeFuture;
+use nusb::transfer::{ControlOut, ControlType, Recipient};
+use sysinfo::Disks;
+
+// Same as `usb::config` in the firmware
+const VID: u16 = 0x1209;
+const PID: u16 = 0x0001;
+
+// The ROM bootloader
+const BOOT_VID: u16 = 0x2e8a;
+const BOOT_PID: u16 = 0x0003;
+
+// Reset interface, as in the pico-sdk
+const CLASS_VENDOR: u8 = 0xff;
+const SUBCLASS_RESET: u8 = 0x00;
+const PROTOCOL_RESET: u8 = 0x01;
+const REQ_BOOTSEL: u8 = 0x01;
+
+// Enumerating and getting mounted by the desktop can take a while
+const MOUNT_TIMEOUT: Duration = Duration::from_secs(15);
+const POLL: Duration = Duration::from_millis(200);
+
+/// Reboots the first board into the bootloader, unless one is already there
+pub fn reboot() -> Result<()> {
+    let devices: Vec<_> = nusb::list_devices().wait().context("listing USB devices")?.collect();
+    if devices
+        .iter()
+        .any(|d| (d.vendor_id(), d.product_id()) == (BOOT_VID, BOOT_PID))
+    {
+        return Ok(());
+    }
+    let Some(device) = devices.iter().find(|d| (d.vendor_id(), d.product_id()) == (VID, PID)) else {
+        bail!("no board found, is it plugged in?");
+    };
+    let Some(iface) = device
+        .interfaces()
+        .find(|i| (i.class(), i.subclass(), i.protocol()) == (CLASS_VENDOR, SUBCLASS_RESET, PROTOCOL_RESET))
+    else {
+        bail!("the firmware has no reset interface, hold BOOTSEL while plugging the board in");
+    };
+    let number = iface.interface_number();
+    let interface = device
+        .open()
+        .wait()
+        .context("opening the board")?
+        .claim_interface(number)
+        .wait()
+        .context("claiming the reset interface")?;
+    let request = ControlOut {
+        control_type: ControlType::Vendor,
+        recipient: Recipient::Interface,
+        request: REQ_BOOTSEL,
+        value: 0,
+        index: number as u16,
+        data: &[],
+    };
+    interface
+        .control_out(request, Duration::from_secs(1))
+        .wait()
+        .context("asking for the bootloader")?;
+    Ok(())
+}
+
+/// Where the bootloader's drive is mounted, waiting for it to show up
+pub fn wait_for_drive() -> Result<PathBuf> {
+    let start = Instant::now();
+    loop {
+        let disks = Disks::new_with_refreshed_list();
+        if let Some(disk) = disks
+            .list()
+            .iter()
+            .find(|d| d.mount_point().join("INFO_UF2.TXT").exists())
+        {
+            return Ok(disk.mount_point().to_path_buf());
+        }
+        if start.elapsed() > MOUNT_TIMEOUT {
+            bail!("the bootloader's RPI-RP2 drive didn't get mounted, mount it and try again");
+        }
+        thread::sleep(POLL);
+    }
+}
+
+/// Hands `elf` to `elf2uf2-rs`, which converts it and copies it to the drive
+pub fn flash(elf: &Path, serial: bool) -> Result<()> {
+    let mut command = process::Command::new("elf2uf2-rs");
+    command.arg("--deploy");
+    if serial {
+        command.arg("--serial");
+    }
+    let status = command
+        .arg(elf)
+        .status()
+        .context("running elf2uf2-rs, install it with `cargo install elf2uf2-rs`")?;
+    if !status.success() {
+        bail!("elf2uf2-rs failed");
+    }
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Reading back the panel with the `shell` firmware's `screenshot` and
+//! `record`, and saving it as PNG or GIF.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, Frame, Rgb, RgbImage};
+
+use crate::frame::{HEIGHT, WIDTH};
+
+// Same as `shell::PIXEL_LETTERS` in the firmware, indexed by red, green and blue bit
+const LETTERS: &[u8; 8] = b".bgcrmyw";
+
+// Delay for the last frame, which has no next one to measure against
+const LAST_DELAY: Duration = Duration::from_millis(100);
+
+/// One frame of `screenshot` or `record` output
+pub struct Shown {
+    /// Frame number of the effect
+    pub frame: u32,
+    /// Board uptime when it was shown
+    pub at: Duration,
+    pub image: RgbImage,
+}
+
+/// Every frame in the output, other lines are skipped
+pub fn parse(output: &str) -> Result<Vec<Shown>> {
+    let mut frames = Vec::new();
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        let Some(header) = line.strip_prefix("frame ") else {
+            continue;
+        };
+        let (frame, ms) = header.split_once(' ').context("bad frame header")?;
+        let mut image = RgbImage::new(WIDTH, HEIGHT);
+        for y in 0..HEIGHT {
+            let row = lines.next().context("frame cut short")?;
+            if row.len() != WIDTH as usize {
+                bail!("bad frame row {row:?}");
+            }
+            for (x, letter) in row.bytes().enumerate() {
+                let i = LETTERS.iter().position(|&l| l == letter).context("bad pixel letter")?;
+                let on = |bit: usize| if i & bit != 0 { 255 } else { 0 };
+                image.put_pixel(x as u32, y, Rgb([on(4), on(2), on(1)]));
+            }
+        }
+        frames.push(Shown {
+            frame: frame.parse()?,
+            at: Duration::from_millis(ms.parse()?),
+            image,
+        });
+    }
+    Ok(frames)
+}
+
+pub fn save_png(shown: &Shown, path: &Path, scale: u32) -> Result<()> {
+    scaled(&shown.image, scale)
+        .save(path)
+        .with_context(|| format!("saving {}", path.display()))
+}
+
+/// Loops forever, frames that repeat are merged into one longer frame
+pub fn save_gif(frames: &[Shown], path: &Path, scale: u32) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let mut i = 0;
+    while i < frames.len() {
+        let mut next = i + 1;
+        while next < frames.len() && frames[next].image == frames[i].image {
+            next += 1;
+        }
+        let delay = match frames.get(next) {
+            Some(f) => f.at.saturating_sub(frames[i].at),
+            None => LAST_DELAY,
+        };
+        let image = image::DynamicImage::ImageRgb8(scaled(&frames[i].image, scale)).to_rgba8();
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, Delay::from_saturating_duration(delay)))?;
+        i = next;
+    }
+    Ok(())
+}
+
+/// Sharp pixels, the panel is tiny
+fn scaled(image: &RgbImage, scale: u32) -> RgbImage {
+    let scale = scale.max(1);
+    imageops::resize(image, WIDTH * scale, HEIGHT * scale, FilterType::Nearest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the firmware's `shell::frame_text` prints, a letter per pixel
+    fn frame_text(frame: u32, ms: u64, letter: impl Fn(u32, u32) -> u8) -> String {
+        let mut text = format!("frame {frame} {ms}\n");
+        for y in 0..HEIGHT {
+            text.extend((0..WIDTH).map(|x| letter(x, y) as char));
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn parses_every_letter() {
+        let letter = |x: u32, y: u32| LETTERS[((x + y) % 8) as usize];
+        let shown = parse(&frame_text(42, 1500, letter)).unwrap();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].frame, 42);
+        assert_eq!(shown[0].at, Duration::from_millis(1500));
+        for (x, y, p) in shown[0].image.enumerate_pixels() {
+            let i = (x + y) % 8;
+            let on = |bit| if i & bit != 0 { 255 } else { 0 };
+            assert_eq!(p.0, [on(4), on(2), on(1)], "pixel {x},{y}");
+        }
+    }
+
+    #[test]
+    fn skips_other_lines() {
+        let mut output = frame_text(1, 10, |_, _| b'r');
+        output += &frame_text(2, 60, |_, _| b'.');
+        output += "recorded 2 frames\n";
+        let shown = parse(&output).unwrap();
+        assert_eq!(shown.iter().map(|s| s.frame).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(shown[0].image.get_pixel(5, 5).0, [255, 0, 0]);
+        assert_eq!(shown[1].image.get_pixel(5, 5).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_cut_frames() {
+        let text = frame_text(1, 10, |_, _| b'w');
+        let cut: String = text.lines().take(10).map(|l| format!("{l}\n")).collect();
+        assert!(parse(&cut).is_err());
+        assert!(parse(&text.replace('w', "x")).is_err());
+    }
+}
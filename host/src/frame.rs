@@ -0,0 +1,179 @@
+//! Turning pictures into frames for the `stream` firmware.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage, RgbaImage};
+
+pub const WIDTH: u32 = 32;
+pub const HEIGHT: u32 = 32;
+
+// Header layout from `stream` in the firmware
+const MAGIC: &[u8; 4] = b"LMFR";
+const FORMAT_RGB888: u8 = 1;
+
+/// Writes numbered frames to the board
+pub struct FrameWriter<W> {
+    out: W,
+    frame: u32,
+}
+
+impl<W: Write> FrameWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, frame: 0 }
+    }
+
+    pub fn send(&mut self, image: &RgbImage) -> Result<()> {
+        let mut packet = Vec::with_capacity(12 + image.as_raw().len());
+        packet.extend_from_slice(MAGIC);
+        packet.extend_from_slice(&[FORMAT_RGB888, image.width() as u8, image.height() as u8, 0]);
+        packet.extend_from_slice(&self.frame.to_le_bytes());
+        packet.extend_from_slice(image.as_raw());
+        self.out.write_all(&packet)?;
+        self.out.flush()?;
+        self.frame = self.frame.wrapping_add(1);
+        Ok(())
+    }
+}
+
+/// How pictures that aren't square are made to fit
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Fit {
+    /// Whole picture, black bars on the sides
+    Contain,
+    /// Fills the panel, cropping the middle
+    Cover,
+    /// Squashed to 32x32
+    Stretch,
+}
+
+/// Scales to the panel and, unless `dither` is off, spreads the error of
+/// rounding to the panel's eight colors
+pub fn to_panel(image: &RgbaImage, fit: Fit, dither: bool) -> RgbImage {
+    let (w, h) = (image.width(), image.height());
+    let scaled = match fit {
+        Fit::Stretch => imageops::resize(image, WIDTH, HEIGHT, FilterType::Triangle),
+        Fit::Contain | Fit::Cover => {
+            let sx = WIDTH as f32 / w as f32;
+            let sy = HEIGHT as f32 / h as f32;
+            let s = if fit == Fit::Contain { sx.min(sy) } else { sx.max(sy) };
+            let nw = ((w as f32 * s).round() as u32).max(1);
+            let nh = ((h as f32 * s).round() as u32).max(1);
+            let resized = imageops::resize(image, nw, nh, FilterType::Triangle);
+            let mut canvas = RgbaImage::new(WIDTH, HEIGHT);
+            let x = (WIDTH as i64 - nw as i64) / 2;
+            let y = (HEIGHT as i64 - nh as i64) / 2;
+            imageops::overlay(&mut canvas, &resized, x, y);
+            canvas
+        }
+    };
+
+    // Transparent parts go black
+    let mut rgb = RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let [r, g, b, a] = scaled.get_pixel(x, y).0;
+        let blend = |c: u8| (c as u16 * a as u16 / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    });
+    if dither {
+        floyd_steinberg(&mut rgb);
+    } else {
+        for p in rgb.pixels_mut() {
+            p.0 = p.0.map(|c| if c >= 128 { 255 } else { 0 });
+        }
+    }
+    rgb
+}
+
+pub fn load(path: &std::path::Path) -> Result<RgbaImage> {
+    let image = image::open(path).with_context(|| format!("opening {}", path.display()))?;
+    Ok(image.to_rgba8())
+}
+
+/// Each channel is on or off on the panel
+fn floyd_steinberg(image: &mut RgbImage) {
+    let (w, h) = (image.width() as i32, image.height() as i32);
+    let mut error = vec![[0i16; 3]; (w * h) as usize];
+    for y in 0..h {
+        for x in 0..w {
+            let i = (y * w + x) as usize;
+            let p = image.get_pixel_mut(x as u32, y as u32);
+            let mut quant = [0i16; 3];
+            for c in 0..3 {
+                let v = (p.0[c] as i16 + error[i][c]).clamp(0, 255);
+                let out = if v >= 128 { 255 } else { 0 };
+                p.0[c] = out as u8;
+                quant[c] = v - out;
+            }
+            for (dx, dy, weight) in [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || nx >= w || ny >= h {
+                    continue;
+                }
+                let j = (ny * w + nx) as usize;
+                for c in 0..3 {
+                    error[j][c] += quant[c] * weight / 16;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(w: u32, h: u32, c: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(w, h, image::Rgba(c))
+    }
+
+    #[test]
+    fn header_matches_the_firmware() {
+        let image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([1, 2, 3]));
+        let mut writer = FrameWriter::new(Vec::new());
+        writer.send(&image).unwrap();
+        writer.send(&image).unwrap();
+
+        let len = 12 + (WIDTH * HEIGHT * 3) as usize;
+        assert_eq!(writer.out.len(), 2 * len);
+        for (frame, packet) in writer.out.chunks(len).enumerate() {
+            // The table in the firmware's `stream` module
+            assert_eq!(&packet[0..4], b"LMFR");
+            assert_eq!(packet[4], 1, "Rgb888");
+            assert_eq!((packet[5], packet[6]), (32, 32));
+            assert_eq!(packet[7], 0);
+            assert_eq!(packet[8..12], (frame as u32).to_le_bytes());
+            assert_eq!(&packet[12..15], &[1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn only_panel_colors() {
+        let image = RgbaImage::from_fn(50, 20, |x, y| image::Rgba([(x * 5) as u8, (y * 12) as u8, 200, 255]));
+        for dither in [false, true] {
+            let panel = to_panel(&image, Fit::Cover, dither);
+            assert_eq!(panel.dimensions(), (WIDTH, HEIGHT));
+            assert!(panel.pixels().flat_map(|p| p.0).all(|c| c == 0 || c == 255));
+        }
+    }
+
+    #[test]
+    fn dithering_keeps_the_average() {
+        let gray = solid(WIDTH, HEIGHT, [100, 100, 100, 255]);
+        let lit = |image: &RgbImage| image.pixels().filter(|p| p.0[0] == 255).count() as f32;
+        assert_eq!(lit(&to_panel(&gray, Fit::Stretch, false)), 0.0);
+        let share = lit(&to_panel(&gray, Fit::Stretch, true)) / (WIDTH * HEIGHT) as f32;
+        assert!((share - 100.0 / 255.0).abs() < 0.05, "{share}");
+    }
+
+    #[test]
+    fn contain_adds_bars() {
+        let wide = solid(64, 32, [255, 255, 255, 255]);
+        let panel = to_panel(&wide, Fit::Contain, false);
+        assert_eq!(panel.get_pixel(16, 0).0, [0, 0, 0]);
+        assert_eq!(panel.get_pixel(16, 16).0, [255, 255, 255]);
+        assert_eq!(panel.get_pixel(16, 31).0, [0, 0, 0]);
+        let panel = to_panel(&wide, Fit::Cover, false);
+        assert_eq!(panel.get_pixel(16, 0).0, [255, 255, 255]);
+    }
+}
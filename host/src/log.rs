@@ -0,0 +1,44 @@
+//! Following the `usb-log` port: defmt frames turned back into text with the
+//! format strings in the firmware's ELF.
+
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use defmt_decoder::{DecodeError, Table};
+use serialport::SerialPort;
+
+/// Prints the log until the board goes away
+pub fn follow(mut port: Box<dyn SerialPort>, elf: &Path) -> Result<()> {
+    let elf_bytes = fs::read(elf).with_context(|| format!("reading {}", elf.display()))?;
+    let table = Table::parse(&elf_bytes)
+        .with_context(|| format!("reading defmt data from {}", elf.display()))?
+        .context("no defmt data in the ELF, is it one of the firmwares?")?;
+    // Without debug info there are just no file and line numbers
+    let locations = table.get_locations(&elf_bytes).unwrap_or_default();
+    let mut decoder = table.new_stream_decoder();
+
+    let mut buf = [0; 1024];
+    loop {
+        let n = match port.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e).context("reading the log"),
+        };
+        decoder.received(&buf[..n]);
+        loop {
+            match decoder.decode() {
+                Ok(frame) => {
+                    println!("{}", frame.display(true));
+                    if let Some(location) = locations.get(&frame.index()) {
+                        println!("└─ {} @ {}:{}", location.module, location.file.display(), location.line);
+                    }
+                }
+                Err(DecodeError::UnexpectedEof) => break,
+                // The board drops logs when nobody reads them fast enough
+                Err(DecodeError::Malformed) => eprintln!("(some log was lost)"),
+            }
+        }
+    }
+}
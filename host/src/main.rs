@@ -0,0 +1,251 @@
+//! Companion CLI for the LED matrix: finds the board over USB and speaks the
+//! firmware's protocols, the serial shell of `shell` and the frames of
+//! `stream`.
+
+mod board;
+mod bootloader;
+mod capture;
+mod frame;
+mod log;
+mod media_keys;
+mod screen;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use image::AnimationDecoder;
+use image::codecs::gif::GifDecoder;
+
+use board::{Firmware, Shell};
+use frame::{Fit, FrameWriter};
+use screen::{Region, Screen};
+
+/// Control the LED matrix over USB
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Serial port of the board, found by its USB IDs when left out
+    #[arg(long, global = true)]
+    port: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Boards that are plugged in and what they run
+    List,
+    /// Show a picture (`stream` firmware)
+    PushImage {
+        file: PathBuf,
+        #[command(flatten)]
+        look: Look,
+    },
+    /// Play an animated GIF (`stream` firmware)
+    PushGif {
+        file: PathBuf,
+        /// Times to play it, 0 for forever
+        #[arg(long, default_value_t = 0)]
+        loops: u32,
+        #[command(flatten)]
+        look: Look,
+    },
+    /// Switch to an effect, or list them without a name (`shell` firmware)
+    Effect { name: Option<String> },
+    /// Change a setting of the running effect (`shell` firmware)
+    Set { param: Param, value: String },
+    /// Effect, frame and settings (`shell` firmware)
+    Status,
+    /// Save what the panel shows as a PNG (`shell` firmware)
+    Screenshot {
+        #[arg(short, long, default_value = "screenshot.png")]
+        output: PathBuf,
+        /// Size of a panel pixel in the picture
+        #[arg(long, default_value_t = 8)]
+        scale: u32,
+    },
+    /// Record the running effect to a GIF (`shell` firmware)
+    Record {
+        #[arg(short, long, default_value = "record.gif")]
+        output: PathBuf,
+        #[arg(long, default_value_t = 100)]
+        frames: u32,
+        /// Size of a panel pixel in the picture
+        #[arg(long, default_value_t = 8)]
+        scale: u32,
+    },
+    /// Mirror part of the screen, scaled to 32x32 (`stream` firmware)
+    StreamScreen {
+        /// x,y,width,height, the whole screen when left out
+        #[arg(long)]
+        region: Option<Region>,
+        #[arg(long, default_value_t = 30)]
+        fps: u32,
+        #[command(flatten)]
+        look: Look,
+    },
+    /// Forward the keyboard's media keys (`shell` firmware, Linux with X11)
+    MediaKeys,
+    /// Print the defmt log of a firmware built with `--features usb-log`
+    Log {
+        /// The running firmware's ELF, for the log's format strings
+        elf: PathBuf,
+    },
+    /// Reboot into the USB bootloader (any firmware with USB)
+    Bootloader,
+    /// Reboot into the bootloader and flash an ELF, works as the cargo runner
+    Flash {
+        elf: PathBuf,
+        /// Print the board's serial output afterwards
+        #[arg(long)]
+        serial: bool,
+    },
+}
+
+#[derive(Args)]
+struct Look {
+    #[arg(long, value_enum, default_value_t = Fit::Contain)]
+    fit: Fit,
+    /// Round to the panel's eight colors instead of dithering
+    #[arg(long)]
+    no_dither: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Param {
+    /// Percent
+    Brightness,
+    /// Milliseconds between frames
+    Speed,
+    /// Number, or `random`
+    Seed,
+}
+
+impl Param {
+    fn command(self) -> &'static str {
+        match self {
+            Param::Brightness => "brightness",
+            Param::Speed => "speed",
+            Param::Seed => "seed",
+        }
+    }
+}
+
+// GIFs with no delay play at the speed browsers use
+const DEFAULT_GIF_DELAY: Duration = Duration::from_millis(100);
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let port = cli.port.as_deref();
+
+    match cli.command {
+        Command::List => {
+            let boards = board::list()?;
+            if boards.is_empty() {
+                println!("no boards found");
+            }
+            for b in boards {
+                let product = b.product.as_deref().unwrap_or("unknown firmware");
+                let serial = b.serial.as_deref().unwrap_or("-");
+                let log = if b.log { " (log)" } else { "" };
+                println!("{}\t{serial}\t{product}{log}", b.path);
+            }
+        }
+        Command::PushImage { file, look } => {
+            let image = frame::to_panel(&frame::load(&file)?, look.fit, !look.no_dither);
+            FrameWriter::new(open_stream(port)?).send(&image)?;
+        }
+        Command::PushGif { file, loops, look } => {
+            let gif = File::open(&file).with_context(|| format!("opening {}", file.display()))?;
+            let decoder = GifDecoder::new(BufReader::new(gif))?;
+            let frames: Vec<_> = decoder
+                .into_frames()
+                .collect_frames()?
+                .into_iter()
+                .map(|f| {
+                    let (num, den) = f.delay().numer_denom_ms();
+                    let delay = Duration::from_micros(num as u64 * 1000 / den.max(1) as u64);
+                    let delay = if delay.is_zero() { DEFAULT_GIF_DELAY } else { delay };
+                    (frame::to_panel(f.buffer(), look.fit, !look.no_dither), delay)
+                })
+                .collect();
+            let mut writer = FrameWriter::new(open_stream(port)?);
+            let mut played = 0;
+            while loops == 0 || played < loops {
+                for (image, delay) in &frames {
+                    let start = Instant::now();
+                    writer.send(image)?;
+                    thread::sleep(delay.saturating_sub(start.elapsed()));
+                }
+                played += 1;
+            }
+        }
+        Command::Effect { name } => {
+            let mut shell = Shell::open(port)?;
+            let line = match name {
+                Some(name) => format!("effect {name}"),
+                None => "effect".to_string(),
+            };
+            print!("{}", shell.command(&line)?);
+        }
+        Command::Set { param, value } => {
+            let mut shell = Shell::open(port)?;
+            print!("{}", shell.command(&format!("{} {value}", param.command()))?);
+        }
+        Command::Status => {
+            print!("{}", Shell::open(port)?.command("status")?);
+        }
+        Command::Screenshot { output, scale } => {
+            let text = Shell::open(port)?.command("screenshot")?;
+            let shown = capture::parse(&text)?;
+            let shown = shown.first().context("no frame in the reply")?;
+            capture::save_png(shown, &output, scale)?;
+            println!("frame {} saved to {}", shown.frame, output.display());
+        }
+        Command::Record { output, frames, scale } => {
+            println!("Recording {frames} frames");
+            let text = Shell::open(port)?.command(&format!("record {frames}"))?;
+            let shown = capture::parse(&text)?;
+            capture::save_gif(&shown, &output, scale)?;
+            println!("{} frames saved to {}", shown.len(), output.display());
+        }
+        Command::StreamScreen { region, fps, look } => {
+            let screen = Screen::open()?;
+            let region = region.unwrap_or_else(|| screen.full());
+            let period = Duration::from_secs(1) / fps.max(1);
+            let mut writer = FrameWriter::new(open_stream(port)?);
+            println!("Streaming {region:?} at {fps} fps, Ctrl-C to stop");
+            loop {
+                let start = Instant::now();
+                let image = frame::to_panel(&screen.capture(region)?, look.fit, !look.no_dither);
+                writer.send(&image)?;
+                thread::sleep(period.saturating_sub(start.elapsed()));
+            }
+        }
+        Command::MediaKeys => media_keys::forward()?,
+        Command::Log { elf } => log::follow(board::open_log(port)?, &elf)?,
+        Command::Bootloader => {
+            bootloader::reboot()?;
+            println!("bootloader drive at {}", bootloader::wait_for_drive()?.display());
+        }
+        Command::Flash { elf, serial } => {
+            bootloader::reboot()?;
+            bootloader::wait_for_drive()?;
+            bootloader::flash(&elf, serial)?;
+        }
+    }
+    Ok(())
+}
+
+fn open_stream(port: Option<&str>) -> Result<Box<dyn serialport::SerialPort>> {
+    let mut port = board::open(port, Firmware::Stream)?;
+    // A whole frame has to fit in before the write gives up
+    port.set_timeout(Duration::from_secs(1))?;
+    Ok(port)
+}
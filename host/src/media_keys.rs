@@ -0,0 +1,110 @@
+//! Forwarding the keyboard's media keys to the `shell` firmware's HID
+//! interface. Desktops keep media keys to themselves, so they are grabbed
+//! from X11 (which includes XWayland) and written to the board through
+//! Linux's hidraw.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::process;
+
+use anyhow::{Context, Result, bail};
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::{ConnectionExt, GrabMode, Keycode, ModMask};
+
+// Same as `usb::config` in the firmware, as the kernel's HID_ID spells them
+const HID_ID: &str = "HID_ID=0003:00001209:00000001";
+
+// Reports in the firmware's `hid` module
+const REPORT_LEN: usize = 16;
+const MEDIA_KEY: u8 = 0x0a;
+const MEDIA_KEYS: u8 = 0x0b;
+
+/// X11 keysyms of the media keys and the Consumer page usages the firmware maps
+const KEYS: [(u32, u16); 8] = [
+    (0x1008ff14, 0xcd), // XF86AudioPlay: play/pause
+    (0x1008ff31, 0xb1), // XF86AudioPause
+    (0x1008ff15, 0xb7), // XF86AudioStop
+    (0x1008ff17, 0xb5), // XF86AudioNext
+    (0x1008ff16, 0xb6), // XF86AudioPrev
+    (0x1008ff13, 0xe9), // XF86AudioRaiseVolume
+    (0x1008ff11, 0xea), // XF86AudioLowerVolume
+    (0x1008ff12, 0xe2), // XF86AudioMute
+];
+
+/// Switches the board's media keys on and forwards them until interrupted
+pub fn forward() -> Result<()> {
+    let mut board = open_hidraw()?;
+
+    let (conn, screen) = x11rb::connect(None).context("connecting to the X server, is DISPLAY set?")?;
+    let setup = conn.setup();
+    let root = setup.roots[screen].root;
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+    let per_keycode = mapping.keysyms_per_keycode as usize;
+
+    let mut usages: Vec<(Keycode, u16)> = Vec::new();
+    for (i, keysyms) in mapping.keysyms.chunks(per_keycode.max(1)).enumerate() {
+        let Some(&(_, usage)) = KEYS.iter().find(|(keysym, _)| keysyms.contains(keysym)) else {
+            continue;
+        };
+        let keycode = min + i as u8;
+        conn.grab_key(false, root, ModMask::ANY, keycode, GrabMode::ASYNC, GrabMode::ASYNC)?
+            .check()
+            .context("grabbing the media keys, does another program have them?")?;
+        usages.push((keycode, usage));
+    }
+    if usages.is_empty() {
+        bail!("the keyboard has no media keys");
+    }
+    conn.flush()?;
+
+    // Back to ignoring them however this ends
+    let mut off = board.try_clone()?;
+    ctrlc::set_handler(move || {
+        let _ = write_report(&mut off, &[MEDIA_KEYS, 0]);
+        process::exit(130);
+    })
+    .context("catching Ctrl-C")?;
+    write_report(&mut board, &[MEDIA_KEYS, 1])?;
+    let result = forward_keys(&conn, &usages, &mut board);
+    let _ = write_report(&mut board, &[MEDIA_KEYS, 0]);
+    result
+}
+
+fn forward_keys(conn: &impl Connection, usages: &[(Keycode, u16)], board: &mut File) -> Result<()> {
+    println!("Forwarding media keys, Ctrl-C to stop");
+    loop {
+        let Event::KeyPress(press) = conn.wait_for_event()? else {
+            continue;
+        };
+        if let Some(&(_, usage)) = usages.iter().find(|(keycode, _)| *keycode == press.detail) {
+            let [lo, hi] = usage.to_le_bytes();
+            write_report(board, &[MEDIA_KEY, lo, hi])?;
+        }
+    }
+}
+
+/// The first board's HID interface, only the `shell` firmware has one
+fn open_hidraw() -> Result<File> {
+    let devices = fs::read_dir("/sys/class/hidraw").context("listing hidraw devices, is this Linux?")?;
+    for device in devices {
+        let device = device?;
+        let uevent = fs::read_to_string(device.path().join("device/uevent")).unwrap_or_default();
+        if uevent.lines().any(|l| l == HID_ID) {
+            let path = format!("/dev/{}", device.file_name().to_string_lossy());
+            return OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .with_context(|| format!("opening {path}, a udev rule can give you access"));
+        }
+    }
+    bail!("no board running the `shell` firmware, flash it with `cargo run --release --bin shell`")
+}
+
+fn write_report(board: &mut File, request: &[u8]) -> Result<()> {
+    // hidraw wants the report ID first, 0 as the firmware doesn't number them
+    let mut report = [0; 1 + REPORT_LEN];
+    report[1..1 + request.len()].copy_from_slice(request);
+    board.write_all(&report).context("writing to the board")
+}
@@ -0,0 +1,91 @@
+//! Grabbing part of the screen, X11 only (which includes XWayland windows).
+
+use anyhow::{Context, Result, bail};
+use image::{Rgba, RgbaImage};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, ImageFormat, Window};
+use x11rb::rust_connection::RustConnection;
+
+/// Part of the screen in pixels
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Region {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl std::str::FromStr for Region {
+    type Err = String;
+
+    /// `x,y,width,height`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [x, y, w, h] = parts[..] else {
+            return Err("expected x,y,width,height".into());
+        };
+        let bad = |_| format!("bad region {s:?}");
+        Ok(Region {
+            x: x.parse().map_err(bad)?,
+            y: y.parse().map_err(bad)?,
+            width: w.parse().map_err(bad)?,
+            height: h.parse().map_err(bad)?,
+        })
+    }
+}
+
+pub struct Screen {
+    conn: RustConnection,
+    root: Window,
+    width: u16,
+    height: u16,
+}
+
+impl Screen {
+    pub fn open() -> Result<Screen> {
+        let (conn, screen) = x11rb::connect(None).context("connecting to the X server, is DISPLAY set?")?;
+        let s = &conn.setup().roots[screen];
+        let (root, width, height) = (s.root, s.width_in_pixels, s.height_in_pixels);
+        Ok(Screen {
+            conn,
+            root,
+            width,
+            height,
+        })
+    }
+
+    pub fn full(&self) -> Region {
+        Region {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn capture(&self, region: Region) -> Result<RgbaImage> {
+        let reply = self
+            .conn
+            .get_image(
+                ImageFormat::Z_PIXMAP,
+                self.root,
+                region.x,
+                region.y,
+                region.width,
+                region.height,
+                !0,
+            )?
+            .reply()
+            .context("capturing the screen, is the region on screen?")?;
+        let (w, h) = (region.width as u32, region.height as u32);
+        // 24 bit TrueColor comes as BGRX, like nearly every X server today
+        if reply.data.len() != (w * h * 4) as usize {
+            bail!("unsupported screen format, depth {}", reply.depth);
+        }
+        Ok(RgbaImage::from_fn(w, h, |x, y| {
+            let i = ((y * w + x) * 4) as usize;
+            let p = &reply.data[i..i + 4];
+            Rgba([p[2], p[1], p[0], 255])
+        }))
+    }
+}
@@ -0,0 +1,7 @@
+//! Numerics shared by the effects that don't need the hardware, built for
+//! the host as well so they can be tested with `cargo test` from `math/`.
+
+#![no_std]
+
+pub mod lorenz;
+pub mod num;
@@ -0,0 +1,131 @@
+//!
+//! The Lorenz system and its integrators, generic over the scalar type.
+//!
+//! <https://en.wikipedia.org/wiki/Lorenz_system>
+//!
+
+use crate::num::Real;
+
+// Standard Lorenz parameters
+const SIGMA: f32 = 10.0;
+const RHO: f32 = 28.0;
+const BETA: f32 = 8.0 / 3.0;
+const DT: f32 = 0.01;
+
+/// Lorenz system constants converted to the scalar type once
+#[derive(Clone, Copy)]
+pub struct Params<T> {
+    sigma: T,
+    rho: T,
+    beta: T,
+    dt: T,
+    half_dt: T,
+    sixth: T,
+    two: T,
+}
+
+impl<T: Real> Params<T> {
+    pub fn new() -> Self {
+        Self {
+            sigma: T::from_f32(SIGMA),
+            rho: T::from_f32(RHO),
+            beta: T::from_f32(BETA),
+            dt: T::from_f32(DT),
+            half_dt: T::from_f32(DT / 2.0),
+            sixth: T::from_f32(1.0 / 6.0),
+            two: T::from_f32(2.0),
+        }
+    }
+}
+
+impl<T: Real> Default for Params<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Right hand side of the Lorenz equations
+pub fn deriv<T: Real>([x, y, z]: [T; 3], p: &Params<T>) -> [T; 3] {
+    [p.sigma * (y - x), x * (p.rho - z) - y, x * y - p.beta * z]
+}
+
+fn add_scaled<T: Real>(s: [T; 3], d: [T; 3], h: T) -> [T; 3] {
+    [s[0] + d[0] * h, s[1] + d[1] * h, s[2] + d[2] * h]
+}
+
+/// One forward Euler step
+pub fn euler<T: Real>(s: [T; 3], p: &Params<T>) -> [T; 3] {
+    add_scaled(s, deriv(s, p), p.dt)
+}
+
+/// One classic Runge-Kutta 4 step
+pub fn rk4<T: Real>(s: [T; 3], p: &Params<T>) -> [T; 3] {
+    let k1 = deriv(s, p);
+    let k2 = deriv(add_scaled(s, k1, p.half_dt), p);
+    let k3 = deriv(add_scaled(s, k2, p.half_dt), p);
+    let k4 = deriv(add_scaled(s, k3, p.dt), p);
+
+    let mut out = s;
+    for i in 0..3 {
+        out[i] += (k1[i] + p.two * k2[i] + p.two * k3[i] + k4[i]) * p.dt * p.sixth;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use fixed::types::I16F16;
+
+    use super::*;
+
+    fn distance(a: [f32; 3], b: [I16F16; 3]) -> f32 {
+        let mut d = 0.0;
+        for i in 0..3 {
+            let e = a[i] - b[i].to_f32();
+            d += e * e;
+        }
+        d
+    }
+
+    fn compare(
+        steps: usize,
+        step_f: fn([f32; 3], &Params<f32>) -> [f32; 3],
+        step_x: fn([I16F16; 3], &Params<I16F16>) -> [I16F16; 3],
+    ) {
+        let pf = Params::<f32>::new();
+        let px = Params::<I16F16>::new();
+
+        let mut sf = [0.1f32, 0.0, 0.0];
+        let mut sx = [I16F16::from_f32(0.1), I16F16::ZERO, I16F16::ZERO];
+
+        // Chaos amplifies rounding differences, so only the early part of
+        // the trajectories is expected to stay close.
+        for n in 0..steps {
+            sf = step_f(sf, &pf);
+            sx = step_x(sx, &px);
+            assert!(distance(sf, sx) < 0.25, "diverged at step {}", n);
+        }
+    }
+
+    #[test]
+    fn euler_fixed_tracks_f32() {
+        // DT = 0.01 isn't exact in I16F16, Euler feels that sooner
+        compare(200, euler, euler);
+    }
+
+    #[test]
+    fn rk4_fixed_tracks_f32() {
+        compare(500, rk4, rk4);
+    }
+
+    #[test]
+    fn rk4_fixed_stays_on_attractor() {
+        let p = Params::<I16F16>::new();
+        let mut s = [I16F16::from_f32(0.1), I16F16::ZERO, I16F16::ZERO];
+        for _ in 0..10_000 {
+            s = rk4(s, &p);
+        }
+        let [x, y, z] = s.map(|v| v.to_f32());
+        assert!(x.abs() < 30.0 && y.abs() < 40.0 && (0.0..60.0).contains(&z));
+    }
+}
@@ -0,0 +1,86 @@
+//!
+//! Arithmetic shared by the two scalar types of the physics effects,
+//! soft-float `f32` and `I16F16` fixed point. The firmware picks one with
+//! its `fixed-math` feature.
+//!
+
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
+use fixed::types::I16F16;
+
+/// Arithmetic the integrators need, implemented by both `f32` and `I16F16`
+pub trait Real:
+    Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + AddAssign
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+
+    #[inline]
+    fn abs(self) -> Self {
+        if self < Self::ZERO { -self } else { self }
+    }
+
+    /// Conversion for constants, avoid calling it in hot loops
+    fn from_f32(v: f32) -> Self;
+
+    fn from_i32(v: i32) -> Self;
+
+    fn to_f32(self) -> f32;
+
+    /// Truncates towards zero
+    fn to_i32(self) -> i32;
+}
+
+impl Real for f32 {
+    const ZERO: Self = 0.0;
+
+    #[inline]
+    fn from_f32(v: f32) -> Self {
+        v
+    }
+
+    #[inline]
+    fn from_i32(v: i32) -> Self {
+        v as f32
+    }
+
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    #[inline]
+    fn to_i32(self) -> i32 {
+        self as i32
+    }
+}
+
+impl Real for I16F16 {
+    const ZERO: Self = I16F16::ZERO;
+
+    #[inline]
+    fn from_f32(v: f32) -> Self {
+        I16F16::saturating_from_num(v)
+    }
+
+    #[inline]
+    fn from_i32(v: i32) -> Self {
+        I16F16::saturating_from_num(v)
+    }
+
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self.to_num()
+    }
+
+    #[inline]
+    fn to_i32(self) -> i32 {
+        self.round_to_zero().to_num()
+    }
+}
@@ -1,6 +1,7 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* The upper megabyte is the `drive` firmware's USB drive */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 1024K - 0x100
 
     /* Pick one of the two options for RAM layout     */
 
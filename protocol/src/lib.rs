@@ -0,0 +1,8 @@
+//! Parsers for what the firmwares receive from the host and the network,
+//! built for the host as well so they can be tested with `cargo test` from
+//! `protocol/`.
+
+#![no_std]
+
+pub mod lighting;
+pub mod stream;
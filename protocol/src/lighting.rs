@@ -0,0 +1,375 @@
+//!
+//! Packets of the lighting control protocols over UDP: Art-Net, E1.31 (sACN)
+//! and DDP.
+//!
+//! DMX universes carry 170 pixels each, the last two channels unused like on
+//! most pixel controllers, so the 1024 pixels of the panel take 7 universes.
+//! DDP addresses the 3072 channels directly.
+//!
+
+pub const ARTNET_PORT: u16 = 6454;
+pub const SACN_PORT: u16 = 5568;
+pub const DDP_PORT: u16 = 4048;
+
+const WIDTH: usize = 32;
+const HEIGHT: usize = 32;
+pub const CHANNELS: usize = WIDTH * HEIGHT * 3;
+
+pub const PIXELS_PER_UNIVERSE: usize = 170;
+pub const UNIVERSES: usize = (WIDTH * HEIGHT).div_ceil(PIXELS_PER_UNIVERSE);
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const ARTNET_OP_POLL: u16 = 0x2000;
+const ARTNET_OP_POLL_REPLY: u16 = 0x2100;
+const ARTNET_OP_DMX: u16 = 0x5000;
+const ARTNET_OP_SYNC: u16 = 0x5200;
+pub const ARTNET_POLL_REPLY_LEN: usize = 239;
+
+const SACN_ID: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const SACN_ROOT_DATA: u32 = 0x04;
+const SACN_ROOT_EXTENDED: u32 = 0x08;
+const SACN_FRAMING_DATA: u32 = 0x02;
+const SACN_FRAMING_SYNC: u32 = 0x01;
+const SACN_OPTION_PREVIEW: u8 = 0x80;
+
+const DDP_VERSION_MASK: u8 = 0xc0;
+const DDP_VERSION_1: u8 = 0x40;
+const DDP_FLAG_PUSH: u8 = 0x01;
+const DDP_FLAG_QUERY: u8 = 0x02;
+const DDP_FLAG_TIMECODE: u8 = 0x10;
+const DDP_TYPE_UNDEFINED: u8 = 0x00;
+const DDP_TYPE_RGB8: u8 = 0x0b;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    ArtNet,
+    Sacn,
+    Ddp,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 3] = [Protocol::ArtNet, Protocol::Sacn, Protocol::Ddp];
+
+    pub fn port(self) -> u16 {
+        match self {
+            Protocol::ArtNet => ARTNET_PORT,
+            Protocol::Sacn => SACN_PORT,
+            Protocol::Ddp => DDP_PORT,
+        }
+    }
+
+    /// None for anything that isn't meant for us or is malformed
+    pub fn parse(self, data: &[u8]) -> Option<Packet<'_>> {
+        match self {
+            Protocol::ArtNet => parse_artnet(data),
+            Protocol::Sacn => parse_sacn(data),
+            Protocol::Ddp => parse_ddp(data),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Packet<'a> {
+    /// DMX channels of one universe, held for a sync packet if `synced`
+    Dmx {
+        protocol: Protocol,
+        universe: u16,
+        data: &'a [u8],
+        synced: bool,
+    },
+    /// Show the held universes
+    Sync,
+    /// Art-Net discovery, answer with [`artnet_poll_reply`]
+    Poll,
+    /// Channels from `offset` on, shown once `push` is set
+    Ddp { offset: usize, data: &'a [u8], push: bool },
+}
+
+fn u16_be(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_be(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+pub fn parse_artnet(data: &[u8]) -> Option<Packet<'_>> {
+    if data.get(..8)? != ARTNET_ID {
+        return None;
+    }
+    match u16::from_le_bytes([*data.get(8)?, *data.get(9)?]) {
+        ARTNET_OP_POLL => Some(Packet::Poll),
+        ARTNET_OP_SYNC => Some(Packet::Sync),
+        ARTNET_OP_DMX => {
+            // 15 bit port address from SubUni and Net
+            let universe = u16::from_le_bytes([*data.get(14)?, *data.get(15)? & 0x7f]);
+            let len = u16_be(data, 16)? as usize;
+            Some(Packet::Dmx {
+                protocol: Protocol::ArtNet,
+                universe,
+                data: data.get(18..18 + len)?,
+                synced: false,
+            })
+        }
+        _ => None,
+    }
+}
+
+pub fn parse_sacn(data: &[u8]) -> Option<Packet<'_>> {
+    if u16_be(data, 0)? != 0x0010 || data.get(4..16)? != SACN_ID {
+        return None;
+    }
+    match (u32_be(data, 18)?, u32_be(data, 40)?) {
+        (SACN_ROOT_DATA, SACN_FRAMING_DATA) => {
+            let options = *data.get(112)?;
+            // Start code 0 is dimmer data, anything else is not for pixels
+            if options & SACN_OPTION_PREVIEW != 0 || *data.get(125)? != 0 {
+                return None;
+            }
+            let count = u16_be(data, 123)? as usize;
+            Some(Packet::Dmx {
+                protocol: Protocol::Sacn,
+                universe: u16_be(data, 113)?,
+                data: data.get(126..125 + count)?,
+                synced: u16_be(data, 109)? != 0,
+            })
+        }
+        (SACN_ROOT_EXTENDED, SACN_FRAMING_SYNC) => Some(Packet::Sync),
+        _ => None,
+    }
+}
+
+pub fn parse_ddp(data: &[u8]) -> Option<Packet<'_>> {
+    let flags = *data.first()?;
+    if flags & DDP_VERSION_MASK != DDP_VERSION_1 || flags & DDP_FLAG_QUERY != 0 {
+        return None;
+    }
+    if !matches!(*data.get(2)?, DDP_TYPE_UNDEFINED | DDP_TYPE_RGB8) {
+        return None;
+    }
+    let header = if flags & DDP_FLAG_TIMECODE != 0 { 14 } else { 10 };
+    let len = u16_be(data, 8)? as usize;
+    Some(Packet::Ddp {
+        offset: u32_be(data, 4)? as usize,
+        data: data.get(header..header + len)?,
+        push: flags & DDP_FLAG_PUSH != 0,
+    })
+}
+
+/// First universe and port count of every ArtPollReply page. The ports on a
+/// page share Net and SubNet, so a page also ends where the SubNet changes.
+fn artnet_pages(first_universe: u16) -> impl Iterator<Item = (u16, usize)> {
+    let mut universe = first_universe;
+    let mut left = UNIVERSES;
+    core::iter::from_fn(move || {
+        if left == 0 {
+            return None;
+        }
+        let ports = left.min(4).min(16 - (universe & 0x0f) as usize);
+        let page = (universe, ports);
+        universe = universe.wrapping_add(ports as u16);
+        left -= ports;
+        Some(page)
+    })
+}
+
+/// Replies [`artnet_poll_reply`] answers a poll with
+pub fn artnet_page_count(first_universe: u16) -> u8 {
+    artnet_pages(first_universe).count() as u8
+}
+
+/// Describes one page of up to four output universes, Art-Net nodes with
+/// more ports send a reply per page
+pub fn artnet_poll_reply(ip: [u8; 4], mac: [u8; 6], first_universe: u16, page: u8) -> [u8; ARTNET_POLL_REPLY_LEN] {
+    let mut r = [0; ARTNET_POLL_REPLY_LEN];
+    let (first, ports) = artnet_pages(first_universe)
+        .nth(page as usize)
+        .unwrap_or((first_universe, 0));
+
+    r[..8].copy_from_slice(ARTNET_ID);
+    r[8..10].copy_from_slice(&ARTNET_OP_POLL_REPLY.to_le_bytes());
+    r[10..14].copy_from_slice(&ip);
+    r[14..16].copy_from_slice(&ARTNET_PORT.to_le_bytes());
+    // Net and SubNet, shared by the ports on this page
+    r[18] = (first >> 8) as u8 & 0x7f;
+    r[19] = (first >> 4) as u8 & 0x0f;
+    // Indicators normal, universes set from the network
+    r[23] = 0xd0;
+    copy_str(&mut r[26..44], "LED matrix");
+    copy_str(&mut r[44..108], "32x32 RGB LED matrix, 170 pixels per universe");
+    copy_str(&mut r[108..172], "#0001 [0000] Power On Tests successful");
+    r[173] = ports as u8;
+    for port in 0..ports {
+        // DMX512 output
+        r[174 + port] = 0x80;
+        // Data is being transmitted
+        r[182 + port] = 0x80;
+        r[190 + port] = (first + port as u16) as u8 & 0x0f;
+    }
+    // StNode
+    r[200] = 0x00;
+    r[201..207].copy_from_slice(&mac);
+    r[207..211].copy_from_slice(&ip);
+    r[211] = page + 1;
+    // Supports 15 bit port addresses
+    r[212] = 0x08;
+    r
+}
+
+fn copy_str(dst: &mut [u8], s: &str) {
+    // Keeps a NUL at the end
+    let len = s.len().min(dst.len() - 1);
+    dst[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RGB: [u8; 6] = [255, 0, 0, 0, 0, 255];
+
+    fn artnet_dmx(net: u8, sub_uni: u8) -> [u8; 18 + 6] {
+        let mut p = [0; 18 + 6];
+        p[..8].copy_from_slice(ARTNET_ID);
+        p[8..10].copy_from_slice(&ARTNET_OP_DMX.to_le_bytes());
+        // Protocol version 14
+        p[11] = 14;
+        p[14] = sub_uni;
+        p[15] = net;
+        p[16..18].copy_from_slice(&(RGB.len() as u16).to_be_bytes());
+        p[18..].copy_from_slice(&RGB);
+        p
+    }
+
+    fn sacn(root: u32, framing: u32) -> [u8; 126 + 6] {
+        let mut p = [0; 126 + 6];
+        p[..2].copy_from_slice(&0x0010u16.to_be_bytes());
+        p[4..16].copy_from_slice(SACN_ID);
+        p[18..22].copy_from_slice(&root.to_be_bytes());
+        p[40..44].copy_from_slice(&framing.to_be_bytes());
+        // Priority
+        p[108] = 100;
+        p[113..115].copy_from_slice(&3u16.to_be_bytes());
+        // DMP set property, one byte addresses
+        p[117] = 0x02;
+        p[118] = 0xa1;
+        p[121..123].copy_from_slice(&1u16.to_be_bytes());
+        // Start code and channels
+        p[123..125].copy_from_slice(&(1 + RGB.len() as u16).to_be_bytes());
+        p[126..].copy_from_slice(&RGB);
+        p
+    }
+
+    fn ddp(flags: u8, header: usize) -> [u8; 14 + 6] {
+        let mut p = [0; 14 + 6];
+        p[0] = flags;
+        p[2] = DDP_TYPE_RGB8;
+        p[3] = 1;
+        p[4..8].copy_from_slice(&300u32.to_be_bytes());
+        p[8..10].copy_from_slice(&(RGB.len() as u16).to_be_bytes());
+        p[header..header + RGB.len()].copy_from_slice(&RGB);
+        p
+    }
+
+    #[test]
+    fn artnet_dmx_packet() {
+        assert_eq!(
+            parse_artnet(&artnet_dmx(0x01, 0x23)),
+            Some(Packet::Dmx {
+                protocol: Protocol::ArtNet,
+                universe: 0x0123,
+                data: &RGB,
+                synced: false,
+            })
+        );
+        let mut short = artnet_dmx(0, 0);
+        short[17] += 1;
+        assert_eq!(parse_artnet(&short), None);
+    }
+
+    #[test]
+    fn artnet_poll_and_sync() {
+        let mut p = [0; 14];
+        p[..8].copy_from_slice(ARTNET_ID);
+        p[8..10].copy_from_slice(&ARTNET_OP_POLL.to_le_bytes());
+        assert_eq!(parse_artnet(&p), Some(Packet::Poll));
+        p[8..10].copy_from_slice(&ARTNET_OP_SYNC.to_le_bytes());
+        assert_eq!(parse_artnet(&p), Some(Packet::Sync));
+        p[0] = b'a';
+        assert_eq!(parse_artnet(&p), None);
+    }
+
+    #[test]
+    fn artnet_poll_reply_pages() {
+        // 7 universes from 14 on: 14-15, 16-19 and 20 across the SubNets
+        assert_eq!(artnet_page_count(14), 3);
+        for (page, first, ports) in [(0, 14, 2), (1, 16, 4), (2, 20, 1)] {
+            let r = artnet_poll_reply([10, 0, 0, 2], [2, 0, 0, 0, 0, 1], 14, page);
+            assert_eq!(&r[..10], b"Art-Net\0\x00\x21");
+            assert_eq!(r[19], (first >> 4) as u8);
+            assert_eq!(r[173], ports);
+            for port in 0..ports as usize {
+                assert_eq!(r[190 + port], (first + port as u16) as u8 & 0x0f);
+            }
+            assert_eq!(r[211], page + 1);
+        }
+    }
+
+    #[test]
+    fn sacn_data_packet() {
+        let mut p = sacn(SACN_ROOT_DATA, SACN_FRAMING_DATA);
+        let dmx = |synced| {
+            Some(Packet::Dmx {
+                protocol: Protocol::Sacn,
+                universe: 3,
+                data: &RGB,
+                synced,
+            })
+        };
+        assert_eq!(parse_sacn(&p), dmx(false));
+        p[109..111].copy_from_slice(&7u16.to_be_bytes());
+        assert_eq!(parse_sacn(&p), dmx(true));
+    }
+
+    #[test]
+    fn sacn_skips_preview_and_other_start_codes() {
+        let mut p = sacn(SACN_ROOT_DATA, SACN_FRAMING_DATA);
+        p[112] = SACN_OPTION_PREVIEW;
+        assert_eq!(parse_sacn(&p), None);
+        let mut p = sacn(SACN_ROOT_DATA, SACN_FRAMING_DATA);
+        p[125] = 0xdd;
+        assert_eq!(parse_sacn(&p), None);
+    }
+
+    #[test]
+    fn sacn_sync_packet() {
+        assert_eq!(
+            parse_sacn(&sacn(SACN_ROOT_EXTENDED, SACN_FRAMING_SYNC)),
+            Some(Packet::Sync)
+        );
+        assert_eq!(parse_sacn(&sacn(SACN_ROOT_EXTENDED, SACN_FRAMING_DATA)), None);
+    }
+
+    #[test]
+    fn ddp_packet() {
+        let packet = |push| {
+            Some(Packet::Ddp {
+                offset: 300,
+                data: &RGB,
+                push,
+            })
+        };
+        assert_eq!(parse_ddp(&ddp(DDP_VERSION_1, 10)), packet(false));
+        assert_eq!(parse_ddp(&ddp(DDP_VERSION_1 | DDP_FLAG_PUSH, 10)), packet(true));
+        // The timecode comes between the header and the data
+        assert_eq!(parse_ddp(&ddp(DDP_VERSION_1 | DDP_FLAG_TIMECODE, 14)), packet(false));
+    }
+
+    #[test]
+    fn ddp_skips_queries_and_other_versions() {
+        assert_eq!(parse_ddp(&ddp(DDP_VERSION_1 | DDP_FLAG_QUERY, 10)), None);
+        assert_eq!(parse_ddp(&ddp(0x80, 10)), None);
+    }
+}
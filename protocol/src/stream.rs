@@ -0,0 +1,649 @@
+//!
+//! Frames pushed from a host, decoded straight into a display buffer.
+//!
+//! Every frame is a 12 byte header followed by the pixels row by row:
+//!
+//! | bytes | field                                              |
+//! |-------|----------------------------------------------------|
+//! | 0..4  | magic `LMFR`                                       |
+//! | 4     | pixel format, 0 = Rgb555 little endian, 1 = Rgb888 |
+//! | 5     | width                                              |
+//! | 6     | height                                             |
+//! | 7     | reserved, 0                                        |
+//! | 8..12 | frame counter, u32 little endian                   |
+//!
+//! Frames smaller than the display go in the top left corner, bigger ones are
+//! cropped.
+//!
+//! For desktop LED software the decoder also understands the two common LED
+//! strip protocols, told apart by their first bytes:
+//!
+//! - Adalight: `Ada`, LED count - 1 as u16 big endian, checksum
+//!   `hi ^ lo ^ 0x55`, then RGB for every LED.
+//! - TPM2: `0xc9`, packet type (`0xda` for data), payload size as u16 big
+//!   endian, the RGB payload and `0x36`.
+//!
+//! Strips have no width, their LEDs are laid out on the panel by [`Layout`].
+//! Anything that doesn't start a frame is skipped until something does.
+//!
+
+use embedded_graphics::{
+    pixelcolor::{Rgb555, Rgb888, raw::RawU16},
+    prelude::*,
+};
+
+pub const MAGIC: [u8; 4] = *b"LMFR";
+
+pub const HEADER_LEN: usize = 12;
+
+const ADALIGHT_MAGIC: [u8; 3] = *b"Ada";
+const ADALIGHT_HEADER_LEN: usize = 6;
+
+const TPM2_START: u8 = 0xc9;
+const TPM2_DATA: u8 = 0xda;
+const TPM2_END: u8 = 0x36;
+const TPM2_HEADER_LEN: usize = 4;
+
+/// Adalight hosts wait for this before they start sending
+pub const ADALIGHT_GREETING: &[u8] = b"Ada\n";
+
+// Strips are mapped onto rows this wide
+const WIDTH: u8 = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PixelFormat {
+    Rgb555,
+    Rgb888,
+}
+
+impl PixelFormat {
+    pub fn from_u8(value: u8) -> Option<PixelFormat> {
+        match value {
+            0 => Some(PixelFormat::Rgb555),
+            1 => Some(PixelFormat::Rgb888),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb555 => 2,
+            PixelFormat::Rgb888 => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameHeader {
+    pub format: PixelFormat,
+    pub width: u8,
+    pub height: u8,
+    pub frame: u32,
+}
+
+impl FrameHeader {
+    pub fn parse(bytes: &[u8; HEADER_LEN]) -> Option<FrameHeader> {
+        if bytes[..4] != MAGIC || bytes[5] == 0 || bytes[6] == 0 {
+            return None;
+        }
+        Some(FrameHeader {
+            format: PixelFormat::from_u8(bytes[4])?,
+            width: bytes[5],
+            height: bytes[6],
+            frame: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        })
+    }
+
+    pub fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let f = self.frame.to_le_bytes();
+        let format = match self.format {
+            PixelFormat::Rgb555 => 0,
+            PixelFormat::Rgb888 => 1,
+        };
+        [
+            MAGIC[0],
+            MAGIC[1],
+            MAGIC[2],
+            MAGIC[3],
+            format,
+            self.width,
+            self.height,
+            0,
+            f[0],
+            f[1],
+            f[2],
+            f[3],
+        ]
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// `LMFR` frames
+    Native,
+    Adalight,
+    Tpm2,
+}
+
+/// How LED strip pixels are laid out on the panel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Layout {
+    /// Every row left to right, top to bottom
+    Rows,
+    /// Zigzag, odd rows run right to left like a snaking strip
+    Serpentine,
+}
+
+/// What the decoder made of a complete frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Frame {
+    Native(FrameHeader),
+    Adalight { leds: usize },
+    Tpm2 { leds: usize },
+}
+
+impl Frame {
+    pub fn protocol(&self) -> Protocol {
+        match self {
+            Frame::Native(_) => Protocol::Native,
+            Frame::Adalight { .. } => Protocol::Adalight,
+            Frame::Tpm2 { .. } => Protocol::Tpm2,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    /// Waiting for the first byte of a frame
+    Sync,
+    Header {
+        protocol: Protocol,
+        len: usize,
+    },
+    Pixels {
+        frame: Frame,
+        format: PixelFormat,
+        width: u8,
+        serpentine: bool,
+        index: usize,
+        count: usize,
+    },
+    /// TPM2 payload that isn't pixels, `frame` is shown after the end byte
+    Skip {
+        remaining: usize,
+        frame: Option<Frame>,
+    },
+    End {
+        frame: Option<Frame>,
+    },
+}
+
+/// Feed it whatever arrives, in chunks of any size
+pub struct FrameDecoder {
+    state: State,
+    layout: Layout,
+    header: [u8; HEADER_LEN],
+    // Bytes of a pixel split between two chunks
+    pixel: [u8; 3],
+    pixel_len: usize,
+    last_frame: Option<u32>,
+    dropped: u32,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder {
+    pub const fn new() -> Self {
+        Self::with_layout(Layout::Rows)
+    }
+
+    pub const fn with_layout(layout: Layout) -> Self {
+        Self {
+            state: State::Sync,
+            layout,
+            header: [0; HEADER_LEN],
+            pixel: [0; 3],
+            pixel_len: 0,
+            last_frame: None,
+            dropped: 0,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Applies from the next strip frame
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    /// Forgets a partial frame, e.g. when the host reconnects
+    pub fn reset(&mut self) {
+        self.state = State::Sync;
+        self.pixel_len = 0;
+        self.last_frame = None;
+    }
+
+    /// Native frames skipped by the host according to the frame counter
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Draws the pixels in `data` into `target`. Stops after the last pixel of
+    /// a frame and returns it, along with how much of `data` was used so the
+    /// rest can be fed once the buffers are swapped.
+    pub fn feed<D>(&mut self, data: &[u8], target: &mut D) -> (usize, Option<Frame>)
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        for (i, &byte) in data.iter().enumerate() {
+            if let Some(frame) = self.byte(byte, target) {
+                return (i + 1, Some(frame));
+            }
+        }
+        (data.len(), None)
+    }
+
+    fn byte<D>(&mut self, byte: u8, target: &mut D) -> Option<Frame>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        match self.state {
+            State::Sync => {
+                let protocol = match byte {
+                    b if b == MAGIC[0] => Protocol::Native,
+                    b if b == ADALIGHT_MAGIC[0] => Protocol::Adalight,
+                    TPM2_START => Protocol::Tpm2,
+                    _ => return None,
+                };
+                self.header[0] = byte;
+                self.state = State::Header { protocol, len: 1 };
+                None
+            }
+            State::Header { protocol, len } => {
+                let (magic, header_len): (&[u8], _) = match protocol {
+                    Protocol::Native => (&MAGIC, HEADER_LEN),
+                    Protocol::Adalight => (&ADALIGHT_MAGIC, ADALIGHT_HEADER_LEN),
+                    Protocol::Tpm2 => (&[TPM2_START], TPM2_HEADER_LEN),
+                };
+                if len < magic.len() && byte != magic[len] {
+                    // Not a frame after all, but this byte might start one
+                    self.state = State::Sync;
+                    return self.byte(byte, target);
+                }
+                self.header[len] = byte;
+                if len + 1 < header_len {
+                    self.state = State::Header { protocol, len: len + 1 };
+                    return None;
+                }
+                self.state = self.start(protocol).unwrap_or(State::Sync);
+                if let State::Pixels { .. } = self.state {
+                    let _ = target.clear(Rgb555::BLACK);
+                    self.pixel_len = 0;
+                }
+                None
+            }
+            State::Pixels {
+                frame,
+                format,
+                width,
+                serpentine,
+                index,
+                count,
+            } => {
+                self.pixel[self.pixel_len] = byte;
+                self.pixel_len += 1;
+                if self.pixel_len < format.bytes_per_pixel() {
+                    return None;
+                }
+                self.pixel_len = 0;
+
+                let color = match format {
+                    PixelFormat::Rgb555 => {
+                        Rgb555::from(RawU16::new(u16::from_le_bytes([self.pixel[0], self.pixel[1]])))
+                    }
+                    PixelFormat::Rgb888 => Rgb888::new(self.pixel[0], self.pixel[1], self.pixel[2]).into(),
+                };
+                let y = index / width as usize;
+                let mut x = index % width as usize;
+                if serpentine && y % 2 == 1 {
+                    x = width as usize - 1 - x;
+                }
+                let _ = target.draw_iter(core::iter::once(Pixel(Point::new(x as i32, y as i32), color)));
+
+                if index + 1 < count {
+                    self.state = State::Pixels {
+                        frame,
+                        format,
+                        width,
+                        serpentine,
+                        index: index + 1,
+                        count,
+                    };
+                    return None;
+                }
+                match frame {
+                    Frame::Tpm2 { .. } => {
+                        // Payloads that aren't a whole number of pixels
+                        let remaining = self.tpm2_size() - count * 3;
+                        self.state = State::Skip {
+                            remaining,
+                            frame: Some(frame),
+                        };
+                        self.skip_done()
+                    }
+                    _ => {
+                        self.state = State::Sync;
+                        self.finish(frame)
+                    }
+                }
+            }
+            State::Skip { remaining, frame } => {
+                self.state = State::Skip {
+                    remaining: remaining - 1,
+                    frame,
+                };
+                self.skip_done()
+            }
+            State::End { frame } => {
+                self.state = State::Sync;
+                // A missing end byte means the frame is garbage
+                if byte != TPM2_END {
+                    return self.byte(byte, target);
+                }
+                frame
+            }
+        }
+    }
+
+    /// Where a complete header leads, None if it's invalid
+    fn start(&self, protocol: Protocol) -> Option<State> {
+        let strip = |frame, count| State::Pixels {
+            frame,
+            format: PixelFormat::Rgb888,
+            width: WIDTH,
+            serpentine: self.layout == Layout::Serpentine,
+            index: 0,
+            count,
+        };
+        let h = &self.header;
+        match protocol {
+            Protocol::Native => {
+                let header = FrameHeader::parse(h)?;
+                Some(State::Pixels {
+                    frame: Frame::Native(header),
+                    format: header.format,
+                    width: header.width,
+                    serpentine: false,
+                    index: 0,
+                    count: header.pixels(),
+                })
+            }
+            Protocol::Adalight => {
+                if h[5] != h[3] ^ h[4] ^ 0x55 {
+                    return None;
+                }
+                let leds = u16::from_be_bytes([h[3], h[4]]) as usize + 1;
+                Some(strip(Frame::Adalight { leds }, leds))
+            }
+            Protocol::Tpm2 => {
+                let size = self.tpm2_size();
+                let leds = size / 3;
+                if h[1] != TPM2_DATA || leds == 0 {
+                    // Commands and such, skipped along with anything they carry
+                    return Some(match size {
+                        0 => State::End { frame: None },
+                        _ => State::Skip {
+                            remaining: size,
+                            frame: None,
+                        },
+                    });
+                }
+                Some(strip(Frame::Tpm2 { leds }, leds))
+            }
+        }
+    }
+
+    fn tpm2_size(&self) -> usize {
+        u16::from_be_bytes([self.header[2], self.header[3]]) as usize
+    }
+
+    fn skip_done(&mut self) -> Option<Frame> {
+        if let State::Skip { remaining: 0, frame } = self.state {
+            self.state = State::End { frame };
+        }
+        None
+    }
+
+    fn finish(&mut self, frame: Frame) -> Option<Frame> {
+        if let Frame::Native(header) = frame {
+            if let Some(last) = self.last_frame {
+                // The counter wraps around, a jump back is a restarted host, not drops
+                let gap = header.frame.wrapping_sub(last).wrapping_sub(1);
+                if gap < u32::MAX / 2 {
+                    self.dropped = self.dropped.saturating_add(gap);
+                }
+            }
+            self.last_frame = Some(header.frame);
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    fn display() -> MockDisplay<Rgb555> {
+        let mut display = MockDisplay::new();
+        display.set_allow_overdraw(true);
+        display
+    }
+
+    fn rgb(r: u8, g: u8, b: u8) -> Option<Rgb555> {
+        Some(Rgb888::new(r, g, b).into())
+    }
+
+    fn native(format: PixelFormat, width: u8, height: u8, frame: u32) -> FrameHeader {
+        FrameHeader {
+            format,
+            width,
+            height,
+            frame,
+        }
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = native(PixelFormat::Rgb555, 32, 16, 0x01020304);
+        let bytes = header.to_bytes();
+        assert_eq!(bytes, *b"LMFR\x00\x20\x10\x00\x04\x03\x02\x01");
+        assert_eq!(FrameHeader::parse(&bytes), Some(header));
+    }
+
+    #[test]
+    fn native_rgb888_frame() {
+        let header = native(PixelFormat::Rgb888, 2, 2, 7);
+        let mut data = [0; HEADER_LEN + 12 + 2];
+        data[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+        data[HEADER_LEN..HEADER_LEN + 12].copy_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        // The bytes after the frame are left for the next buffer
+        assert_eq!(
+            decoder.feed(&data, &mut display),
+            (HEADER_LEN + 12, Some(Frame::Native(header)))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(255, 0, 0));
+        assert_eq!(display.get_pixel(Point::new(1, 0)), rgb(0, 255, 0));
+        assert_eq!(display.get_pixel(Point::new(0, 1)), rgb(0, 0, 255));
+        assert_eq!(display.get_pixel(Point::new(1, 1)), rgb(255, 255, 255));
+    }
+
+    #[test]
+    fn native_rgb555_split_across_chunks() {
+        let header = native(PixelFormat::Rgb555, 2, 1, 0);
+        let red = RawU16::from(Rgb555::RED).into_inner().to_le_bytes();
+        let blue = RawU16::from(Rgb555::BLUE).into_inner().to_le_bytes();
+        let mut data = [0; HEADER_LEN + 4];
+        data[..HEADER_LEN].copy_from_slice(&header.to_bytes());
+        data[HEADER_LEN..].copy_from_slice(&[red[0], red[1], blue[0], blue[1]]);
+
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        // Splits in the header and in the middle of a pixel
+        for chunk in [
+            &data[..5],
+            &data[5..HEADER_LEN + 1],
+            &data[HEADER_LEN + 1..HEADER_LEN + 3],
+        ] {
+            assert_eq!(decoder.feed(chunk, &mut display), (chunk.len(), None));
+        }
+        assert_eq!(
+            decoder.feed(&data[HEADER_LEN + 3..], &mut display),
+            (1, Some(Frame::Native(header)))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), Some(Rgb555::RED));
+        assert_eq!(display.get_pixel(Point::new(1, 0)), Some(Rgb555::BLUE));
+    }
+
+    #[test]
+    fn native_skips_garbage_and_bad_headers() {
+        let header = native(PixelFormat::Rgb888, 1, 1, 0);
+        let mut data = [0; 4 + HEADER_LEN + HEADER_LEN + 3];
+        // Stray bytes, a false start and a header with a zero width
+        data[..4].copy_from_slice(b"xLMx");
+        let mut bad = header.to_bytes();
+        bad[5] = 0;
+        data[4..4 + HEADER_LEN].copy_from_slice(&bad);
+        data[4 + HEADER_LEN..4 + 2 * HEADER_LEN].copy_from_slice(&header.to_bytes());
+        data[4 + 2 * HEADER_LEN..].copy_from_slice(&[0, 255, 0]);
+
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(&data, &mut display),
+            (data.len(), Some(Frame::Native(header)))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(0, 255, 0));
+    }
+
+    #[test]
+    fn dropped_frames() {
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        let mut feed = |frame| {
+            let mut data = [0; HEADER_LEN + 3];
+            data[..HEADER_LEN].copy_from_slice(&native(PixelFormat::Rgb888, 1, 1, frame).to_bytes());
+            assert!(decoder.feed(&data, &mut display).1.is_some());
+            decoder.dropped()
+        };
+        assert_eq!(feed(u32::MAX - 1), 0);
+        // Two skipped across the wraparound
+        assert_eq!(feed(1), 2);
+        assert_eq!(feed(2), 2);
+        // A restarted host counts from 0 again
+        assert_eq!(feed(0), 2);
+    }
+
+    #[test]
+    fn adalight_frame() {
+        let data = b"Ada\x00\x01\x54\xff\x00\x00\x00\x00\xff";
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(data, &mut display),
+            (data.len(), Some(Frame::Adalight { leds: 2 }))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(255, 0, 0));
+        assert_eq!(display.get_pixel(Point::new(1, 0)), rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn adalight_serpentine() {
+        // 33 LEDs, the last one starts the second row from the right
+        let mut data = [0; ADALIGHT_HEADER_LEN + 33 * 3];
+        data[..ADALIGHT_HEADER_LEN].copy_from_slice(b"Ada\x00\x20\x75");
+        data[ADALIGHT_HEADER_LEN + 32 * 3..].copy_from_slice(&[0, 255, 0]);
+
+        let mut display = display();
+        let mut decoder = FrameDecoder::with_layout(Layout::Serpentine);
+        assert_eq!(
+            decoder.feed(&data, &mut display),
+            (data.len(), Some(Frame::Adalight { leds: 33 }))
+        );
+        assert_eq!(display.get_pixel(Point::new(31, 1)), rgb(0, 255, 0));
+        assert_eq!(display.get_pixel(Point::new(0, 1)), Some(Rgb555::BLACK));
+    }
+
+    #[test]
+    fn adalight_resync() {
+        // A garbage byte, a false start, a bad checksum and a magic cut short
+        // by the real frame
+        let data = b"\x00AdxAda\x00\x00\x00AdAda\x00\x00\x55\x00\xff\x00";
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(data, &mut display),
+            (data.len(), Some(Frame::Adalight { leds: 1 }))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(0, 255, 0));
+    }
+
+    #[test]
+    fn tpm2_frame() {
+        let data = b"\xc9\xda\x00\x06\xff\x00\x00\x00\x00\xff\x36";
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(data, &mut display),
+            (data.len(), Some(Frame::Tpm2 { leds: 2 }))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(255, 0, 0));
+        assert_eq!(display.get_pixel(Point::new(1, 0)), rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn tpm2_skips_partial_pixels_and_commands() {
+        // A command packet, then 7 bytes of payload, the last one not a whole pixel
+        let data = b"\xc9\xc0\x00\x02\x01\x02\x36\xc9\xda\x00\x07\x00\xff\x00\x00\x00\xff\x01\x36";
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(data, &mut display),
+            (data.len(), Some(Frame::Tpm2 { leds: 2 }))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(0, 255, 0));
+        assert_eq!(display.get_pixel(Point::new(1, 0)), rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn tpm2_resync() {
+        // A garbage byte, then a frame whose end byte is missing: it isn't
+        // shown, and the byte in its place starts the next frame
+        let data = b"\x00\xc9\xda\x00\x03\xff\x00\x00\xc9\xda\x00\x03\x00\x00\xff\x36";
+        let mut display = display();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(data, &mut display),
+            (data.len(), Some(Frame::Tpm2 { leds: 1 }))
+        );
+        assert_eq!(display.get_pixel(Point::new(0, 0)), rgb(0, 0, 255));
+    }
+}
@@ -1,10 +1,39 @@
+//! <https://en.wikipedia.org/wiki/Langton%27s_ant>
+//! <https://en.wikipedia.org/wiki/Turmite>
+
 use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use heapless::Vec;
 
 const W: usize = 32;
 const H: usize = 32;
 
-#[derive(Clone, Copy)]
-enum Direction {
+pub const MAX_STATES: usize = 4;
+pub const MAX_COLORS: usize = 16;
+pub const MAX_ANTS: usize = 16;
+
+// Cell color 0 is always black, the rest cycle through this palette
+const PALETTE: [Rgb555; 7] = [
+    Rgb555::WHITE,
+    Rgb555::RED,
+    Rgb555::GREEN,
+    Rgb555::BLUE,
+    Rgb555::YELLOW,
+    Rgb555::CYAN,
+    Rgb555::MAGENTA,
+];
+
+// Trail and ant colors, one per species
+const SPECIES: [Rgb555; 6] = [
+    Rgb555::RED,
+    Rgb555::GREEN,
+    Rgb555::BLUE,
+    Rgb555::YELLOW,
+    Rgb555::CYAN,
+    Rgb555::MAGENTA,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Direction {
     Up,
     Right,
     Down,
@@ -29,18 +58,297 @@ impl Direction {
             Direction::Right => Direction::Up,
         }
     }
+
+    fn u_turn(&self) -> Self {
+        self.turn_right().turn_right()
+    }
+}
+
+/// Relative turn an ant makes before moving
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Turn {
+    None,
+    Right,
+    UTurn,
+    Left,
+}
+
+impl Turn {
+    fn apply(self, dir: Direction) -> Direction {
+        match self {
+            Turn::None => dir,
+            Turn::Right => dir.turn_right(),
+            Turn::UTurn => dir.u_turn(),
+            Turn::Left => dir.turn_left(),
+        }
+    }
+
+    /// Rulestring letter: N, R, U or L
+    fn from_letter(c: u8) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            b'N' => Some(Turn::None),
+            b'R' => Some(Turn::Right),
+            b'U' => Some(Turn::UTurn),
+            b'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    /// Turn code of the {write, turn, next state} turmite notation:
+    /// 1 no turn, 2 right, 4 u-turn, 8 left
+    fn from_code(c: u8) -> Option<Self> {
+        match c {
+            1 => Some(Turn::None),
+            2 => Some(Turn::Right),
+            4 => Some(Turn::UTurn),
+            8 => Some(Turn::Left),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RuleError {
+    /// No colors or no states
+    Empty,
+    /// More than `MAX_COLORS` colors or `MAX_STATES` states
+    TooLarge,
+    /// Rulestring letter other than N, R, U, L
+    BadTurn(u8),
+    /// Written color or next state outside of the table
+    OutOfRange,
+    /// Table rows of different lengths
+    Ragged,
+}
+
+/// What an ant does for a given (state, cell color)
+#[derive(Clone, Copy)]
+struct Rule {
+    write: u8,
+    turn: Turn,
+    next_state: u8,
+}
+
+const NOOP_RULE: Rule = Rule {
+    write: 0,
+    turn: Turn::None,
+    next_state: 0,
+};
+
+/// Turmite state table: (ant state, cell color) -> (write color, turn, next state).
+/// Multi-color Langton's ants are single state turmites.
+#[derive(Clone)]
+pub struct Turmite {
+    rules: [[Rule; MAX_COLORS]; MAX_STATES],
+    states: u8,
+    colors: u8,
+}
+
+impl Turmite {
+    /// Classic 2 color Langton's ant, builds a highway after ~10k steps
+    pub fn langton() -> Self {
+        Self::from_rulestring("RL").unwrap()
+    }
+
+    /// Multi-color Langton's ant, e.g. `RLR`, `LLRR`, `RRLLLRLLLRRR`.
+    /// An ant on a cell of color `i` turns according to letter `i`
+    /// and repaints the cell with color `i + 1`.
+    pub fn from_rulestring(rule: &str) -> Result<Self, RuleError> {
+        let rule = rule.as_bytes();
+        if rule.is_empty() {
+            return Err(RuleError::Empty);
+        }
+        if rule.len() > MAX_COLORS {
+            return Err(RuleError::TooLarge);
+        }
+
+        let mut rules = [[NOOP_RULE; MAX_COLORS]; MAX_STATES];
+        for (c, &l) in rule.iter().enumerate() {
+            rules[0][c] = Rule {
+                write: ((c + 1) % rule.len()) as u8,
+                turn: Turn::from_letter(l).ok_or(RuleError::BadTurn(l))?,
+                next_state: 0,
+            };
+        }
+
+        Ok(Self {
+            rules,
+            states: 1,
+            colors: rule.len() as u8,
+        })
+    }
+
+    /// Full turmite table in the `{{{write, turn, next state}, ...}, ...}`
+    /// notation: one row per state, one `(write, turn, next)` per color,
+    /// turn codes are 1 (none), 2 (right), 4 (u-turn), 8 (left).
+    pub fn from_table(table: &[&[(u8, u8, u8)]]) -> Result<Self, RuleError> {
+        let states = table.len();
+        let colors = table.first().map(|r| r.len()).unwrap_or(0);
+        if states == 0 || colors == 0 {
+            return Err(RuleError::Empty);
+        }
+        if states > MAX_STATES || colors > MAX_COLORS {
+            return Err(RuleError::TooLarge);
+        }
+
+        let mut rules = [[NOOP_RULE; MAX_COLORS]; MAX_STATES];
+        for (s, row) in table.iter().enumerate() {
+            if row.len() != colors {
+                return Err(RuleError::Ragged);
+            }
+            for (c, &(write, turn, next_state)) in row.iter().enumerate() {
+                if write as usize >= colors || next_state as usize >= states {
+                    return Err(RuleError::OutOfRange);
+                }
+                rules[s][c] = Rule {
+                    write,
+                    turn: Turn::from_code(turn).ok_or(RuleError::BadTurn(turn))?,
+                    next_state,
+                };
+            }
+        }
+
+        Ok(Self {
+            rules,
+            states: states as u8,
+            colors: colors as u8,
+        })
+    }
+
+    /// Spiral that grows with Fibonacci-like arm lengths
+    pub fn fibonacci_spiral() -> Self {
+        Self::from_table(&[&[(1, 8, 1), (1, 8, 1)], &[(1, 2, 1), (0, 1, 0)]]).unwrap()
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    pub fn colors(&self) -> u8 {
+        self.colors
+    }
+
+    fn rule(&self, state: u8, color: u8) -> Rule {
+        self.rules[state as usize][color as usize]
+    }
+}
+
+/// Well known rules worth showing, see the Wikipedia pages in the module docs
+pub fn presets() -> [Turmite; 6] {
+    let rs = |r| Turmite::from_rulestring(r).unwrap();
+    [
+        Turmite::langton(),
+        // Grows symmetrically
+        rs("LLRR"),
+        // Fills a growing square
+        rs("LRRRRRLLR"),
+        // Builds a convoluted highway
+        rs("LLRRRLRLRLLR"),
+        // Fills a growing triangle
+        rs("RRLLLRLLLRRR"),
+        Turmite::fibonacci_spiral(),
+    ]
+}
+
+/// Where and how an ant enters the colony
+#[derive(Clone, Copy)]
+pub struct Spawn {
+    pub x: i32,
+    pub y: i32,
+    pub dir: Direction,
+    /// Index into the species colors, ants of a species share a trail color
+    pub species: u8,
+}
+
+/// What happens when an ant steps onto a cell occupied by another ant
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Collision {
+    /// Ants overlap and carry on
+    Ignore,
+    /// Both ants turn around
+    Bounce,
+    /// The moving ant is absorbed by the one already there
+    Merge,
+    /// A new ant of the moving ant's species hatches, heading left of it
+    Reproduce,
+}
+
+/// How cells are colored
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Coloring {
+    /// By the species of the ant that last painted the cell
+    Species,
+    /// By the turmite cell color, shows multi-color rules better
+    Cells,
+}
+
+/// Colony configuration
+#[derive(Clone)]
+pub struct Colony {
+    pub spawns: Vec<Spawn, MAX_ANTS>,
+    pub collision: Collision,
+    /// Steps an ant lives for, `None` for immortal ants.
+    /// The colony respawns once every ant is gone.
+    pub lifespan: Option<u32>,
+}
+
+impl Default for Colony {
+    fn default() -> Self {
+        let mut spawns = Vec::new();
+        for s in [
+            Spawn { x: 10, y: 10, dir: Direction::Up, species: 0 },
+            Spawn { x: 20, y: 10, dir: Direction::Right, species: 1 },
+            Spawn { x: 15, y: 20, dir: Direction::Down, species: 2 },
+        ] {
+            let _ = spawns.push(s);
+        }
+        Self {
+            spawns,
+            collision: Collision::Ignore,
+            lifespan: None,
+        }
+    }
 }
 
 struct Ant {
     x: i32,
     y: i32,
     dir: Direction,
-    color: Rgb555,
+    state: u8,
+    species: u8,
+    age: u32,
+}
+
+impl Ant {
+    fn new(s: &Spawn) -> Self {
+        Self {
+            x: s.x.rem_euclid(W as i32),
+            y: s.y.rem_euclid(H as i32),
+            dir: s.dir,
+            state: 0,
+            species: s.species,
+            age: 0,
+        }
+    }
+
+    fn pos(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    fn color(&self) -> Rgb555 {
+        SPECIES[self.species as usize % SPECIES.len()]
+    }
 }
 
 pub struct Ants {
+    // Cell colors, indices into the turmite colors
     grid: [u8; W * H],
-    ants: [Ant; 3],
+    // Species of the ant that last painted each cell
+    owners: [u8; W * H],
+    ants: Vec<Ant, MAX_ANTS>,
+    turmite: Turmite,
+    colony: Colony,
+    coloring: Coloring,
 }
 
 impl Default for Ants {
@@ -51,28 +359,79 @@ impl Default for Ants {
 
 impl Ants {
     pub fn new() -> Self {
-        Self {
+        Self::with_turmite(Turmite::langton())
+    }
+
+    pub fn with_turmite(turmite: Turmite) -> Self {
+        Self::with_colony(turmite, Colony::default())
+    }
+
+    pub fn with_colony(turmite: Turmite, colony: Colony) -> Self {
+        let mut ants = Self {
             grid: [0; W * H],
-            ants: [
-                Ant { x: 10, y: 10, dir: Direction::Up, color: Rgb555::RED },
-                Ant { x: 20, y: 10, dir: Direction::Right, color: Rgb555::GREEN },
-                Ant { x: 15, y: 20, dir: Direction::Down, color: Rgb555::BLUE },
-            ],
+            owners: [0; W * H],
+            ants: Vec::new(),
+            turmite,
+            colony,
+            coloring: Coloring::Species,
+        };
+        ants.respawn();
+        ants
+    }
+
+    /// Swap the rule table, clearing the grid and respawning the colony
+    pub fn set_turmite(&mut self, turmite: Turmite) {
+        self.turmite = turmite;
+        self.reset();
+    }
+
+    /// Swap the colony configuration, clearing the grid
+    pub fn set_colony(&mut self, colony: Colony) {
+        self.colony = colony;
+        self.reset();
+    }
+
+    pub fn set_coloring(&mut self, coloring: Coloring) {
+        self.coloring = coloring;
+    }
+
+    /// Add an ant to the running colony, false if it's full
+    pub fn spawn(&mut self, s: &Spawn) -> bool {
+        self.ants.push(Ant::new(s)).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ants.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.grid.fill(0);
+        self.owners.fill(0);
+        self.respawn();
+    }
+
+    fn respawn(&mut self) {
+        self.ants.clear();
+        for s in self.colony.spawns.iter() {
+            let _ = self.ants.push(Ant::new(s));
         }
     }
 
     pub fn step(&mut self) {
-        for ant in self.ants.iter_mut() {
-            let idx = (ant.y as usize * W + ant.x as usize) % (W * H);
-            let cell = self.grid[idx];
-
-            if cell == 0 {
-                ant.dir = ant.dir.turn_right();
-                self.grid[idx] = 1; // Mark as "on"
-            } else {
-                ant.dir = ant.dir.turn_left();
-                self.grid[idx] = 0; // Mark as "off"
-            }
+        let mut i = 0;
+        while i < self.ants.len() {
+            let ant = &mut self.ants[i];
+            let idx = ant.y as usize * W + ant.x as usize;
+            let rule = self.turmite.rule(ant.state, self.grid[idx]);
+
+            ant.dir = rule.turn.apply(ant.dir);
+            ant.state = rule.next_state;
+            self.grid[idx] = rule.write;
+            self.owners[idx] = ant.species;
 
             // Move ant
             match ant.dir {
@@ -85,6 +444,56 @@ impl Ants {
             // Wrap around
             ant.x = (ant.x + W as i32) % W as i32;
             ant.y = (ant.y + H as i32) % H as i32;
+
+            ant.age = ant.age.saturating_add(1);
+            if self.colony.lifespan.is_some_and(|l| ant.age >= l) {
+                self.ants.swap_remove(i);
+                continue;
+            }
+
+            i += self.collide(i);
+        }
+
+        if self.ants.is_empty() {
+            self.respawn();
+        }
+    }
+
+    /// Apply the collision rule to ant `i` after it moved,
+    /// returns how many ants the step loop should advance past.
+    fn collide(&mut self, i: usize) -> usize {
+        let (x, y) = self.ants[i].pos();
+        let Some(j) = (0..self.ants.len()).find(|&j| j != i && self.ants[j].pos() == (x, y)) else {
+            return 1;
+        };
+
+        match self.colony.collision {
+            Collision::Ignore => 1,
+            Collision::Bounce => {
+                self.ants[i].dir = self.ants[i].dir.u_turn();
+                self.ants[j].dir = self.ants[j].dir.u_turn();
+                1
+            }
+            Collision::Merge => {
+                // swap_remove moves the last ant into slot i, it hasn't moved yet
+                self.ants.swap_remove(i);
+                0
+            }
+            Collision::Reproduce => {
+                let parent = &self.ants[i];
+                let child = Spawn {
+                    x,
+                    y,
+                    dir: parent.dir.turn_left(),
+                    species: parent.species,
+                };
+                // Newborns start moving on the next step
+                if self.ants.insert(i + 1, Ant::new(&child)).is_ok() {
+                    2
+                } else {
+                    1
+                }
+            }
         }
     }
 
@@ -94,16 +503,25 @@ impl Ants {
     {
         // Draw grid
         for i in 0..self.grid.len() {
-            if self.grid[i] != 0 {
+            let c = self.grid[i];
+            if c != 0 {
                 let x = (i % W) as i32;
                 let y = (i / W) as i32;
-                Pixel(Point::new(x, y), Rgb555::WHITE).draw(target)?;
+                let color = match self.coloring {
+                    Coloring::Species => SPECIES[self.owners[i] as usize % SPECIES.len()],
+                    Coloring::Cells => PALETTE[(c as usize - 1) % PALETTE.len()],
+                };
+                Pixel(Point::new(x, y), color).draw(target)?;
             }
         }
 
-        // Draw ants with their colors
+        // Draw ants, white heads stand out from their own trails
         for ant in self.ants.iter() {
-            Pixel(Point::new(ant.x, ant.y), ant.color).draw(target)?;
+            let color = match self.coloring {
+                Coloring::Species => Rgb555::WHITE,
+                Coloring::Cells => ant.color(),
+            };
+            Pixel(Point::new(ant.x, ant.y), color).draw(target)?;
         }
 
         Ok(())
@@ -8,7 +8,10 @@ use core::sync::atomic::Ordering;
 use portable_atomic::AtomicPtr;
 
 use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
-use embassy_adafruit_rpi_2040_uf2_led_matrix::ants::Ants;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::ants::{
+    self, Ants, Coloring, Collision, Colony, Direction, Spawn, Turmite,
+};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::log;
 use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
 use embassy_executor::Executor;
 use embassy_rp::gpio::{Level, Output};
@@ -16,7 +19,7 @@ use embassy_rp::multicore::{spawn_core1, Stack};
 use embassy_sync::once_lock::OnceLock;
 use embassy_time::Timer;
 use static_cell::StaticCell;
-use {defmt_rtt as _, panic_probe as _};
+use panic_probe as _;
 
 static mut CORE1_STACK: Stack<8192> = Stack::new();
 static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
@@ -30,6 +33,8 @@ static LMD_READY: OnceLock<()> = OnceLock::new();
 
 static ANTS: StaticCell<Ants> = StaticCell::new();
 
+const STEPS_PER_RULE: u32 = 6000;
+
 #[embassy_executor::task]
 async fn matrix_task(mut lm: LedMatrix<'static>) {
     LMD_READY.get().await;
@@ -54,10 +59,18 @@ async fn graphics_task() {
     let mut inactive_lmd = LMD2.init(LedMatrixDisplay::new());
     LMD_READY.init(()).unwrap();
 
-    let ants = ANTS.init(Ants::new());
+    let mut colony = Colony {
+        collision: Collision::Bounce,
+        ..Default::default()
+    };
+    let _ = colony.spawns.push(Spawn { x: 5, y: 25, dir: Direction::Left, species: 3 });
+
+    let ants = ANTS.init(Ants::with_colony(Turmite::langton(), colony));
+    let presets = ants::presets();
 
     defmt::info!("Starting Ants simulation");
 
+    let mut tick = 0u32;
     loop {
         inactive_lmd.clear();
         ants.draw(inactive_lmd).unwrap();
@@ -65,6 +78,16 @@ async fn graphics_task() {
 
         ants.step();
 
+        // Cycle through the known rules
+        tick = tick.wrapping_add(1);
+        if tick.is_multiple_of(STEPS_PER_RULE) {
+            let i = (tick / STEPS_PER_RULE) as usize % presets.len();
+            defmt::info!("Switching to rule {}", i);
+            ants.set_turmite(presets[i].clone());
+            // Multi-color rules read better with cells colored by state
+            ants.set_coloring(if presets[i].colors() > 2 { Coloring::Cells } else { Coloring::Species });
+        }
+
         Timer::after_millis(10).await;
     }
 }
@@ -103,6 +126,7 @@ fn main() -> ! {
 
     let executor0 = EXECUTOR0.init(Executor::new());
     executor0.run(|spawner| {
+        log::start(spawner, p.USB);
         spawner.spawn(graphics_task()).unwrap();
     });
 }
@@ -5,14 +5,16 @@
 #![no_std]
 #![no_main]
 
+use embassy_adafruit_rpi_2040_uf2_led_matrix::log;
 use embassy_executor::Spawner;
 use embassy_rp::gpio::{Level, Output};
 use embassy_time::Timer;
-use {defmt_rtt as _, panic_probe as _};
+use panic_probe as _;
 
 #[embassy_executor::main]
-async fn main(_spawner: Spawner) {
+async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
+    log::start(spawner, p.USB);
 
     // Test pins
     let mut pins = [
@@ -0,0 +1,293 @@
+//! LED matrix as a USB drive: copy BMP and GIF files onto it and they play.
+//!
+//! The drive is the upper megabyte of flash, formatted as FAT12 on first boot
+//! with a `CONFIG.TXT` to edit. Playback stops while the host writes and
+//! starts over with the new files once it's done.
+
+#![no_std]
+#![no_main]
+
+use core::convert::Infallible;
+use core::ptr;
+use core::sync::atomic::Ordering;
+use portable_atomic::{AtomicPtr, AtomicU8};
+
+use embassy_adafruit_rpi_2040_uf2_led_matrix::bmp;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::fat::{self, FatError, FileReader, Volume};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::gif::Gif;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::msc::{self, BlockDevice, MassStorageClass};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::playlist::{self, Config, Kind};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::storage::{self, FlashDisk};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::usb::{self, UsbDriver};
+use embassy_executor::Executor;
+use embassy_futures::select::{select, Either};
+use embassy_rp::flash::Flash;
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::multicore::{spawn_core1, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::once_lock::OnceLock;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_deadline, Duration, Instant, Timer};
+use embedded_graphics::prelude::*;
+use static_cell::StaticCell;
+use panic_probe as _;
+
+static mut CORE1_STACK: Stack<8192> = Stack::new();
+static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
+static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
+
+static LMD1: StaticCell<LedMatrixDisplay> = StaticCell::new();
+static LMD2: StaticCell<LedMatrixDisplay> = StaticCell::new();
+
+static ACTIVE_LMD: AtomicPtr<LedMatrixDisplay> = AtomicPtr::new(ptr::null_mut());
+static LMD_READY: OnceLock<()> = OnceLock::new();
+
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(100);
+
+static MSC_STATE: StaticCell<msc::State> = StaticCell::new();
+static DISK: StaticCell<FlashDisk> = StaticCell::new();
+
+const LABEL: &[u8; 11] = b"LED MATRIX ";
+
+// The host is done this long after its last write
+const SETTLE: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum Drive {
+    Writing,
+    /// Written back to flash, safe to read again
+    Settled,
+}
+
+static DRIVE: Signal<CriticalSectionRawMutex, Drive> = Signal::new();
+
+#[embassy_executor::task]
+async fn matrix_task(mut lm: LedMatrix<'static>) {
+    LMD_READY.get().await;
+    defmt::info!("Starting matrix scans");
+
+    loop {
+        let ptr = ACTIVE_LMD.load(Ordering::Relaxed);
+        if !ptr.is_null() {
+            let lmd = unsafe { &*ptr };
+            lmd.run_with_brightness(&mut lm, BRIGHTNESS.load(Ordering::Relaxed));
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn graphics_task() {
+    let lmd1 = LMD1.init(LedMatrixDisplay::new());
+    ACTIVE_LMD.store(lmd1, Ordering::Release);
+
+    let mut inactive_lmd = LMD2.init(LedMatrixDisplay::new());
+    LMD_READY.init(()).unwrap();
+
+    loop {
+        let changed = match Volume::new(storage::contents()) {
+            Ok(volume) => play(&volume, &mut inactive_lmd).await,
+            Err(e) => {
+                defmt::warn!("Can't read the drive: {}, format it as FAT", e);
+                DRIVE.wait().await
+            }
+        };
+        if changed == Drive::Writing {
+            inactive_lmd.clear();
+            swap(&mut inactive_lmd);
+            while DRIVE.wait().await != Drive::Settled {}
+        }
+        defmt::info!("Drive changed");
+    }
+}
+
+/// Plays everything on the drive over and over until it changes
+async fn play(volume: &Volume<'static>, back: &mut &'static mut LedMatrixDisplay) -> Drive {
+    let config = Config::load(volume);
+    defmt::info!("{}", config);
+    BRIGHTNESS.store(config.brightness, Ordering::Relaxed);
+    let seconds = Duration::from_secs(config.seconds as u64);
+
+    loop {
+        let mut shown = false;
+        for entry in volume.files() {
+            let Some(kind) = Kind::of(&entry) else {
+                continue;
+            };
+            defmt::info!("Playing {}", entry);
+            let file = volume.open(&entry);
+            let played = match kind {
+                Kind::Bmp => show_bmp(file, back, seconds).await,
+                Kind::Gif => play_gif(file, back, seconds).await,
+            };
+            match played {
+                Ok(played) => shown |= played,
+                Err(changed) => return changed,
+            }
+        }
+        if !shown {
+            defmt::info!("Nothing to play, copy BMP or GIF files to the drive");
+            return DRIVE.wait().await;
+        }
+    }
+}
+
+/// Whether it could be shown, `Err` when the drive changes meanwhile
+async fn show_bmp(
+    file: FileReader<'static>,
+    back: &mut &'static mut LedMatrixDisplay,
+    seconds: Duration,
+) -> Result<bool, Drive> {
+    back.clear();
+    if let Err(e) = bmp::draw(file, &mut back.color_converted()) {
+        defmt::warn!("Can't show it: {}", e);
+        return Ok(false);
+    }
+    swap(back);
+    wait(seconds).await?;
+    Ok(true)
+}
+
+/// Loops until `seconds` are up, whether anything could be shown
+async fn play_gif(
+    file: FileReader<'static>,
+    back: &mut &'static mut LedMatrixDisplay,
+    seconds: Duration,
+) -> Result<bool, Drive> {
+    let until = Instant::now() + seconds;
+    let mut shown = false;
+    loop {
+        let mut gif = match Gif::new(file.clone()) {
+            Ok(gif) => gif,
+            Err(e) => {
+                defmt::warn!("Can't play it: {}", e);
+                return Ok(shown);
+            }
+        };
+        let mut frames = 0;
+        loop {
+            match gif.next_frame() {
+                Ok(Some(delay_ms)) => {
+                    let Ok::<(), Infallible>(()) = gif.draw(&mut back.color_converted());
+                    swap(back);
+                    shown = true;
+                    frames += 1;
+                    wait(Duration::from_millis(delay_ms as u64)).await?;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    defmt::warn!("Can't play it: {}", e);
+                    return Ok(shown);
+                }
+            }
+        }
+        if frames == 0 || Instant::now() >= until {
+            return Ok(shown);
+        }
+    }
+}
+
+/// `Err` if the drive changes first
+async fn wait(duration: Duration) -> Result<(), Drive> {
+    match select(Timer::after(duration), DRIVE.wait()).await {
+        Either::First(()) => Ok(()),
+        Either::Second(changed) => Err(changed),
+    }
+}
+
+fn swap(back: &mut &'static mut LedMatrixDisplay) {
+    let front = ACTIVE_LMD.swap(&mut **back as *mut _, Ordering::AcqRel);
+    *back = unsafe { &mut *front };
+}
+
+#[embassy_executor::task]
+async fn msc_task(mut class: MassStorageClass<'static, UsbDriver>, disk: &'static mut FlashDisk) {
+    let mut last_write: Option<Instant> = None;
+    loop {
+        let command = match last_write {
+            Some(at) => match with_deadline(at + SETTLE, class.command()).await {
+                Ok(command) => command,
+                Err(_) => {
+                    if let Err(e) = disk.flush() {
+                        defmt::error!("Writing back failed: {}", e);
+                    }
+                    last_write = None;
+                    DRIVE.signal(Drive::Settled);
+                    continue;
+                }
+            },
+            None => class.command().await,
+        };
+        let command = match command {
+            Ok(command) => command,
+            Err(e) => {
+                defmt::warn!("Mass storage read failed: {}", e);
+                continue;
+            }
+        };
+        if command.is_write() {
+            if last_write.is_none() {
+                DRIVE.signal(Drive::Writing);
+            }
+            last_write = Some(Instant::now());
+        }
+        if let Err(e) = class.execute(command, disk).await {
+            defmt::warn!("Mass storage transfer failed: {}", e);
+        }
+    }
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let p = embassy_rp::init(Default::default());
+
+    let m_r1 = Output::new(p.PIN_8, Level::Low);
+    let m_b1 = Output::new(p.PIN_9, Level::Low);
+    let m_r2 = Output::new(p.PIN_11, Level::Low);
+    let m_b2 = Output::new(p.PIN_12, Level::Low);
+    let m_a = Output::new(p.PIN_25, Level::Low);
+    let m_c = Output::new(p.PIN_29, Level::Low);
+    let m_clk = Output::new(p.PIN_13, Level::Low);
+    let m_oe = Output::new(p.PIN_0, Level::Low);
+
+    let m_lat = Output::new(p.PIN_1, Level::Low);
+    let m_d = Output::new(p.PIN_28, Level::Low);
+    let m_b = Output::new(p.PIN_24, Level::Low);
+    let m_g2 = Output::new(p.PIN_10, Level::Low);
+    let m_g1 = Output::new(p.PIN_7, Level::Low);
+
+    let lm = LedMatrix::new(
+        m_r1, m_r2, m_g1, m_g2, m_b1, m_b2, m_clk, m_lat, m_oe, m_a, m_b, m_c, m_d,
+    );
+
+    let disk = DISK.init(FlashDisk::new(Flash::new_blocking(p.FLASH)));
+    if let Err(FatError::Unformatted) = Volume::new(storage::contents()) {
+        defmt::info!("Formatting the drive, {} blocks", disk.block_count());
+        let config = (playlist::CONFIG_NAME, playlist::DEFAULT_CONFIG.as_bytes());
+        if let Err(e) = fat::format(disk, LABEL, config) {
+            defmt::error!("Formatting failed: {}", e);
+        }
+    }
+
+    let driver = usb::driver(p.USB);
+    let mut builder = usb::builder(driver, usb::config("LED matrix drive"));
+    let class = MassStorageClass::new(&mut builder, MSC_STATE.init(msc::State::new()), usb::MAX_PACKET_SIZE);
+    let usb = builder.build();
+
+    spawn_core1(
+        p.CORE1,
+        unsafe { &mut *core::ptr::addr_of_mut!(CORE1_STACK) },
+        move || {
+            let executor1 = EXECUTOR1.init(Executor::new());
+            executor1.run(|spawner| spawner.spawn(matrix_task(lm)).unwrap());
+        },
+    );
+
+    let executor0 = EXECUTOR0.init(Executor::new());
+    executor0.run(|spawner| {
+        spawner.spawn(usb::usb_task(usb)).unwrap();
+        spawner.spawn(msc_task(class, disk)).unwrap();
+        spawner.spawn(graphics_task()).unwrap();
+    });
+}
@@ -9,6 +9,7 @@ use portable_atomic::AtomicPtr;
 
 use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
 use embassy_adafruit_rpi_2040_uf2_led_matrix::gol::Gol;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::log;
 use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
 use embassy_executor::Executor;
 use embassy_rp::gpio::{Level, Output};
@@ -16,7 +17,7 @@ use embassy_rp::multicore::{spawn_core1, Stack};
 use embassy_sync::once_lock::OnceLock;
 use embassy_time::Timer;
 use static_cell::StaticCell;
-use {defmt_rtt as _, panic_probe as _};
+use panic_probe as _;
 
 static mut CORE1_STACK: Stack<8192> = Stack::new();
 static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
@@ -109,6 +110,7 @@ fn main() -> ! {
 
     let executor0 = EXECUTOR0.init(Executor::new());
     executor0.run(|spawner| {
+        log::start(spawner, p.USB);
         spawner.spawn(graphics_task()).unwrap();
     });
 }
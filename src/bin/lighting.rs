@@ -0,0 +1,217 @@
+//! LED matrix as a network pixel fixture driven by Art-Net, sACN or DDP.
+//!
+//! Shows up as a USB Ethernet adapter (CDC-NCM) with the panel at
+//! 192.168.7.2. On Linux `ip addr add 192.168.7.1/24 dev <usb interface>`
+//! and point xLights, a lighting console or any UDP sender at it.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+use core::ptr;
+use core::sync::atomic::Ordering;
+use portable_atomic::AtomicPtr;
+
+use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::lighting::{self, Packet, Pixels, Protocol, UNIVERSES};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::rng;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::usb::{self, UsbDriver};
+use embassy_executor::Executor;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{Ipv4Address, Ipv4Cidr, Stack, StackResources, StaticConfigV4};
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::multicore::{spawn_core1, Stack as CoreStack};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::once_lock::OnceLock;
+use embassy_sync::signal::Signal;
+use embassy_time::Instant;
+use embassy_usb::class::cdc_ncm::embassy_net::{Device, Runner, State as NetState};
+use embassy_usb::class::cdc_ncm::{CdcNcmClass, State};
+use rand::RngCore;
+use static_cell::StaticCell;
+use panic_probe as _;
+
+static mut CORE1_STACK: CoreStack<8192> = CoreStack::new();
+static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
+static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
+
+static LMD1: StaticCell<LedMatrixDisplay> = StaticCell::new();
+static LMD2: StaticCell<LedMatrixDisplay> = StaticCell::new();
+
+static ACTIVE_LMD: AtomicPtr<LedMatrixDisplay> = AtomicPtr::new(ptr::null_mut());
+static LMD_READY: OnceLock<()> = OnceLock::new();
+
+const MTU: usize = 1514;
+
+// Locally administered, the host end of the link gets its own
+const MAC: [u8; 6] = [0x02, 0x4c, 0x45, 0x44, 0x00, 0x02];
+const HOST_MAC: [u8; 6] = [0x02, 0x4c, 0x45, 0x44, 0x00, 0x01];
+const IP: Ipv4Address = Ipv4Address::new(192, 168, 7, 2);
+
+// smoltcp's `iface-max-multicast-group-count-8` in Cargo.toml, a group per sACN universe
+const MULTICAST_GROUPS: usize = 8;
+const _: () = assert!(UNIVERSES <= MULTICAST_GROUPS);
+
+static NCM_STATE: StaticCell<State> = StaticCell::new();
+static NET_STATE: StaticCell<NetState<MTU, 4, 4>> = StaticCell::new();
+static RESOURCES: StaticCell<StackResources<4>> = StaticCell::new();
+
+// Filled by the UDP tasks, drawn into the back buffer when a frame is complete
+static PIXELS: Mutex<CriticalSectionRawMutex, RefCell<Pixels>> = Mutex::new(RefCell::new(Pixels::new()));
+static SHOW: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+#[embassy_executor::task]
+async fn matrix_task(mut lm: LedMatrix<'static>) {
+    LMD_READY.get().await;
+    defmt::info!("Starting matrix scans");
+
+    loop {
+        let ptr = ACTIVE_LMD.load(Ordering::Relaxed);
+        if !ptr.is_null() {
+            let lmd = unsafe { &*ptr };
+            lmd.run(&mut lm);
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn graphics_task() {
+    let lmd1 = LMD1.init(LedMatrixDisplay::new());
+    ACTIVE_LMD.store(lmd1, Ordering::Release);
+
+    let mut inactive_lmd = LMD2.init(LedMatrixDisplay::new());
+    LMD_READY.init(()).unwrap();
+
+    let mut frames = 0u32;
+    loop {
+        SHOW.wait().await;
+        PIXELS.lock(|p| p.borrow().draw(inactive_lmd).unwrap());
+        inactive_lmd = unsafe { &mut *ACTIVE_LMD.swap(inactive_lmd as *mut _, Ordering::AcqRel) };
+
+        frames = frames.wrapping_add(1);
+        if frames.is_multiple_of(256) {
+            defmt::info!("{} frames shown", frames);
+        }
+    }
+}
+
+#[embassy_executor::task(pool_size = 3)]
+async fn udp_task(stack: Stack<'static>, protocol: Protocol) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 8];
+    let mut rx_buffer = [0; 4096];
+    let mut tx_meta = [PacketMetadata::EMPTY; 2];
+    let mut tx_buffer = [0; 2 * lighting::ARTNET_POLL_REPLY_LEN];
+    let mut buf = [0; MTU];
+
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+    socket.bind(protocol.port()).unwrap();
+    defmt::info!("Listening for {} on port {}", protocol, protocol.port());
+
+    loop {
+        let (n, meta) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                defmt::warn!("{} receive failed: {}", protocol, e);
+                continue;
+            }
+        };
+        match protocol.parse(&buf[..n]) {
+            Some(Packet::Poll) => {
+                let (first, _) = PIXELS.lock(|p| p.borrow().first_universes());
+                for page in 0..lighting::artnet_page_count(first) {
+                    let reply = lighting::artnet_poll_reply(IP.octets(), MAC, first, page);
+                    if let Err(e) = socket.send_to(&reply, (meta.endpoint.addr, lighting::ARTNET_PORT)).await {
+                        defmt::warn!("ArtPollReply failed: {}", e);
+                    }
+                }
+            }
+            Some(packet) => {
+                let complete = PIXELS.lock(|p| p.borrow_mut().handle(packet, Instant::now()));
+                if complete {
+                    SHOW.signal(());
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn usb_ncm_task(runner: Runner<'static, UsbDriver, MTU>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, Device<'static, MTU>>) -> ! {
+    runner.run().await
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let p = embassy_rp::init(Default::default());
+
+    let m_r1 = Output::new(p.PIN_8, Level::Low);
+    let m_b1 = Output::new(p.PIN_9, Level::Low);
+    let m_r2 = Output::new(p.PIN_11, Level::Low);
+    let m_b2 = Output::new(p.PIN_12, Level::Low);
+    let m_a = Output::new(p.PIN_25, Level::Low);
+    let m_c = Output::new(p.PIN_29, Level::Low);
+    let m_clk = Output::new(p.PIN_13, Level::Low);
+    let m_oe = Output::new(p.PIN_0, Level::Low);
+
+    let m_lat = Output::new(p.PIN_1, Level::Low);
+    let m_d = Output::new(p.PIN_28, Level::Low);
+    let m_b = Output::new(p.PIN_24, Level::Low);
+    let m_g2 = Output::new(p.PIN_10, Level::Low);
+    let m_g1 = Output::new(p.PIN_7, Level::Low);
+
+    let lm = LedMatrix::new(
+        m_r1, m_r2, m_g1, m_g2, m_b1, m_b2, m_clk, m_lat, m_oe, m_a, m_b, m_c, m_d,
+    );
+
+    let driver = usb::driver(p.USB);
+    let mut builder = usb::builder(driver, usb::config("LED matrix network"));
+    let class = CdcNcmClass::new(&mut builder, NCM_STATE.init(State::new()), HOST_MAC, usb::MAX_PACKET_SIZE);
+    let (ncm_runner, device) = class.into_embassy_net_device::<MTU, 4, 4>(NET_STATE.init(NetState::new()), MAC);
+    let usb = builder.build();
+
+    let config = embassy_net::Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(IP, 24),
+        gateway: None,
+        dns_servers: Default::default(),
+    });
+    let seed = rng::small_rng().next_u64();
+    let (stack, net_runner) = embassy_net::new(device, config, RESOURCES.init(StackResources::new()), seed);
+
+    // sACN senders multicast every universe to its own group
+    let (_, first) = PIXELS.lock(|p| p.borrow().first_universes());
+    for universe in first..first + UNIVERSES as u16 {
+        let [hi, lo] = universe.to_be_bytes();
+        if let Err(e) = stack.join_multicast_group(Ipv4Address::new(239, 255, hi, lo)) {
+            // smoltcp drops the datagrams of groups it isn't in, part of the panel would never update
+            defmt::panic!("Can't join sACN universe {}: {}", universe, e);
+        }
+    }
+
+    spawn_core1(
+        p.CORE1,
+        unsafe { &mut *core::ptr::addr_of_mut!(CORE1_STACK) },
+        move || {
+            let executor1 = EXECUTOR1.init(Executor::new());
+            executor1.run(|spawner| spawner.spawn(matrix_task(lm)).unwrap());
+        },
+    );
+
+    let executor0 = EXECUTOR0.init(Executor::new());
+    executor0.run(|spawner| {
+        spawner.spawn(usb::usb_task(usb)).unwrap();
+        spawner.spawn(usb_ncm_task(ncm_runner)).unwrap();
+        spawner.spawn(net_task(net_runner)).unwrap();
+        spawner.spawn(graphics_task()).unwrap();
+        for protocol in Protocol::ALL {
+            spawner.spawn(udp_task(stack, protocol)).unwrap();
+        }
+    });
+}
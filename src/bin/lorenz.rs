@@ -8,7 +8,8 @@ use core::sync::atomic::Ordering;
 use portable_atomic::AtomicPtr;
 
 use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
-use embassy_adafruit_rpi_2040_uf2_led_matrix::lorenz::Lorenz;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::log;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::lorenz::{Integrator, Lorenz};
 use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
 use embassy_executor::Executor;
 use embassy_rp::gpio::{Level, Output};
@@ -16,7 +17,7 @@ use embassy_rp::multicore::{spawn_core1, Stack};
 use embassy_sync::once_lock::OnceLock;
 use embassy_time::Timer;
 use static_cell::StaticCell;
-use {defmt_rtt as _, panic_probe as _};
+use panic_probe as _;
 
 static mut CORE1_STACK: Stack<8192> = Stack::new();
 static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
@@ -54,7 +55,8 @@ async fn graphics_task() {
     let mut inactive_lmd = LMD2.init(LedMatrixDisplay::new());
     LMD_READY.init(()).unwrap();
 
-    let lorenz = LORENZ.init(Lorenz::new());
+    // A few particles started close together to show the orbits diverge
+    let lorenz = LORENZ.init(Lorenz::with_particles(4, Integrator::Rk4));
 
     defmt::info!("Starting Lorenz simulation");
 
@@ -103,6 +105,7 @@ fn main() -> ! {
 
     let executor0 = EXECUTOR0.init(Executor::new());
     executor0.run(|spawner| {
+        log::start(spawner, p.USB);
         spawner.spawn(graphics_task()).unwrap();
     });
 }
@@ -4,6 +4,7 @@
 #![no_main]
 
 use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::log;
 use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
 use embassy_executor::Spawner;
 use embassy_rp::gpio::{Level, Output};
@@ -14,7 +15,7 @@ use embedded_graphics::{
     prelude::*,
     primitives::{Circle, PrimitiveStyleBuilder, Rectangle},
 };
-use {defmt_rtt as _, panic_probe as _};
+use panic_probe as _;
 
 #[embassy_executor::task]
 async fn matrix_task(mut lm: LedMatrix<'static>, lmd: LedMatrixDisplay) {
@@ -27,6 +28,7 @@ async fn matrix_task(mut lm: LedMatrix<'static>, lmd: LedMatrixDisplay) {
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
+    log::start(spawner, p.USB);
 
     let m_r1 = Output::new(p.PIN_8, Level::Low);
     let m_b1 = Output::new(p.PIN_9, Level::Low);
@@ -5,11 +5,12 @@
 #![no_std]
 #![no_main]
 
+use embassy_adafruit_rpi_2040_uf2_led_matrix::log;
 use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
 use embassy_executor::Spawner;
 use embassy_rp::gpio::{Level, Output};
 use embassy_time::Timer;
-use {defmt_rtt as _, panic_probe as _};
+use panic_probe as _;
 
 #[embassy_executor::task]
 async fn matrix_task(mut lm: LedMatrix<'static>) {
@@ -51,6 +52,7 @@ async fn matrix_task(mut lm: LedMatrix<'static>) {
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
+    log::start(spawner, p.USB);
 
     let m_r1 = Output::new(p.PIN_8, Level::Low);
     let m_b1 = Output::new(p.PIN_9, Level::Low);
@@ -0,0 +1,437 @@
+//! Every effect on the LED matrix, controlled from a USB serial shell.
+//!
+//! Connect with any terminal, e.g. `picocom /dev/ttyACM0`, and type `help`.
+//! The same controls are on a vendor defined HID interface for programs, see
+//! `hid` in the library for the reports. `screenshot` and `record` print the
+//! panel as text, `matrix screenshot` in `host/` turns it into a PNG or GIF.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write as _;
+use core::ptr;
+use core::sync::atomic::Ordering;
+use portable_atomic::{AtomicPtr, AtomicU8};
+
+use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::effects::{Effect, Effects};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::hid::{self, ControlReport, Request, Status};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::rng;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::shell::{self, Command, Echo, FrameText, LineEditor, ParseError, HELP, PROMPT};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::usb::{self, Reboot, UsbDriver};
+use embassy_executor::Executor;
+use embassy_futures::select::{select, Either};
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::multicore::{spawn_core1, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::once_lock::OnceLock;
+use embassy_sync::watch::{self, Watch};
+use embassy_time::{Instant, Timer};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, Receiver, Sender, State};
+use embassy_usb::class::hid::{HidReader, HidReaderWriter, HidWriter};
+use embassy_usb::driver::EndpointError;
+use heapless::String;
+use static_cell::StaticCell;
+use usbd_hid::descriptor::SerializedDescriptor;
+use panic_probe as _;
+
+static mut CORE1_STACK: Stack<8192> = Stack::new();
+static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
+static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
+
+static LMD1: StaticCell<LedMatrixDisplay> = StaticCell::new();
+static LMD2: StaticCell<LedMatrixDisplay> = StaticCell::new();
+
+static ACTIVE_LMD: AtomicPtr<LedMatrixDisplay> = AtomicPtr::new(ptr::null_mut());
+static LMD_READY: OnceLock<()> = OnceLock::new();
+
+static EFFECTS: StaticCell<Effects> = StaticCell::new();
+static CDC_STATE: StaticCell<State> = StaticCell::new();
+static HID_STATE: StaticCell<embassy_usb::class::hid::State> = StaticCell::new();
+
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(100);
+
+// Lines use \n, the shell task turns them into \r\n
+type Reply = String<256>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Client {
+    Shell,
+    Hid,
+}
+
+// Applied by the graphics task between frames, only the shell waits for a reply
+static COMMANDS: Channel<CriticalSectionRawMutex, (Client, Command), 4> = Channel::new();
+static REPLIES: Channel<CriticalSectionRawMutex, Reply, 1> = Channel::new();
+
+// Published after every command, sent to the HID host
+static STATUS: Watch<CriticalSectionRawMutex, Status, 1> = Watch::new();
+
+// Effect frame and time of every buffer swap, for `record`
+static SHOWN: Watch<CriticalSectionRawMutex, (u32, Instant), 1> = Watch::new();
+type ShownReceiver = watch::Receiver<'static, CriticalSectionRawMutex, (u32, Instant), 1>;
+
+#[embassy_executor::task]
+async fn matrix_task(mut lm: LedMatrix<'static>) {
+    LMD_READY.get().await;
+    defmt::info!("Starting matrix scans");
+
+    loop {
+        let ptr = ACTIVE_LMD.load(Ordering::Relaxed);
+        if !ptr.is_null() {
+            let lmd = unsafe { &*ptr };
+            lmd.run_with_brightness(&mut lm, BRIGHTNESS.load(Ordering::Relaxed));
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn graphics_task() {
+    Timer::after_millis(100).await;
+
+    let lmd1 = LMD1.init(LedMatrixDisplay::new());
+    ACTIVE_LMD.store(lmd1, Ordering::Release);
+
+    let mut inactive_lmd = LMD2.init(LedMatrixDisplay::new());
+    LMD_READY.init(()).unwrap();
+
+    let effects = EFFECTS.init(Effects::new());
+    let mut speed_ms = effects.effect().frame_ms();
+    let mut seed = None;
+    let mut paused = false;
+    let mut steps = 0u32;
+    let mut media_keys = false;
+    let mut lit_brightness = BRIGHTNESS.load(Ordering::Relaxed);
+
+    defmt::info!("Starting {}", effects.effect());
+
+    let mut changed = true;
+    loop {
+        while let Ok((client, command)) = COMMANDS.try_receive() {
+            defmt::info!("{}", command);
+            changed = true;
+            let mut reply = Reply::new();
+            match command {
+                // The shell answers these itself
+                Command::Help | Command::Screenshot | Command::Record(_) | Command::Bootloader => {}
+                Command::Status => {
+                    let _ = write!(
+                        reply,
+                        "effect {}, frame {}{}\nspeed {}ms\nbrightness {}%\n",
+                        effects.effect().name(),
+                        effects.frame(),
+                        if paused { " (paused)" } else { "" },
+                        speed_ms,
+                        BRIGHTNESS.load(Ordering::Relaxed),
+                    );
+                    let _ = match seed {
+                        Some(s) => writeln!(reply, "seed {}", s),
+                        None => writeln!(reply, "seed random"),
+                    };
+                    let _ = writeln!(reply, "media keys {}", if media_keys { "on" } else { "off" });
+                }
+                Command::Effect(None) => {
+                    for e in Effect::ALL {
+                        let mark = if e == effects.effect() { '*' } else { ' ' };
+                        let _ = writeln!(reply, "{} {}", mark, e.name());
+                    }
+                }
+                Command::Effect(Some(e)) => {
+                    effects.set_effect(e);
+                    speed_ms = e.frame_ms();
+                    let _ = writeln!(reply, "effect {}", e.name());
+                }
+                Command::Next => {
+                    let i = Effect::ALL.iter().position(|&e| e == effects.effect()).unwrap_or(0);
+                    let e = Effect::ALL[(i + 1) % Effect::ALL.len()];
+                    effects.set_effect(e);
+                    speed_ms = e.frame_ms();
+                    let _ = writeln!(reply, "effect {}", e.name());
+                }
+                Command::Restart => effects.restart(),
+                Command::Brightness(percent) => {
+                    BRIGHTNESS.store(percent, Ordering::Relaxed);
+                    if percent > 0 {
+                        lit_brightness = percent;
+                    }
+                }
+                Command::SpeedMs(ms) => speed_ms = ms,
+                Command::Seed(s) => {
+                    seed = s;
+                    rng::set_seed(s);
+                    effects.restart();
+                }
+                Command::Pause => paused = true,
+                Command::Resume => {
+                    paused = false;
+                    steps = 0;
+                }
+                Command::Step(n) => {
+                    paused = true;
+                    steps = steps.saturating_add(n);
+                }
+                Command::MediaKeys(on) => media_keys = on,
+            }
+            if client == Client::Shell {
+                if reply.is_empty() {
+                    let _ = reply.push_str("ok\n");
+                }
+                REPLIES.send(reply).await;
+            }
+        }
+        if changed {
+            changed = false;
+            STATUS.sender().send(Status {
+                effect: effects.effect(),
+                brightness: BRIGHTNESS.load(Ordering::Relaxed),
+                lit_brightness,
+                paused,
+                seeded: seed.is_some(),
+                media_keys,
+                speed_ms,
+                frame: effects.frame(),
+            });
+        }
+
+        inactive_lmd.clear();
+        effects.draw(inactive_lmd).unwrap();
+        inactive_lmd = unsafe { &mut *ACTIVE_LMD.swap(inactive_lmd as *mut _, Ordering::AcqRel) };
+        SHOWN.sender().send((effects.frame(), Instant::now()));
+
+        if !paused {
+            effects.step();
+        } else if steps > 0 {
+            effects.step();
+            steps -= 1;
+        }
+
+        Timer::after_millis(speed_ms as u64).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn shell_task(class: CdcAcmClass<'static, UsbDriver>) {
+    let (mut tx, mut rx) = class.split();
+    let mut editor = LineEditor::new();
+    let mut shown = SHOWN.receiver().unwrap();
+
+    loop {
+        rx.wait_connection().await;
+        defmt::info!("Shell connected");
+        let _ = shell(&mut tx, &mut rx, &mut editor, &mut shown).await;
+        defmt::info!("Shell disconnected");
+    }
+}
+
+/// Runs until the host goes away
+async fn shell(
+    tx: &mut Sender<'static, UsbDriver>,
+    rx: &mut Receiver<'static, UsbDriver>,
+    editor: &mut LineEditor,
+    shown: &mut ShownReceiver,
+) -> Result<(), EndpointError> {
+    write_lines(tx, "LED matrix shell, `help` lists the commands\n").await?;
+    usb::write_all(tx, PROMPT.as_bytes()).await?;
+
+    let mut buf = [0; usb::MAX_PACKET_SIZE as usize];
+    loop {
+        let n = rx.read_packet(&mut buf).await?;
+        for &byte in &buf[..n] {
+            let mut echo = Echo::new();
+            let done = editor.feed(byte, &mut echo);
+            if !echo.is_empty() {
+                usb::write_all(tx, &echo).await?;
+            }
+            if done {
+                run(tx, rx, shown, editor.line()).await?;
+                usb::write_all(tx, PROMPT.as_bytes()).await?;
+            }
+        }
+    }
+}
+
+async fn run(
+    tx: &mut Sender<'static, UsbDriver>,
+    rx: &mut Receiver<'static, UsbDriver>,
+    shown: &mut ShownReceiver,
+    line: &str,
+) -> Result<(), EndpointError> {
+    match line.parse::<Command>() {
+        Ok(Command::Help) => write_lines(tx, HELP).await,
+        Ok(Command::Screenshot) => {
+            let (frame, at) = SHOWN.try_get().unwrap_or((0, Instant::now()));
+            write_lines(tx, &active_frame_text(frame, at)).await
+        }
+        Ok(Command::Record(frames)) => record(tx, rx, shown, frames).await,
+        Ok(Command::Bootloader) => {
+            usb::reboot(Reboot::Bootloader);
+            write_lines(tx, "rebooting into the bootloader\n").await
+        }
+        Ok(command) => {
+            COMMANDS.send((Client::Shell, command)).await;
+            let reply = REPLIES.receive().await;
+            write_lines(tx, &reply).await
+        }
+        Err(ParseError::Empty) => Ok(()),
+        Err(e) => {
+            let mut msg = String::<64>::new();
+            let _ = writeln!(msg, "error: {}", e);
+            write_lines(tx, &msg).await
+        }
+    }
+}
+
+/// Frames as they are shown, those the host is too slow for are skipped
+async fn record(
+    tx: &mut Sender<'static, UsbDriver>,
+    rx: &mut Receiver<'static, UsbDriver>,
+    shown: &mut ShownReceiver,
+    frames: Option<u32>,
+) -> Result<(), EndpointError> {
+    let mut buf = [0; usb::MAX_PACKET_SIZE as usize];
+    let mut recorded = 0;
+    while frames.is_none_or(|n| recorded < n) {
+        match select(shown.changed(), rx.read_packet(&mut buf)).await {
+            Either::First((frame, at)) => {
+                write_lines(tx, &active_frame_text(frame, at)).await?;
+                recorded += 1;
+            }
+            // Any key stops
+            Either::Second(read) => {
+                read?;
+                break;
+            }
+        }
+    }
+    let mut msg = String::<32>::new();
+    let _ = writeln!(msg, "recorded {} frames", recorded);
+    write_lines(tx, &msg).await
+}
+
+/// The front buffer as text
+fn active_frame_text(frame: u32, at: Instant) -> FrameText {
+    let ptr = ACTIVE_LMD.load(Ordering::Acquire);
+    if ptr.is_null() {
+        return FrameText::new();
+    }
+    // Graphics runs on this core too, so it can't swap the buffers while this reads
+    let lmd = unsafe { &*ptr };
+    shell::frame_text(lmd, frame, at.as_millis())
+}
+
+#[embassy_executor::task]
+async fn hid_reader_task(mut reader: HidReader<'static, UsbDriver, { hid::REPORT_LEN }>) {
+    let mut buf = [0; hid::REPORT_LEN];
+    loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(n) => n,
+            Err(e) => {
+                defmt::warn!("HID read failed: {}", e);
+                reader.ready().await;
+                continue;
+            }
+        };
+        let command = match Request::parse(&buf[..n]) {
+            Ok(Request::Command(command)) => Some(command),
+            // Off unless switched on, see `hid`
+            Ok(Request::MediaKey(usage)) => STATUS
+                .try_get()
+                .filter(|status| status.media_keys)
+                .and_then(|status| hid::media_key_command(usage, &status)),
+            Err(e) => {
+                defmt::warn!("Bad HID request: {}", e);
+                None
+            }
+        };
+        if let Some(command) = command {
+            COMMANDS.send((Client::Hid, command)).await;
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn hid_writer_task(mut writer: HidWriter<'static, UsbDriver, { hid::REPORT_LEN }>) {
+    let mut status = STATUS.receiver().unwrap();
+    loop {
+        let report = status.changed().await.to_report();
+        writer.ready().await;
+        if let Err(e) = writer.write(&report).await {
+            defmt::warn!("HID write failed: {}", e);
+        }
+    }
+}
+
+/// Terminals want \r\n
+async fn write_lines(tx: &mut Sender<'static, UsbDriver>, text: &str) -> Result<(), EndpointError> {
+    for line in text.split_inclusive('\n') {
+        match line.strip_suffix('\n') {
+            Some(line) => {
+                usb::write_all(tx, line.as_bytes()).await?;
+                usb::write_all(tx, b"\r\n").await?;
+            }
+            None => usb::write_all(tx, line.as_bytes()).await?,
+        }
+    }
+    Ok(())
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let p = embassy_rp::init(Default::default());
+
+    let m_r1 = Output::new(p.PIN_8, Level::Low);
+    let m_b1 = Output::new(p.PIN_9, Level::Low);
+    let m_r2 = Output::new(p.PIN_11, Level::Low);
+    let m_b2 = Output::new(p.PIN_12, Level::Low);
+    let m_a = Output::new(p.PIN_25, Level::Low);
+    let m_c = Output::new(p.PIN_29, Level::Low);
+    let m_clk = Output::new(p.PIN_13, Level::Low);
+    let m_oe = Output::new(p.PIN_0, Level::Low);
+
+    let m_lat = Output::new(p.PIN_1, Level::Low);
+    let m_d = Output::new(p.PIN_28, Level::Low);
+    let m_b = Output::new(p.PIN_24, Level::Low);
+    let m_g2 = Output::new(p.PIN_10, Level::Low);
+    let m_g1 = Output::new(p.PIN_7, Level::Low);
+
+    let lm = LedMatrix::new(
+        m_r1, m_r2, m_g1, m_g2, m_b1, m_b2, m_clk, m_lat, m_oe, m_a, m_b, m_c, m_d,
+    );
+
+    let driver = usb::driver(p.USB);
+    let mut builder = usb::builder(driver, usb::config("LED matrix shell"));
+    let class = CdcAcmClass::new(&mut builder, CDC_STATE.init(State::new()), usb::MAX_PACKET_SIZE);
+    let hid_config = embassy_usb::class::hid::Config {
+        report_descriptor: ControlReport::desc(),
+        request_handler: None,
+        poll_ms: 10,
+        max_packet_size: usb::MAX_PACKET_SIZE,
+    };
+    let hid = HidReaderWriter::<_, { hid::REPORT_LEN }, { hid::REPORT_LEN }>::new(
+        &mut builder,
+        HID_STATE.init(embassy_usb::class::hid::State::new()),
+        hid_config,
+    );
+    let (hid_reader, hid_writer) = hid.split();
+    let device = builder.build();
+
+    spawn_core1(
+        p.CORE1,
+        unsafe { &mut *core::ptr::addr_of_mut!(CORE1_STACK) },
+        move || {
+            let executor1 = EXECUTOR1.init(Executor::new());
+            executor1.run(|spawner| spawner.spawn(matrix_task(lm)).unwrap());
+        },
+    );
+
+    let executor0 = EXECUTOR0.init(Executor::new());
+    executor0.run(|spawner| {
+        spawner.spawn(usb::usb_task(device)).unwrap();
+        spawner.spawn(shell_task(class)).unwrap();
+        spawner.spawn(hid_reader_task(hid_reader)).unwrap();
+        spawner.spawn(hid_writer_task(hid_writer)).unwrap();
+        spawner.spawn(graphics_task()).unwrap();
+    });
+}
@@ -0,0 +1,140 @@
+//! LED matrix as a USB display, showing frames streamed from a host.
+//!
+//! Frames go to the CDC-ACM serial port, either in our own format or as
+//! Adalight or TPM2 from desktop LED software, see `stream` in the library.
+//! Each one is decoded into the back buffer, which is swapped in as soon as
+//! the last pixel arrives.
+
+#![no_std]
+#![no_main]
+
+use core::ptr;
+use core::sync::atomic::Ordering;
+use portable_atomic::AtomicPtr;
+
+use embassy_adafruit_rpi_2040_uf2_led_matrix::display::LedMatrixDisplay;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::matrix::*;
+use embassy_adafruit_rpi_2040_uf2_led_matrix::stream::{FrameDecoder, Layout, ADALIGHT_GREETING};
+use embassy_adafruit_rpi_2040_uf2_led_matrix::usb::{self, UsbDriver};
+use embassy_executor::Executor;
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::multicore::{spawn_core1, Stack};
+use embassy_sync::once_lock::OnceLock;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use static_cell::StaticCell;
+use panic_probe as _;
+
+static mut CORE1_STACK: Stack<8192> = Stack::new();
+static EXECUTOR0: StaticCell<Executor> = StaticCell::new();
+static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
+
+static LMD1: StaticCell<LedMatrixDisplay> = StaticCell::new();
+static LMD2: StaticCell<LedMatrixDisplay> = StaticCell::new();
+
+static ACTIVE_LMD: AtomicPtr<LedMatrixDisplay> = AtomicPtr::new(ptr::null_mut());
+static LMD_READY: OnceLock<()> = OnceLock::new();
+
+static CDC_STATE: StaticCell<State> = StaticCell::new();
+
+// How Adalight and TPM2 strips wrap onto the panel, match the host's setup
+const LAYOUT: Layout = Layout::Rows;
+
+#[embassy_executor::task]
+async fn matrix_task(mut lm: LedMatrix<'static>) {
+    LMD_READY.get().await;
+    defmt::info!("Starting matrix scans");
+
+    loop {
+        let ptr = ACTIVE_LMD.load(Ordering::Relaxed);
+        if !ptr.is_null() {
+            let lmd = unsafe { &*ptr };
+            lmd.run(&mut lm);
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn stream_task(mut class: CdcAcmClass<'static, UsbDriver>) {
+    let lmd1 = LMD1.init(LedMatrixDisplay::new());
+    ACTIVE_LMD.store(lmd1, Ordering::Release);
+
+    let mut inactive_lmd = LMD2.init(LedMatrixDisplay::new());
+    LMD_READY.init(()).unwrap();
+
+    let mut decoder = FrameDecoder::with_layout(LAYOUT);
+    let mut frames = 0u32;
+    let mut buf = [0; usb::MAX_PACKET_SIZE as usize];
+
+    loop {
+        class.wait_connection().await;
+        defmt::info!("Host connected, waiting for frames");
+        decoder.reset();
+        let mut protocol = None;
+        // Harmless for the other protocols
+        let _ = class.write_packet(ADALIGHT_GREETING).await;
+
+        while let Ok(n) = class.read_packet(&mut buf).await {
+            let mut data = &buf[..n];
+            while !data.is_empty() {
+                let (used, frame) = decoder.feed(data, inactive_lmd);
+                data = &data[used..];
+                if let Some(frame) = frame {
+                    inactive_lmd = unsafe { &mut *ACTIVE_LMD.swap(inactive_lmd as *mut _, Ordering::AcqRel) };
+                    if protocol != Some(frame.protocol()) {
+                        protocol = Some(frame.protocol());
+                        defmt::info!("Receiving {}", frame.protocol());
+                    }
+                    frames = frames.wrapping_add(1);
+                    if frames.is_multiple_of(256) {
+                        defmt::info!("{}, {} frames, {} dropped", frame, frames, decoder.dropped());
+                    }
+                }
+            }
+        }
+        defmt::info!("Host disconnected");
+    }
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    let p = embassy_rp::init(Default::default());
+
+    let m_r1 = Output::new(p.PIN_8, Level::Low);
+    let m_b1 = Output::new(p.PIN_9, Level::Low);
+    let m_r2 = Output::new(p.PIN_11, Level::Low);
+    let m_b2 = Output::new(p.PIN_12, Level::Low);
+    let m_a = Output::new(p.PIN_25, Level::Low);
+    let m_c = Output::new(p.PIN_29, Level::Low);
+    let m_clk = Output::new(p.PIN_13, Level::Low);
+    let m_oe = Output::new(p.PIN_0, Level::Low);
+
+    let m_lat = Output::new(p.PIN_1, Level::Low);
+    let m_d = Output::new(p.PIN_28, Level::Low);
+    let m_b = Output::new(p.PIN_24, Level::Low);
+    let m_g2 = Output::new(p.PIN_10, Level::Low);
+    let m_g1 = Output::new(p.PIN_7, Level::Low);
+
+    let lm = LedMatrix::new(
+        m_r1, m_r2, m_g1, m_g2, m_b1, m_b2, m_clk, m_lat, m_oe, m_a, m_b, m_c, m_d,
+    );
+
+    let driver = usb::driver(p.USB);
+    let mut builder = usb::builder(driver, usb::config("LED matrix display"));
+    let class = CdcAcmClass::new(&mut builder, CDC_STATE.init(State::new()), usb::MAX_PACKET_SIZE);
+    let device = builder.build();
+
+    spawn_core1(
+        p.CORE1,
+        unsafe { &mut *core::ptr::addr_of_mut!(CORE1_STACK) },
+        move || {
+            let executor1 = EXECUTOR1.init(Executor::new());
+            executor1.run(|spawner| spawner.spawn(matrix_task(lm)).unwrap());
+        },
+    );
+
+    let executor0 = EXECUTOR0.init(Executor::new());
+    executor0.run(|spawner| {
+        spawner.spawn(usb::usb_task(device)).unwrap();
+        spawner.spawn(stream_task(class)).unwrap();
+    });
+}
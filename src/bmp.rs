@@ -0,0 +1,179 @@
+//!
+//! Windows bitmaps read front to back, so they can be drawn straight off a
+//! byte stream: 1, 4, 8, 16, 24 and 32 bits per pixel, uncompressed or with
+//! bit fields. Pictures are centered, anything past 32x32 is cropped.
+//!
+
+use core::convert::Infallible;
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+
+const W: i32 = 32;
+const H: i32 = 32;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum BmpError {
+    NotBmp,
+    /// Compressed, or a header or bit depth we don't read
+    Unsupported,
+    /// Ends before the last row
+    Truncated,
+}
+
+/// Counts bytes so the pixels can be found by offset
+struct Reader<I> {
+    data: I,
+    pos: u32,
+}
+
+impl<I: Iterator<Item = u8>> Reader<I> {
+    fn u8(&mut self) -> Result<u8, BmpError> {
+        self.pos += 1;
+        self.data.next().ok_or(BmpError::Truncated)
+    }
+
+    fn u16(&mut self) -> Result<u16, BmpError> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn u32(&mut self) -> Result<u32, BmpError> {
+        Ok(u32::from_le_bytes([self.u8()?, self.u8()?, self.u8()?, self.u8()?]))
+    }
+
+    fn skip(&mut self, n: u32) -> Result<(), BmpError> {
+        for _ in 0..n {
+            self.u8()?;
+        }
+        Ok(())
+    }
+}
+
+/// Mask of one channel in a 16 or 32 bit pixel
+#[derive(Clone, Copy)]
+struct Channel {
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    fn new(mask: u32) -> Self {
+        let shift = mask.trailing_zeros().min(31);
+        Channel {
+            mask,
+            shift,
+            max: (mask >> shift).max(1),
+        }
+    }
+
+    fn get(&self, pixel: u32) -> u8 {
+        (((pixel & self.mask) >> self.shift) as u64 * 255 / self.max as u64) as u8
+    }
+}
+
+pub fn draw<D>(data: impl Iterator<Item = u8>, target: &mut D) -> Result<(), BmpError>
+where
+    D: DrawTarget<Color = Rgb888, Error = Infallible>,
+{
+    let mut r = Reader { data, pos: 0 };
+    if r.u16()? != u16::from_le_bytes(*b"BM") {
+        return Err(BmpError::NotBmp);
+    }
+    r.skip(8)?;
+    let pixels_at = r.u32()?;
+
+    let header_size = r.u32()?;
+    if header_size < 40 {
+        return Err(BmpError::Unsupported);
+    }
+    let width = r.u32()? as i32;
+    let height = r.u32()? as i32;
+    r.skip(2)?;
+    let bpp = r.u16()?;
+    let compression = r.u32()?;
+    r.skip(12)?;
+    let colors_used = r.u32()?;
+    r.skip(4)?;
+
+    // Bit fields come after the basic header, inside the bigger ones
+    let (red, green, blue) = match (compression, bpp) {
+        (BI_BITFIELDS, 16 | 32) => {
+            let masks = (r.u32()?, r.u32()?, r.u32()?);
+            r.skip(header_size.saturating_sub(52))?;
+            masks
+        }
+        (BI_RGB, 16) => (0x7c00, 0x03e0, 0x001f),
+        (BI_RGB, 32) => (0xff_0000, 0xff00, 0xff),
+        (BI_RGB, 1 | 4 | 8 | 24) => (0, 0, 0),
+        _ => return Err(BmpError::Unsupported),
+    };
+    if compression == BI_RGB {
+        r.skip(header_size - 40)?;
+    }
+    let (red, green, blue) = (Channel::new(red), Channel::new(green), Channel::new(blue));
+    if !(1..=4096).contains(&width) || height == 0 || height == i32::MIN {
+        return Err(BmpError::Unsupported);
+    }
+
+    let mut palette = [Rgb888::BLACK; 256];
+    if bpp <= 8 {
+        let count = match colors_used {
+            0 => 1 << bpp,
+            n => n.min(256),
+        };
+        for color in &mut palette[..count as usize] {
+            let [b, g, red, _] = r.u32()?.to_le_bytes();
+            *color = Rgb888::new(red, g, b);
+        }
+    }
+    if r.pos > pixels_at {
+        return Err(BmpError::Unsupported);
+    }
+    r.skip(pixels_at - r.pos)?;
+
+    // Rows are padded to 4 bytes and stored bottom up unless the height is negative
+    let stride = (width as u32 * bpp as u32).div_ceil(32) * 4;
+    let rows = height.unsigned_abs() as i32;
+    let left = (W - width) / 2;
+    let top = (H - rows) / 2;
+    for row in 0..rows {
+        let y = top + if height > 0 { rows - 1 - row } else { row };
+        let mut used = 0;
+        let mut bits = 0u32;
+        let mut nbits = 0;
+        for x in 0..width {
+            let color = match bpp {
+                1 | 4 | 8 => {
+                    if nbits == 0 {
+                        bits = r.u8()? as u32;
+                        nbits = 8;
+                        used += 1;
+                    }
+                    nbits -= bpp as u32;
+                    palette[((bits >> nbits) & ((1 << bpp) - 1)) as usize]
+                }
+                24 => {
+                    let (b, g, red) = (r.u8()?, r.u8()?, r.u8()?);
+                    used += 3;
+                    Rgb888::new(red, g, b)
+                }
+                _ => {
+                    let pixel = if bpp == 16 { r.u16()? as u32 } else { r.u32()? };
+                    used += bpp as u32 / 8;
+                    Rgb888::new(red.get(pixel), green.get(pixel), blue.get(pixel))
+                }
+            };
+            let x = left + x;
+            if (0..W).contains(&x) && (0..H).contains(&y) {
+                let Ok(()) = target.draw_iter([Pixel(Point::new(x, y), color)]);
+            }
+        }
+        if row + 1 < rows {
+            r.skip(stride - used)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,289 @@
+//! Flocking simulation.
+//!
+//! <https://en.wikipedia.org/wiki/Boids>
+//! <https://vanhunteradams.com/Pico/Animal_Movement/Boids-algorithm.html>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use heapless::Vec;
+use rand::{Rng, rngs::SmallRng};
+
+use crate::num::{Real, Scalar};
+use crate::trail::Trail;
+
+const W: i32 = 32;
+const H: i32 = 32;
+
+const TRAIL_LEN: usize = 6;
+pub const MAX_BOIDS: usize = 24;
+
+const PALETTE: [Rgb555; 6] = [
+    Rgb555::RED,
+    Rgb555::GREEN,
+    Rgb555::BLUE,
+    Rgb555::YELLOW,
+    Rgb555::CYAN,
+    Rgb555::MAGENTA,
+];
+
+/// What happens at the panel edges
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Edges {
+    /// Fly out one side, come back on the other
+    Wrap,
+    /// Steer away from the edges
+    Avoid,
+}
+
+/// Flocking constants converted to the scalar type once, in pixels and steps
+struct Params {
+    visual_range_sq: Scalar,
+    protected_range_sq: Scalar,
+    centering: Scalar,
+    matching: Scalar,
+    avoid: Scalar,
+    turn: Scalar,
+    margin: Scalar,
+    min_speed: Scalar,
+    max_speed: Scalar,
+    // Alpha max plus beta min vector length approximation
+    alpha: Scalar,
+    beta: Scalar,
+    size: [Scalar; 2],
+    half_size: [Scalar; 2],
+    // Just inside the far edge
+    inset: Scalar,
+}
+
+impl Params {
+    fn new() -> Self {
+        Self {
+            visual_range_sq: Scalar::from_f32(8.0 * 8.0),
+            protected_range_sq: Scalar::from_f32(2.0 * 2.0),
+            centering: Scalar::from_f32(0.005),
+            matching: Scalar::from_f32(0.05),
+            avoid: Scalar::from_f32(0.05),
+            turn: Scalar::from_f32(0.05),
+            margin: Scalar::from_f32(4.0),
+            min_speed: Scalar::from_f32(0.3),
+            max_speed: Scalar::from_f32(0.8),
+            alpha: Scalar::from_f32(0.96),
+            beta: Scalar::from_f32(0.398),
+            size: [Scalar::from_i32(W), Scalar::from_i32(H)],
+            half_size: [Scalar::from_i32(W / 2), Scalar::from_i32(H / 2)],
+            inset: Scalar::from_f32(0.01),
+        }
+    }
+}
+
+struct Boid {
+    p: [Scalar; 2],
+    v: [Scalar; 2],
+    trail: Trail<TRAIL_LEN>,
+    color: Rgb555,
+}
+
+pub struct Boids {
+    boids: Vec<Boid, MAX_BOIDS>,
+    edges: Edges,
+    params: Params,
+    rng: SmallRng,
+}
+
+impl Default for Boids {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Boids {
+    pub fn new() -> Self {
+        Self::with_boids(16, Edges::Wrap)
+    }
+
+    /// Up to `MAX_BOIDS` boids at random positions
+    pub fn with_boids(n: usize, edges: Edges) -> Self {
+        let mut boids = Self {
+            boids: Vec::new(),
+            edges,
+            params: Params::new(),
+            rng: crate::rng::small_rng(),
+        };
+        for _ in 0..n.min(MAX_BOIDS) {
+            boids.add();
+        }
+        boids
+    }
+
+    /// Add a boid at a random position, false if the flock is full
+    pub fn add(&mut self) -> bool {
+        let speed =
+            |rng: &mut SmallRng| Scalar::from_i32(rng.gen_range(-8..=8)) / Scalar::from_i32(10);
+        let boid = Boid {
+            p: [
+                Scalar::from_i32(self.rng.gen_range(0..W)),
+                Scalar::from_i32(self.rng.gen_range(0..H)),
+            ],
+            v: [speed(&mut self.rng), speed(&mut self.rng)],
+            trail: Trail::new(),
+            color: PALETTE[self.boids.len() % PALETTE.len()],
+        };
+        self.boids.push(boid).is_ok()
+    }
+
+    /// Drop the newest boid, false if there are none
+    pub fn remove(&mut self) -> bool {
+        self.boids.pop().is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.boids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boids.is_empty()
+    }
+
+    pub fn set_edges(&mut self, edges: Edges) {
+        self.edges = edges;
+    }
+
+    // Indexing by axis reads better than zipping x and y components
+    #[allow(clippy::needless_range_loop)]
+    pub fn step(&mut self) {
+        let pr = &self.params;
+        let zero = [Scalar::ZERO; 2];
+
+        // New velocities, computed from the old state of the whole flock
+        let mut vs = [zero; MAX_BOIDS];
+
+        for (i, b) in self.boids.iter().enumerate() {
+            let mut close = zero;
+            let mut pos_sum = zero;
+            let mut vel_sum = zero;
+            let mut neighbors = 0;
+
+            for (j, o) in self.boids.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let d = [self.delta(b.p[0], o.p[0], 0), self.delta(b.p[1], o.p[1], 1)];
+                let dist_sq = d[0] * d[0] + d[1] * d[1];
+
+                if dist_sq < pr.protected_range_sq {
+                    // Separation
+                    close[0] += -d[0];
+                    close[1] += -d[1];
+                } else if dist_sq < pr.visual_range_sq {
+                    pos_sum[0] += d[0];
+                    pos_sum[1] += d[1];
+                    vel_sum[0] += o.v[0];
+                    vel_sum[1] += o.v[1];
+                    neighbors += 1;
+                }
+            }
+
+            let mut v = b.v;
+            if neighbors > 0 {
+                let n = Scalar::from_i32(neighbors);
+                for k in 0..2 {
+                    // Cohesion, towards the neighbors' center
+                    v[k] += pos_sum[k] / n * pr.centering;
+                    // Alignment, match the neighbors' velocity
+                    v[k] += (vel_sum[k] / n - b.v[k]) * pr.matching;
+                }
+            }
+            for k in 0..2 {
+                v[k] += close[k] * pr.avoid;
+            }
+
+            if self.edges == Edges::Avoid {
+                for k in 0..2 {
+                    if b.p[k] < pr.margin {
+                        v[k] += pr.turn;
+                    }
+                    if b.p[k] > pr.size[k] - pr.margin {
+                        v[k] += -pr.turn;
+                    }
+                }
+            }
+
+            vs[i] = pr.limit_speed(v);
+        }
+
+        for (b, &v) in self.boids.iter_mut().zip(vs.iter()) {
+            b.v = v;
+            for k in 0..2 {
+                b.p[k] += v[k];
+                match self.edges {
+                    Edges::Wrap => {
+                        if b.p[k] < Scalar::ZERO {
+                            b.p[k] += pr.size[k];
+                        } else if b.p[k] >= pr.size[k] {
+                            b.p[k] += -pr.size[k];
+                        }
+                    }
+                    Edges::Avoid => {
+                        if b.p[k] < Scalar::ZERO {
+                            b.p[k] = Scalar::ZERO;
+                        } else if b.p[k] >= pr.size[k] {
+                            b.p[k] = pr.size[k] - pr.inset;
+                        }
+                    }
+                }
+            }
+            b.trail
+                .push(b.p[0].to_i32() as i8, b.p[1].to_i32() as i8, 0);
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        for b in self.boids.iter() {
+            for (i, (px, py, _)) in b.trail.iter().enumerate() {
+                if (0..W as i8).contains(&px) && (0..H as i8).contains(&py) {
+                    let is_head = i == b.trail.len() - 1;
+                    let color = if is_head { Rgb555::WHITE } else { b.color };
+                    Pixel(Point::new(px as i32, py as i32), color).draw(target)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Vector from `from` to `to` along axis `k`, the short way around when wrapping
+    fn delta(&self, from: Scalar, to: Scalar, k: usize) -> Scalar {
+        let pr = &self.params;
+        let mut d = to - from;
+        if self.edges == Edges::Wrap {
+            if d > pr.half_size[k] {
+                d += -pr.size[k];
+            } else if d < -pr.half_size[k] {
+                d += pr.size[k];
+            }
+        }
+        d
+    }
+}
+
+impl Params {
+    /// Scale the velocity into the [min, max] speed range
+    fn limit_speed(&self, v: [Scalar; 2]) -> [Scalar; 2] {
+        let speed = self.magnitude(v);
+        if speed > self.max_speed {
+            [v[0] * self.max_speed / speed, v[1] * self.max_speed / speed]
+        } else if speed < self.min_speed && speed > Scalar::ZERO {
+            [v[0] * self.min_speed / speed, v[1] * self.min_speed / speed]
+        } else {
+            v
+        }
+    }
+
+    /// Vector length within 4%, without a sqrt
+    fn magnitude(&self, [x, y]: [Scalar; 2]) -> Scalar {
+        let (x, y) = (x.abs(), y.abs());
+        let (hi, lo) = if x > y { (x, y) } else { (y, x) };
+        hi * self.alpha + lo * self.beta
+    }
+}
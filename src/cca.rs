@@ -1,5 +1,6 @@
 use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
-use embassy_rp::clocks::RoscRng;
+use rand::RngCore;
+use rand::rngs::SmallRng;
 
 const W: usize = 32;
 const H: usize = 32;
@@ -9,6 +10,8 @@ const THRESHOLD: u8 = 3;
 pub struct Cca {
     states: [u8; W * H],
     next_states: [u8; W * H],
+    // Also kickstarts dead boards, so a seed replays the whole run
+    rng: SmallRng,
 }
 
 impl Default for Cca {
@@ -22,17 +25,18 @@ impl Cca {
         let mut cca = Self {
             states: [0; W * H],
             next_states: [0; W * H],
+            rng: crate::rng::small_rng(),
         };
         cca.randomize();
         cca
     }
 
     pub fn randomize(&mut self) {
-        let mut rng = RoscRng;
+        self.rng = crate::rng::small_rng();
         defmt::info!("Randomizing CCA states...");
         let mut sum = 0u32;
         for i in 0..self.states.len() {
-            let s = (rng.next_u32() % STATES as u32) as u8;
+            let s = (self.rng.next_u32() % STATES as u32) as u8;
             self.states[i] = s;
             sum += s as u32;
         }
@@ -76,7 +80,7 @@ impl Cca {
         if changed == 0 {
             // If nothing changed, it means we reached a steady state or randomization failed.
             // Let's re-randomize one pixel to kickstart it if it's dead.
-            let i = (RoscRng.next_u32() % (W * H) as u32) as usize;
+            let i = (self.rng.next_u32() % (W * H) as u32) as usize;
             self.states[i] = (self.states[i] + 1) % STATES;
         }
     }
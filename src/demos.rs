@@ -0,0 +1,167 @@
+//! Classic demoscene framebuffer effects, tuned for 32x32.
+//!
+//! Angles are `u8`, 256 steps per turn, trig goes through a lookup table.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+pub mod copper;
+pub mod fire;
+pub mod plasma;
+pub mod rain;
+pub mod rotozoom;
+pub mod starfield;
+pub mod tunnel;
+
+pub use copper::Copper;
+pub use fire::Fire;
+pub use plasma::Plasma;
+pub use rain::Rain;
+pub use rotozoom::Rotozoom;
+pub use starfield::Starfield;
+pub use tunnel::Tunnel;
+
+const W: i32 = 32;
+const H: i32 = 32;
+
+/// Color wheel, as smooth as 1 bit per channel gets
+const HUES: [Rgb555; 6] = [
+    Rgb555::RED,
+    Rgb555::YELLOW,
+    Rgb555::GREEN,
+    Rgb555::CYAN,
+    Rgb555::BLUE,
+    Rgb555::MAGENTA,
+];
+
+/// Bhaskara I's sine approximation, good to ~0.2%, no floats
+const fn sin_table() -> [i8; 256] {
+    let mut t = [0i8; 256];
+    let mut i = 0;
+    while i < 256 {
+        // Half a turn is 128 steps
+        let a = (i % 128) as i32;
+        let p = a * (128 - a);
+        let v = (127 * 16 * p / (5 * 128 * 128 - 4 * p)) as i8;
+        t[i] = if i < 128 { v } else { -v };
+        i += 1;
+    }
+    t
+}
+
+const SIN: [i8; 256] = sin_table();
+
+/// -127..=127
+#[inline]
+pub(crate) fn sin(a: u8) -> i8 {
+    SIN[a as usize]
+}
+
+/// -127..=127
+#[inline]
+pub(crate) fn cos(a: u8) -> i8 {
+    SIN[a.wrapping_add(64) as usize]
+}
+
+/// Pick a hue for any byte, wrapping around the color wheel
+#[inline]
+fn hue(v: u8) -> Rgb555 {
+    HUES[(v as usize * HUES.len()) >> 8]
+}
+
+/// All the effects, to cycle through them
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Effect {
+    Fire,
+    Plasma,
+    Tunnel,
+    Rotozoom,
+    Copper,
+    Starfield,
+    Rain,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 7] = [
+        Effect::Fire,
+        Effect::Plasma,
+        Effect::Tunnel,
+        Effect::Rotozoom,
+        Effect::Copper,
+        Effect::Starfield,
+        Effect::Rain,
+    ];
+}
+
+/// Owns one of each effect and runs the selected one
+pub struct Demos {
+    effect: Effect,
+    fire: Fire,
+    plasma: Plasma,
+    tunnel: Tunnel,
+    rotozoom: Rotozoom,
+    copper: Copper,
+    starfield: Starfield,
+    rain: Rain,
+}
+
+impl Default for Demos {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Demos {
+    pub fn new() -> Self {
+        Self {
+            effect: Effect::Fire,
+            fire: Fire::new(),
+            plasma: Plasma::new(),
+            tunnel: Tunnel::new(),
+            rotozoom: Rotozoom::new(),
+            copper: Copper::new(),
+            starfield: Starfield::new(),
+            rain: Rain::new(),
+        }
+    }
+
+    pub fn effect(&self) -> Effect {
+        self.effect
+    }
+
+    pub fn set_effect(&mut self, effect: Effect) {
+        self.effect = effect;
+    }
+
+    /// Switch to the next effect, wrapping around
+    pub fn next(&mut self) {
+        let i = Effect::ALL.iter().position(|&e| e == self.effect).unwrap_or(0);
+        self.effect = Effect::ALL[(i + 1) % Effect::ALL.len()];
+    }
+
+    pub fn step(&mut self) {
+        match self.effect {
+            Effect::Fire => self.fire.step(),
+            Effect::Plasma => self.plasma.step(),
+            Effect::Tunnel => self.tunnel.step(),
+            Effect::Rotozoom => self.rotozoom.step(),
+            Effect::Copper => self.copper.step(),
+            Effect::Starfield => self.starfield.step(),
+            Effect::Rain => self.rain.step(),
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        match self.effect {
+            Effect::Fire => self.fire.draw(target),
+            Effect::Plasma => self.plasma.draw(target),
+            Effect::Tunnel => self.tunnel.draw(target),
+            Effect::Rotozoom => self.rotozoom.draw(target),
+            Effect::Copper => self.copper.draw(target),
+            Effect::Starfield => self.starfield.draw(target),
+            Effect::Rain => self.rain.draw(target),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Copper bars, bouncing horizontal bars on a raster background.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+use super::{H, W, cos, sin};
+
+const BARS: [Rgb555; 4] = [Rgb555::RED, Rgb555::GREEN, Rgb555::BLUE, Rgb555::MAGENTA];
+const HALF_HEIGHT: i32 = 2;
+
+pub struct Copper {
+    t: u8,
+}
+
+impl Default for Copper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Copper {
+    pub fn new() -> Self {
+        Self { t: 0 }
+    }
+
+    pub fn step(&mut self) {
+        self.t = self.t.wrapping_add(2);
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        // (depth, center row, color), bars swing around a cylinder
+        let mut bars = BARS.map(|c| (0i32, 0i32, c));
+        for (i, b) in bars.iter_mut().enumerate() {
+            let phase = self.t.wrapping_add(i as u8 * 40);
+            b.0 = cos(phase) as i32;
+            b.1 = H / 2 + sin(phase) as i32 * (H / 2 - HALF_HEIGHT - 1) / 127;
+        }
+        // Back to front
+        bars.sort_unstable_by_key(|b| b.0);
+
+        let mut rows = [Rgb555::BLACK; H as usize];
+        for &(_, cy, color) in bars.iter() {
+            for dy in -HALF_HEIGHT..=HALF_HEIGHT {
+                if let Some(r) = rows.get_mut((cy + dy) as usize) {
+                    // Shiny middle
+                    *r = if dy == 0 { Rgb555::WHITE } else { color };
+                }
+            }
+        }
+
+        let it = (0..W * H).map(|i| Pixel(Point::new(i % W, i / W), rows[(i / W) as usize]));
+        target.draw_iter(it)
+    }
+}
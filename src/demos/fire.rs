@@ -0,0 +1,67 @@
+//! Doom style fire.
+//!
+//! <https://fabiensanglard.net/doom_fire_psx/>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use rand::{RngCore, rngs::SmallRng};
+
+use super::{H, W};
+
+// Heat cools by 0.75 per row on average, so flames reach ~2/3 up the panel
+const MAX_HEAT: u8 = 14;
+
+pub struct Fire {
+    // Heat per pixel, with an extra always burning source row at the bottom
+    heat: [u8; (W * (H + 1)) as usize],
+    rng: SmallRng,
+}
+
+impl Default for Fire {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fire {
+    pub fn new() -> Self {
+        let mut heat = [0; (W * (H + 1)) as usize];
+        heat[(W * H) as usize..].fill(MAX_HEAT);
+        Self {
+            heat,
+            rng: crate::rng::small_rng(),
+        }
+    }
+
+    pub fn step(&mut self) {
+        for y in 1..=H {
+            for x in 0..W {
+                let r = self.rng.next_u32();
+                // Drift up and sideways, cooling down a bit on the way
+                let dx = (r & 3) as i32 - 1;
+                let src = (y * W + x) as usize;
+                let dst = ((y - 1) * W + (x + dx).rem_euclid(W)) as usize;
+                let cool = (r >> 2 & 1) + (r >> 3 & r >> 4 & 1);
+                self.heat[dst] = self.heat[src].saturating_sub(cool as u8);
+            }
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = self.heat[..(W * H) as usize].iter().enumerate().map(|(i, &h)| {
+            let x = i as i32 % W;
+            let y = i as i32 / W;
+            let color = match h {
+                0 => Rgb555::BLACK,
+                1..=6 => Rgb555::RED,
+                7..=12 => Rgb555::YELLOW,
+                _ => Rgb555::WHITE,
+            };
+            Pixel(Point::new(x, y), color)
+        });
+
+        target.draw_iter(it)
+    }
+}
@@ -0,0 +1,46 @@
+//! Sine plasma.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+use super::{H, W, hue, sin};
+
+pub struct Plasma {
+    t: u8,
+}
+
+impl Default for Plasma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plasma {
+    pub fn new() -> Self {
+        Self { t: 0 }
+    }
+
+    pub fn step(&mut self) {
+        self.t = self.t.wrapping_add(1);
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let t = self.t;
+        let it = (0..W * H).map(|i| {
+            let x = (i % W) as u8;
+            let y = (i / W) as u8;
+
+            let v = sin(x.wrapping_mul(8).wrapping_add(t)) as i32
+                + sin(y.wrapping_mul(6).wrapping_sub(t.wrapping_mul(2))) as i32
+                + sin(x.wrapping_add(y).wrapping_mul(4).wrapping_add(t.wrapping_mul(3))) as i32;
+            // -381..=381 to 0..=254, then slowly rotate the colors
+            let v = ((v + 381) / 3) as u8;
+
+            Pixel(Point::new(x as i32, y as i32), hue(v.wrapping_add(t)))
+        });
+
+        target.draw_iter(it)
+    }
+}
@@ -0,0 +1,106 @@
+//! Matrix digital rain.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use rand::{Rng, RngCore, rngs::SmallRng};
+
+use super::{H, W};
+
+#[derive(Clone, Copy)]
+struct Drop {
+    // Head row, starts above the panel
+    y: i32,
+    len: i32,
+    // Steps per move
+    delay: u8,
+    tick: u8,
+}
+
+pub struct Rain {
+    drops: [Drop; W as usize],
+    // Lit "glyph" pixels per column, flipped at random to flicker
+    glyphs: [u32; W as usize],
+    rng: SmallRng,
+}
+
+impl Default for Rain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rain {
+    pub fn new() -> Self {
+        let mut rain = Self {
+            drops: [Drop {
+                y: 0,
+                len: 0,
+                delay: 1,
+                tick: 0,
+            }; W as usize],
+            glyphs: [u32::MAX; W as usize],
+            rng: crate::rng::small_rng(),
+        };
+        for x in 0..W as usize {
+            rain.drops[x] = rain.spawn();
+            // Some start mid screen
+            rain.drops[x].y = rain.rng.gen_range(-H..H);
+        }
+        rain
+    }
+
+    fn spawn(&mut self) -> Drop {
+        Drop {
+            y: -self.rng.gen_range(0..H),
+            len: self.rng.gen_range(4..16),
+            delay: self.rng.gen_range(1..4),
+            tick: 0,
+        }
+    }
+
+    pub fn step(&mut self) {
+        for x in 0..W as usize {
+            let d = &mut self.drops[x];
+            d.tick += 1;
+            if d.tick >= d.delay {
+                d.tick = 0;
+                d.y += 1;
+            }
+            if d.y - d.len >= H {
+                self.drops[x] = self.spawn();
+            }
+        }
+
+        // Change a few glyphs, mostly back on
+        for _ in 0..4 {
+            let r = self.rng.next_u32();
+            let x = r as usize % W as usize;
+            let bit = 1 << ((r >> 8) % H as u32);
+            if r >> 16 & 3 == 0 {
+                self.glyphs[x] &= !bit;
+            } else {
+                self.glyphs[x] |= bit;
+            }
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = (0..W * H).map(|i| {
+            let x = i % W;
+            let y = i / W;
+            let d = &self.drops[x as usize];
+            let color = if y == d.y {
+                Rgb555::WHITE
+            } else if y < d.y && y > d.y - d.len && self.glyphs[x as usize] & (1 << y) != 0 {
+                Rgb555::GREEN
+            } else {
+                Rgb555::BLACK
+            };
+            Pixel(Point::new(x, y), color)
+        });
+
+        target.draw_iter(it)
+    }
+}
@@ -0,0 +1,56 @@
+//! Rotating and zooming checkerboard.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+use super::{H, W, cos, hue, sin};
+
+pub struct Rotozoom {
+    t: u8,
+}
+
+impl Default for Rotozoom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rotozoom {
+    pub fn new() -> Self {
+        Self { t: 0 }
+    }
+
+    pub fn step(&mut self) {
+        self.t = self.t.wrapping_add(1);
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let t = self.t;
+        // Zoom breathes between ~1/2x and ~1.5x
+        let zoom = 128 + sin(t.wrapping_mul(3)) as i32 / 2;
+        let c = cos(t) as i32 * zoom;
+        let s = sin(t) as i32 * zoom;
+        // Drift across the texture
+        let (ox, oy) = (t as i32 * 2, t as i32);
+
+        let it = (0..W * H).map(|i| {
+            let dx = i % W - W / 2;
+            let dy = i / W - H / 2;
+            let u = ((dx * c - dy * s) >> 13) + ox;
+            let v = ((dx * s + dy * c) >> 13) + oy;
+
+            // 4x4 texel checkerboard, tiles colored by their position
+            let color = if (u >> 2 ^ v >> 2) & 1 == 1 {
+                hue(((u >> 3) + (v >> 3)).wrapping_mul(40) as u8)
+            } else {
+                Rgb555::BLACK
+            };
+
+            Pixel(Point::new(i % W, i / W), color)
+        });
+
+        target.draw_iter(it)
+    }
+}
@@ -0,0 +1,87 @@
+//! 3D starfield flying towards the viewer.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use rand::{Rng, rngs::SmallRng};
+
+use super::{H, W};
+
+const STARS: usize = 48;
+const FAR: i32 = 256;
+const SPEED: i32 = 4;
+
+#[derive(Clone, Copy)]
+struct Star {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+pub struct Starfield {
+    stars: [Star; STARS],
+    rng: SmallRng,
+}
+
+impl Default for Starfield {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Starfield {
+    pub fn new() -> Self {
+        let mut sf = Self {
+            stars: [Star { x: 0, y: 0, z: 0 }; STARS],
+            rng: crate::rng::small_rng(),
+        };
+        for i in 0..STARS {
+            sf.stars[i] = sf.spawn();
+            // Spread out in depth to start with
+            sf.stars[i].z = sf.rng.gen_range(1..FAR);
+        }
+        sf
+    }
+
+    fn spawn(&mut self) -> Star {
+        Star {
+            x: self.rng.gen_range(-FAR..FAR),
+            y: self.rng.gen_range(-FAR..FAR),
+            z: FAR,
+        }
+    }
+
+    pub fn step(&mut self) {
+        for i in 0..STARS {
+            self.stars[i].z -= SPEED;
+            if self.stars[i].z <= 0 || Self::project(&self.stars[i]).is_none() {
+                self.stars[i] = self.spawn();
+            }
+        }
+    }
+
+    fn project(s: &Star) -> Option<Point> {
+        let p = Point::new(W / 2 + s.x * (W / 2) / s.z, H / 2 + s.y * (H / 2) / s.z);
+        ((0..W).contains(&p.x) && (0..H).contains(&p.y)).then_some(p)
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        target.clear(Rgb555::BLACK)?;
+
+        for s in self.stars.iter() {
+            if let Some(p) = Self::project(s) {
+                // Brighter as they get closer
+                let color = if s.z > FAR * 2 / 3 {
+                    Rgb555::BLUE
+                } else if s.z > FAR / 3 {
+                    Rgb555::CYAN
+                } else {
+                    Rgb555::WHITE
+                };
+                Pixel(p, color).draw(target)?;
+            }
+        }
+        Ok(())
+    }
+}
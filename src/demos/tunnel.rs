@@ -0,0 +1,98 @@
+//! Textured tunnel flythrough.
+//!
+//! Per pixel depth and angle are computed once, each frame only shifts
+//! the texture coordinates.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+use super::{H, W, hue};
+
+pub struct Tunnel {
+    depth: [u8; (W * H) as usize],
+    angle: [u8; (W * H) as usize],
+    t: u8,
+}
+
+impl Default for Tunnel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tunnel {
+    pub fn new() -> Self {
+        let mut depth = [0; (W * H) as usize];
+        let mut angle = [0; (W * H) as usize];
+        for y in 0..H {
+            for x in 0..W {
+                // Half pixel units from the panel center
+                let dx = 2 * x - (W - 1);
+                let dy = 2 * y - (H - 1);
+                let i = (y * W + x) as usize;
+                depth[i] = (2048 / isqrt(dx * dx + dy * dy)).min(255) as u8;
+                angle[i] = atan2(dy, dx);
+            }
+        }
+        Self { depth, angle, t: 0 }
+    }
+
+    pub fn step(&mut self) {
+        self.t = self.t.wrapping_add(1);
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = (0..(W * H) as usize).map(|i| {
+            let d = self.depth[i];
+            // Spin and fly forward
+            let u = self.angle[i].wrapping_add(self.t);
+            let v = d.wrapping_add(self.t.wrapping_mul(2));
+
+            let color = if d > 160 {
+                // Too far to see
+                Rgb555::BLACK
+            } else if (u >> 5 ^ v >> 3) & 1 == 1 {
+                hue(v)
+            } else {
+                Rgb555::BLACK
+            };
+
+            Pixel(Point::new(i as i32 % W, i as i32 / W), color)
+        });
+
+        target.draw_iter(it)
+    }
+}
+
+fn isqrt(n: i32) -> i32 {
+    let mut r = 0;
+    while (r + 1) * (r + 1) <= n {
+        r += 1;
+    }
+    r
+}
+
+/// 256 steps per turn, within ~1 step
+fn atan2(y: i32, x: i32) -> u8 {
+    let (ax, ay) = (x.abs(), y.abs());
+    if ax == 0 && ay == 0 {
+        return 0;
+    }
+
+    // atan(r) ~ pi/4 r + 0.273 r (1 - r) for r in [0, 1], 0.273 rad is ~11 steps
+    let octant = |lo: i32, hi: i32| {
+        let r = 256 * lo / hi;
+        (32 * r + 11 * r * (256 - r) / 256) / 256
+    };
+    let a = if ax >= ay { octant(ay, ax) } else { 64 - octant(ax, ay) };
+
+    let a = match (x >= 0, y >= 0) {
+        (true, true) => a,
+        (false, true) => 128 - a,
+        (false, false) => 128 + a,
+        (true, false) => 256 - a,
+    };
+    a as u8
+}
@@ -2,7 +2,7 @@
 //! Matrix implementation of embedded graphics API
 //!
 
-use embedded_graphics::{pixelcolor::Rgb555, prelude::*, primitives::Rectangle};
+use embedded_graphics::{image::GetPixel, pixelcolor::Rgb555, prelude::*, primitives::Rectangle};
 
 use crate::matrix::LedMatrix;
 
@@ -33,6 +33,13 @@ impl LedMatrixDisplay {
 
     /// Needs to be run in the loop to keep updating matrix
     pub fn run(&self, lm: &mut LedMatrix<'_>) {
+        self.run_with_brightness(lm, 100);
+    }
+
+    /// Like `run`, with the rows lit for `percent` of the time.
+    /// The scan takes as long at any brightness so there's no flicker.
+    pub fn run_with_brightness(&self, lm: &mut LedMatrix<'_>, percent: u8) {
+        let on = PULSE_DELAY_CYCLES * percent.min(100) as u32 / 100;
         for row in 0..16 {
             lm.oe(false);
             lm.addr(row);
@@ -41,8 +48,14 @@ impl LedMatrixDisplay {
                 lm.clk();
             }
             lm.lat();
-            lm.oe(true);
-            cortex_m::asm::delay(PULSE_DELAY_CYCLES);
+            if on > 0 {
+                lm.oe(true);
+                cortex_m::asm::delay(on);
+            }
+            if on < PULSE_DELAY_CYCLES {
+                lm.oe(false);
+                cortex_m::asm::delay(PULSE_DELAY_CYCLES - on);
+            }
         }
     }
 }
@@ -103,3 +116,22 @@ impl DrawTarget for LedMatrixDisplay {
         Ok(())
     }
 }
+
+/// Reads back what is drawn, the panel's eight colors at full intensity
+impl GetPixel for LedMatrixDisplay {
+    type Color = Rgb555;
+
+    fn pixel(&self, p: Point) -> Option<Self::Color> {
+        if !(0..32).contains(&p.x) || !(0..32).contains(&p.y) {
+            return None;
+        }
+        let (x, y) = (p.x as usize, p.y as usize);
+        let c = if y < 16 {
+            self.framebuffer[x + y * 32] & 0x0f
+        } else {
+            self.framebuffer[x + (y - 16) * 32] >> 4
+        };
+        let on = |bit: u8| if c & bit != 0 { 0x1f } else { 0 };
+        Some(Rgb555::new(on(4), on(2), on(1)))
+    }
+}
@@ -0,0 +1,302 @@
+//! Every effect behind one type, for binaries that switch between them at
+//! runtime.
+//!
+//! Only the selected effect is kept in memory, switching builds it from
+//! scratch.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+use crate::ants::Ants;
+use crate::boids::Boids;
+use crate::cca::Cca;
+use crate::demos::Demos;
+use crate::fluid::Fluid;
+use crate::fractal::Fractal;
+use crate::gol::Gol;
+use crate::gray_scott::GrayScott;
+use crate::hex::{HexAnts, HexLife};
+use crate::lenia::Lenia;
+use crate::lorenz::{Integrator, Lorenz};
+use crate::maze::{self, Maze, Phase};
+use crate::sand::Sand;
+use crate::sort::{self, Sorter};
+use crate::wireframe::Wireframe;
+use crate::wireworld::Wireworld;
+
+// Frames to show a solved maze or sorted bars before starting over
+const HOLD_FRAMES: u32 = 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Effect {
+    Gol,
+    Cca,
+    Ants,
+    GrayScott,
+    Lorenz,
+    Boids,
+    HexAnts,
+    HexLife,
+    Lenia,
+    Sand,
+    Wireworld,
+    Fluid,
+    Fractal,
+    Demos,
+    Maze,
+    Sort,
+    Wireframe,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 17] = [
+        Effect::Gol,
+        Effect::Cca,
+        Effect::Ants,
+        Effect::GrayScott,
+        Effect::Lorenz,
+        Effect::Boids,
+        Effect::HexAnts,
+        Effect::HexLife,
+        Effect::Lenia,
+        Effect::Sand,
+        Effect::Wireworld,
+        Effect::Fluid,
+        Effect::Fractal,
+        Effect::Demos,
+        Effect::Maze,
+        Effect::Sort,
+        Effect::Wireframe,
+    ];
+
+    /// Same as the name of the binary that runs only this effect
+    pub fn name(self) -> &'static str {
+        match self {
+            Effect::Gol => "gol",
+            Effect::Cca => "cca",
+            Effect::Ants => "ants",
+            Effect::GrayScott => "gray_scott",
+            Effect::Lorenz => "lorenz",
+            Effect::Boids => "boids",
+            Effect::HexAnts => "hex_ants",
+            Effect::HexLife => "hex_life",
+            Effect::Lenia => "lenia",
+            Effect::Sand => "sand",
+            Effect::Wireworld => "wireworld",
+            Effect::Fluid => "fluid",
+            Effect::Fractal => "fractal",
+            Effect::Demos => "demos",
+            Effect::Maze => "maze",
+            Effect::Sort => "sort",
+            Effect::Wireframe => "wireframe",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Effect> {
+        Self::ALL.into_iter().find(|e| e.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Delay between frames the standalone binary uses
+    pub fn frame_ms(self) -> u32 {
+        match self {
+            Effect::Gol | Effect::Wireworld => 100,
+            Effect::Cca => 50,
+            Effect::HexLife => 150,
+            Effect::Lorenz | Effect::HexAnts | Effect::Sort => 20,
+            Effect::Boids | Effect::Sand | Effect::Demos | Effect::Maze | Effect::Wireframe => 30,
+            Effect::Ants | Effect::GrayScott | Effect::Lenia | Effect::Fluid | Effect::Fractal => 10,
+        }
+    }
+}
+
+// Sized by the largest effect, which is fine since there is only ever one
+#[allow(clippy::large_enum_variant)]
+enum State {
+    Gol(Gol),
+    Cca(Cca),
+    Ants(Ants),
+    GrayScott(GrayScott),
+    Lorenz(Lorenz),
+    Boids(Boids),
+    HexAnts(HexAnts),
+    HexLife(HexLife),
+    Lenia(Lenia),
+    Sand(Sand),
+    Wireworld(Wireworld),
+    Fluid(Fluid),
+    Fractal(Fractal),
+    Demos(Demos),
+    Maze(Maze),
+    Sort(Sorter),
+    Wireframe(Wireframe),
+}
+
+impl State {
+    fn new(effect: Effect) -> Self {
+        match effect {
+            Effect::Gol => {
+                let mut gol = Gol::new();
+                gol.randomize();
+                gol.glider();
+                State::Gol(gol)
+            }
+            Effect::Cca => State::Cca(Cca::new()),
+            Effect::Ants => State::Ants(Ants::new()),
+            Effect::GrayScott => State::GrayScott(GrayScott::new()),
+            Effect::Lorenz => State::Lorenz(Lorenz::with_particles(4, Integrator::Rk4)),
+            Effect::Boids => State::Boids(Boids::new()),
+            Effect::HexAnts => State::HexAnts(HexAnts::new()),
+            Effect::HexLife => State::HexLife(HexLife::new()),
+            Effect::Lenia => State::Lenia(Lenia::new()),
+            Effect::Sand => State::Sand(Sand::new()),
+            Effect::Wireworld => State::Wireworld(Wireworld::new()),
+            Effect::Fluid => State::Fluid(Fluid::new()),
+            Effect::Fractal => {
+                let mut fractal = Fractal::new();
+                fractal.render();
+                State::Fractal(fractal)
+            }
+            Effect::Demos => State::Demos(Demos::new()),
+            Effect::Maze => State::Maze(Maze::new()),
+            Effect::Sort => State::Sort(Sorter::new()),
+            Effect::Wireframe => State::Wireframe(Wireframe::new()),
+        }
+    }
+}
+
+/// The selected effect
+pub struct Effects {
+    effect: Effect,
+    state: State,
+    frame: u32,
+    // Frames since a maze or sort finished
+    held: u32,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Effects {
+    pub fn new() -> Self {
+        Self::with_effect(Effect::Gol)
+    }
+
+    pub fn with_effect(effect: Effect) -> Self {
+        Self {
+            effect,
+            state: State::new(effect),
+            frame: 0,
+            held: 0,
+        }
+    }
+
+    pub fn effect(&self) -> Effect {
+        self.effect
+    }
+
+    /// Switches to a freshly started effect
+    pub fn set_effect(&mut self, effect: Effect) {
+        self.effect = effect;
+        self.restart();
+    }
+
+    /// Starts the current effect over, e.g. after changing the random seed
+    pub fn restart(&mut self) {
+        self.state = State::new(self.effect);
+        self.frame = 0;
+        self.held = 0;
+    }
+
+    /// Frames since the effect (re)started
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn step(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+        match &mut self.state {
+            State::Gol(e) => e.step(),
+            State::Cca(e) => e.step(),
+            State::Ants(e) => e.step(),
+            State::GrayScott(e) => {
+                // Slow to develop otherwise
+                for _ in 0..8 {
+                    e.step();
+                }
+            }
+            State::Lorenz(e) => e.step(),
+            State::Boids(e) => e.step(),
+            State::HexAnts(e) => e.step(),
+            State::HexLife(e) => e.step(),
+            State::Lenia(e) => e.step(),
+            State::Sand(e) => e.step(),
+            State::Wireworld(e) => e.step(),
+            State::Fluid(e) => e.step(),
+            State::Fractal(e) => {
+                e.step();
+                e.render();
+            }
+            State::Demos(e) => e.step(),
+            State::Maze(e) => {
+                if e.phase() != Phase::Solved {
+                    e.step();
+                } else if hold(&mut self.held) {
+                    e.set_generator(next(&maze::Generator::ALL, e.generator()));
+                    e.set_solver(next(&maze::Solver::ALL, e.solver()));
+                    e.reset();
+                }
+            }
+            State::Sort(e) => {
+                if !e.is_sorted() {
+                    e.step();
+                } else if hold(&mut self.held) {
+                    e.set_algorithm(next(&sort::Algorithm::ALL, e.algorithm()));
+                }
+            }
+            State::Wireframe(e) => e.step(),
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        match &self.state {
+            State::Gol(e) => e.draw(target),
+            State::Cca(e) => e.draw(target),
+            State::Ants(e) => e.draw(target),
+            State::GrayScott(e) => e.draw(target),
+            State::Lorenz(e) => e.draw(target),
+            State::Boids(e) => e.draw(target),
+            State::HexAnts(e) => e.draw(target),
+            State::HexLife(e) => e.draw(target),
+            State::Lenia(e) => e.draw(target),
+            State::Sand(e) => e.draw(target),
+            State::Wireworld(e) => e.draw(target),
+            State::Fluid(e) => e.draw(target),
+            State::Fractal(e) => e.draw(target),
+            State::Demos(e) => e.draw(target),
+            State::Maze(e) => e.draw(target),
+            State::Sort(e) => e.draw(target),
+            State::Wireframe(e) => e.draw(target),
+        }
+    }
+}
+
+/// Counts frames spent finished, true once it's time to start over
+fn hold(held: &mut u32) -> bool {
+    *held += 1;
+    if *held < HOLD_FRAMES {
+        return false;
+    }
+    *held = 0;
+    true
+}
+
+/// The one after `cur` in `all`, wrapping around
+fn next<T: Copy + PartialEq>(all: &[T], cur: T) -> T {
+    let i = all.iter().position(|&a| a == cur).unwrap_or(0);
+    all[(i + 1) % all.len()]
+}
@@ -0,0 +1,282 @@
+//!
+//! Just enough FAT for a small USB drive: formatting it as FAT12 with one
+//! starter file, and reading the files in the root directory.
+//!
+//! Reading works on the whole volume as a byte slice, e.g. memory mapped
+//! flash, and follows cluster chains so files don't have to be contiguous.
+//! FAT16 volumes read too, in case the host reformats the drive.
+//!
+
+use heapless::String;
+
+use crate::msc::{BLOCK_SIZE, BlockDevice, BlockError};
+
+const DIR_ENTRY_SIZE: usize = 32;
+
+const ATTR_HIDDEN: u8 = 0x02;
+const ATTR_SYSTEM: u8 = 0x04;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+const DELETED: u8 = 0xe5;
+
+// FAT12 stops at 4084 clusters, one block per cluster keeps a megabyte under that
+const ROOT_ENTRIES: u16 = 64;
+const FAT_COUNT: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum FatError {
+    /// No boot sector signature, e.g. erased flash
+    Unformatted,
+    /// Formatted as something else
+    Unsupported,
+}
+
+/// Writes an empty FAT12 volume over `device` with `file` in it
+pub fn format(device: &mut impl BlockDevice, label: &[u8; 11], file: (&[u8; 11], &[u8])) -> Result<(), BlockError> {
+    let total = device.block_count();
+    let root_blocks = (ROOT_ENTRIES as u32 * DIR_ENTRY_SIZE as u32).div_ceil(BLOCK_SIZE as u32);
+    // A byte and a half per cluster, a few blocks of slack don't matter
+    let fat_blocks = (total * 3 / 2).div_ceil(BLOCK_SIZE as u32);
+    let (name, contents) = file;
+    if contents.len() > BLOCK_SIZE {
+        return Err(BlockError::OutOfRange);
+    }
+
+    let mut block = [0u8; BLOCK_SIZE];
+    block[0..3].copy_from_slice(&[0xeb, 0x3c, 0x90]);
+    block[3..11].copy_from_slice(b"LEDMATRX");
+    block[11..13].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+    // Blocks per cluster, reserved blocks
+    block[13] = 1;
+    block[14..16].copy_from_slice(&1u16.to_le_bytes());
+    block[16] = FAT_COUNT;
+    block[17..19].copy_from_slice(&ROOT_ENTRIES.to_le_bytes());
+    block[19..21].copy_from_slice(&(total as u16).to_le_bytes());
+    block[21] = 0xf8;
+    block[22..24].copy_from_slice(&(fat_blocks as u16).to_le_bytes());
+    // Made up geometry, nothing looks at it
+    block[24..26].copy_from_slice(&32u16.to_le_bytes());
+    block[26..28].copy_from_slice(&2u16.to_le_bytes());
+    // Extended boot record
+    block[36] = 0x80;
+    block[38] = 0x29;
+    block[39..43].copy_from_slice(&0x4c45_4400u32.to_le_bytes());
+    block[43..54].copy_from_slice(label);
+    block[54..62].copy_from_slice(b"FAT12   ");
+    block[510..512].copy_from_slice(&[0x55, 0xaa]);
+    device.write_block(0, &block)?;
+
+    // Media byte, end of chain marker, then the file in cluster 2
+    let mut lba = 1;
+    for _ in 0..FAT_COUNT {
+        for i in 0..fat_blocks {
+            block.fill(0);
+            if i == 0 {
+                block[..6].copy_from_slice(&[0xf8, 0xff, 0xff, 0xff, 0x0f, 0x00]);
+            }
+            device.write_block(lba, &block)?;
+            lba += 1;
+        }
+    }
+
+    for i in 0..root_blocks {
+        block.fill(0);
+        if i == 0 {
+            block[0..11].copy_from_slice(label);
+            block[11] = ATTR_VOLUME_ID;
+            let entry = &mut block[DIR_ENTRY_SIZE..2 * DIR_ENTRY_SIZE];
+            entry[0..11].copy_from_slice(name);
+            entry[11] = ATTR_ARCHIVE;
+            entry[26..28].copy_from_slice(&2u16.to_le_bytes());
+            entry[28..32].copy_from_slice(&(contents.len() as u32).to_le_bytes());
+        }
+        device.write_block(lba, &block)?;
+        lba += 1;
+    }
+
+    block.fill(0);
+    block[..contents.len()].copy_from_slice(contents);
+    device.write_block(lba, &block)?;
+    device.flush()
+}
+
+/// A FAT12 or FAT16 volume
+#[derive(Clone, Copy)]
+pub struct Volume<'a> {
+    data: &'a [u8],
+    fat: usize,
+    root: usize,
+    root_entries: usize,
+    clusters_start: usize,
+    cluster_size: usize,
+    clusters: u32,
+    fat16: bool,
+}
+
+impl<'a> Volume<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, FatError> {
+        if data.len() < BLOCK_SIZE || data[510..512] != [0x55, 0xaa] {
+            return Err(FatError::Unformatted);
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]) as usize;
+        let block_size = u16_at(11);
+        let cluster_blocks = data[13] as usize;
+        let reserved = u16_at(14);
+        let fats = data[16] as usize;
+        let root_entries = u16_at(17);
+        let total = match u16_at(19) {
+            0 => u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize,
+            n => n,
+        };
+        let fat_blocks = u16_at(22);
+        if !matches!(block_size, 512 | 1024 | 2048 | 4096) || cluster_blocks == 0 || fats == 0 || fat_blocks == 0 {
+            return Err(FatError::Unsupported);
+        }
+
+        let fat = reserved * block_size;
+        let root = fat + fats * fat_blocks * block_size;
+        let clusters_start = root + root_entries * DIR_ENTRY_SIZE;
+        let cluster_size = cluster_blocks * block_size;
+        let end = total.saturating_mul(block_size).min(data.len());
+        if clusters_start > end {
+            return Err(FatError::Unsupported);
+        }
+        let clusters = ((end - clusters_start) / cluster_size) as u32;
+        // FAT32 has no fixed root directory
+        if root_entries == 0 || clusters >= 65525 {
+            return Err(FatError::Unsupported);
+        }
+        Ok(Volume {
+            data,
+            fat,
+            root,
+            root_entries,
+            clusters_start,
+            cluster_size,
+            clusters,
+            fat16: clusters >= 4085,
+        })
+    }
+
+    /// Files in the root directory in the order they were created, without
+    /// the hidden ones hosts leave behind
+    pub fn files(&self) -> impl Iterator<Item = DirEntry> + 'a {
+        let skip = ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID | ATTR_DIRECTORY;
+        let root = &self.data[self.root..self.root + self.root_entries * DIR_ENTRY_SIZE];
+        let (entries, _) = root.as_chunks::<DIR_ENTRY_SIZE>();
+        entries
+            .iter()
+            .take_while(|e| e[0] != 0)
+            .filter(move |e| e[0] != DELETED && e[11] != ATTR_LONG_NAME && e[11] & skip == 0)
+            .map(|e| DirEntry {
+                name: e[0..11].try_into().unwrap(),
+                cluster: u16::from_le_bytes([e[26], e[27]]),
+                size: u32::from_le_bytes(e[28..32].try_into().unwrap()),
+            })
+    }
+
+    /// `name` as stored, e.g. `b"CONFIG  TXT"`
+    pub fn find(&self, name: &[u8; 11]) -> Option<DirEntry> {
+        self.files().find(|e| &e.name == name)
+    }
+
+    pub fn open(&self, entry: &DirEntry) -> FileReader<'a> {
+        FileReader {
+            volume: *self,
+            cluster: entry.cluster as u32,
+            offset: 0,
+            left: entry.size,
+        }
+    }
+
+    /// The cluster after `cluster`, `None` at the end of the chain or on a bad link
+    fn next_cluster(&self, cluster: u32) -> Option<u32> {
+        let pair = |i: usize| Some(u16::from_le_bytes([*self.data.get(i)?, *self.data.get(i + 1)?]) as u32);
+        let next = if self.fat16 {
+            pair(self.fat + cluster as usize * 2)?
+        } else {
+            let pair = pair(self.fat + cluster as usize * 3 / 2)?;
+            if cluster.is_multiple_of(2) {
+                pair & 0xfff
+            } else {
+                pair >> 4
+            }
+        };
+        self.valid(next).then_some(next)
+    }
+
+    fn valid(&self, cluster: u32) -> bool {
+        (2..self.clusters + 2).contains(&cluster)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DirEntry {
+    /// 8.3 name padded with spaces, without the dot
+    pub name: [u8; 11],
+    cluster: u16,
+    pub size: u32,
+}
+
+impl DirEntry {
+    pub fn extension(&self) -> &[u8] {
+        self.name[8..].trim_ascii_end()
+    }
+
+    /// e.g. `NYANCA~1.GIF`
+    pub fn display_name(&self) -> String<12> {
+        let mut s = String::new();
+        for &c in self.name[..8].trim_ascii_end() {
+            let _ = s.push(c as char);
+        }
+        if !self.extension().is_empty() {
+            let _ = s.push('.');
+            for &c in self.extension() {
+                let _ = s.push(c as char);
+            }
+        }
+        s
+    }
+}
+
+impl defmt::Format for DirEntry {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{} ({} bytes)", self.display_name().as_str(), self.size)
+    }
+}
+
+/// A file's bytes in order, cloning it starts another read from the same spot
+#[derive(Clone)]
+pub struct FileReader<'a> {
+    volume: Volume<'a>,
+    cluster: u32,
+    /// Into `cluster`
+    offset: usize,
+    left: u32,
+}
+
+impl Iterator for FileReader<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.left == 0 || !self.volume.valid(self.cluster) {
+            return None;
+        }
+        if self.offset == self.volume.cluster_size {
+            self.cluster = self.volume.next_cluster(self.cluster)?;
+            self.offset = 0;
+        }
+        let start = self.volume.clusters_start + (self.cluster - 2) as usize * self.volume.cluster_size;
+        let byte = *self.volume.data.get(start + self.offset)?;
+        self.offset += 1;
+        self.left -= 1;
+        Some(byte)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.left as usize))
+    }
+}
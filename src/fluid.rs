@@ -0,0 +1,264 @@
+//! Stable fluids in fixed point, with drifting dye jets.
+//!
+//! Jos Stam, "Real-Time Fluid Dynamics for Games":
+//! <https://www.dgp.toronto.edu/public_user/stam/reality/Research/pdf/GDC03.pdf>
+//!
+//! Velocities are in cells per step, so there's no separate time step.
+//! Inviscid: advection smears the velocity enough on its own.
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use fixed::types::I16F16;
+use fixed_macro::fixed;
+
+use crate::demos::{cos, sin};
+
+const N: usize = 32;
+// Plus a boundary cell on each side
+const SIZE: usize = (N + 2) * (N + 2);
+
+type Field = [I16F16; SIZE];
+
+// Dye diffusion rate and 1 / (1 + 4 * rate) for the Gauss-Seidel solve
+const DIFF: I16F16 = fixed!(0.02: I16F16);
+const DIFF_INV: I16F16 = fixed!(0.9259259: I16F16);
+const DIFF_ITERS: usize = 4;
+const PROJECT_ITERS: usize = 10;
+
+// Per step
+const DYE_DECAY: I16F16 = fixed!(0.95: I16F16);
+const DYE_RATE: I16F16 = fixed!(0.5: I16F16);
+const MAX_DYE: I16F16 = fixed!(4.0: I16F16);
+// Jet speed in cells per step, over 127 to scale the sin table
+const FORCE: I16F16 = fixed!(0.0047: I16F16);
+
+const SOURCES: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    /// Continuous across the wall
+    Scalar,
+    /// Horizontal velocity, reflected off the side walls
+    U,
+    /// Vertical velocity, reflected off the top and bottom
+    V,
+}
+
+/// Dye jet moving on a Lissajous curve while it turns around
+#[derive(Clone, Copy)]
+struct Source {
+    orbit: u8,
+    speed: u8,
+    dir: u8,
+    spin: u8,
+}
+
+pub struct Fluid {
+    u: Field,
+    v: Field,
+    u0: Field,
+    v0: Field,
+    dye: Field,
+    dye0: Field,
+    sources: [Source; SOURCES],
+}
+
+impl Default for Fluid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fluid {
+    pub fn new() -> Self {
+        Self {
+            u: [I16F16::ZERO; SIZE],
+            v: [I16F16::ZERO; SIZE],
+            u0: [I16F16::ZERO; SIZE],
+            v0: [I16F16::ZERO; SIZE],
+            dye: [I16F16::ZERO; SIZE],
+            dye0: [I16F16::ZERO; SIZE],
+            sources: [
+                Source {
+                    orbit: 0,
+                    speed: 1,
+                    dir: 0,
+                    spin: 3,
+                },
+                Source {
+                    orbit: 85,
+                    speed: 2,
+                    dir: 85,
+                    spin: 253,
+                },
+                Source {
+                    orbit: 170,
+                    speed: 1,
+                    dir: 170,
+                    spin: 5,
+                },
+            ],
+        }
+    }
+
+    /// Push the fluid at a cell with a velocity in cells per step and add dye there,
+    /// e.g. from user input
+    pub fn splat(&mut self, x: i32, y: i32, fx: I16F16, fy: I16F16) {
+        if !(0..N as i32).contains(&x) || !(0..N as i32).contains(&y) {
+            return;
+        }
+        let i = ix(x as usize + 1, y as usize + 1);
+        self.u[i] += fx;
+        self.v[i] += fy;
+        self.dye[i] = (self.dye[i] + DYE_RATE).min(MAX_DYE);
+    }
+
+    pub fn step(&mut self) {
+        for s in 0..SOURCES {
+            let src = &mut self.sources[s];
+            src.orbit = src.orbit.wrapping_add(src.speed);
+            src.dir = src.dir.wrapping_add(src.spin);
+            let Source { orbit, dir, .. } = *src;
+
+            let x = N as i32 / 2 + sin(orbit) as i32 * 11 / 127;
+            let y = N as i32 / 2 + sin(orbit.wrapping_mul(2).wrapping_add(64)) as i32 * 11 / 127;
+            let fx = FORCE * cos(dir) as i32;
+            let fy = FORCE * sin(dir) as i32;
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                self.splat(x + dx, y + dy, fx, fy);
+            }
+        }
+
+        // Velocity: make it divergence free, move it along itself, and again
+        project(&mut self.u, &mut self.v, &mut self.u0, &mut self.v0);
+        core::mem::swap(&mut self.u, &mut self.u0);
+        core::mem::swap(&mut self.v, &mut self.v0);
+        advect(Bound::U, &mut self.u, &self.u0, &self.u0, &self.v0);
+        advect(Bound::V, &mut self.v, &self.v0, &self.u0, &self.v0);
+        project(&mut self.u, &mut self.v, &mut self.u0, &mut self.v0);
+
+        // Dye: spread out a bit, then ride the velocity
+        core::mem::swap(&mut self.dye, &mut self.dye0);
+        diffuse(&mut self.dye, &self.dye0);
+        core::mem::swap(&mut self.dye, &mut self.dye0);
+        advect(Bound::Scalar, &mut self.dye, &self.dye0, &self.u, &self.v);
+
+        for d in self.dye.iter_mut() {
+            *d *= DYE_DECAY;
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = (0..N * N).map(|p| {
+            let (x, y) = (p % N, p / N);
+            let d = self.dye[ix(x + 1, y + 1)];
+
+            let color = if d < fixed!(0.05: I16F16) {
+                Rgb555::BLACK
+            } else if d < fixed!(0.15: I16F16) {
+                Rgb555::BLUE
+            } else if d < fixed!(0.35: I16F16) {
+                Rgb555::MAGENTA
+            } else if d < fixed!(0.7: I16F16) {
+                Rgb555::RED
+            } else if d < fixed!(1.5: I16F16) {
+                Rgb555::YELLOW
+            } else {
+                Rgb555::WHITE
+            };
+
+            Pixel(Point::new(x as i32, y as i32), color)
+        });
+
+        target.draw_iter(it)
+    }
+}
+
+fn ix(i: usize, j: usize) -> usize {
+    i + (N + 2) * j
+}
+
+/// Fill the boundary cells from their inner neighbors
+fn set_bound(b: Bound, x: &mut Field) {
+    for i in 1..=N {
+        let flip = |v: I16F16, wall: Bound| if b == wall { -v } else { v };
+        x[ix(0, i)] = flip(x[ix(1, i)], Bound::U);
+        x[ix(N + 1, i)] = flip(x[ix(N, i)], Bound::U);
+        x[ix(i, 0)] = flip(x[ix(i, 1)], Bound::V);
+        x[ix(i, N + 1)] = flip(x[ix(i, N)], Bound::V);
+    }
+    x[ix(0, 0)] = (x[ix(1, 0)] + x[ix(0, 1)]) / 2;
+    x[ix(0, N + 1)] = (x[ix(1, N + 1)] + x[ix(0, N)]) / 2;
+    x[ix(N + 1, 0)] = (x[ix(N, 0)] + x[ix(N + 1, 1)]) / 2;
+    x[ix(N + 1, N + 1)] = (x[ix(N, N + 1)] + x[ix(N + 1, N)]) / 2;
+}
+
+fn neighbors(x: &Field, i: usize, j: usize) -> I16F16 {
+    x[ix(i - 1, j)] + x[ix(i + 1, j)] + x[ix(i, j - 1)] + x[ix(i, j + 1)]
+}
+
+/// Implicit diffusion of `x0` into `x`, stable for any rate
+fn diffuse(x: &mut Field, x0: &Field) {
+    for _ in 0..DIFF_ITERS {
+        for j in 1..=N {
+            for i in 1..=N {
+                x[ix(i, j)] = (x0[ix(i, j)] + DIFF * neighbors(x, i, j)) * DIFF_INV;
+            }
+        }
+        set_bound(Bound::Scalar, x);
+    }
+}
+
+/// Semi-Lagrangian advection: trace each cell back along the velocity
+/// and sample `d0` there
+fn advect(b: Bound, d: &mut Field, d0: &Field, u: &Field, v: &Field) {
+    let lo = fixed!(0.5: I16F16);
+    let hi = I16F16::from_num(N) + lo;
+    for j in 1..=N {
+        for i in 1..=N {
+            let x = (I16F16::from_num(i) - u[ix(i, j)]).clamp(lo, hi);
+            let y = (I16F16::from_num(j) - v[ix(i, j)]).clamp(lo, hi);
+
+            let (i0, j0) = (x.to_num::<usize>(), y.to_num::<usize>());
+            let (s1, t1) = (x.frac(), y.frac());
+            let (s0, t0) = (I16F16::ONE - s1, I16F16::ONE - t1);
+
+            d[ix(i, j)] = s0 * (t0 * d0[ix(i0, j0)] + t1 * d0[ix(i0, j0 + 1)])
+                + s1 * (t0 * d0[ix(i0 + 1, j0)] + t1 * d0[ix(i0 + 1, j0 + 1)]);
+        }
+    }
+    set_bound(b, d);
+}
+
+/// Remove the divergent part of the velocity, which is what makes it swirl.
+/// `p` and `div` are scratch space.
+fn project(u: &mut Field, v: &mut Field, p: &mut Field, div: &mut Field) {
+    for j in 1..=N {
+        for i in 1..=N {
+            div[ix(i, j)] = -((u[ix(i + 1, j)] - u[ix(i - 1, j)] + v[ix(i, j + 1)] - v[ix(i, j - 1)]) / 2);
+            p[ix(i, j)] = I16F16::ZERO;
+        }
+    }
+    set_bound(Bound::Scalar, div);
+    set_bound(Bound::Scalar, p);
+
+    for _ in 0..PROJECT_ITERS {
+        for j in 1..=N {
+            for i in 1..=N {
+                p[ix(i, j)] = (div[ix(i, j)] + neighbors(p, i, j)) / 4;
+            }
+        }
+        set_bound(Bound::Scalar, p);
+    }
+
+    for j in 1..=N {
+        for i in 1..=N {
+            u[ix(i, j)] -= (p[ix(i + 1, j)] - p[ix(i - 1, j)]) / 2;
+            v[ix(i, j)] -= (p[ix(i, j + 1)] - p[ix(i, j - 1)]) / 2;
+        }
+    }
+    set_bound(Bound::U, u);
+    set_bound(Bound::V, v);
+}
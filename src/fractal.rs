@@ -0,0 +1,344 @@
+//! Mandelbrot zoom and Julia set animation in fixed point.
+//!
+//! Pixels are independent, so a frame can be rendered by both cores at once
+//! through a [`PixelQueue`].
+//!
+//! <https://en.wikipedia.org/wiki/Mandelbrot_set>
+
+use core::cell::Cell;
+use core::sync::atomic::Ordering;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, Instant};
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use fixed::types::I5F27;
+use fixed_macro::fixed;
+use portable_atomic::{AtomicU8, AtomicUsize};
+
+use crate::demos::{cos, sin};
+
+const W: usize = 32;
+const H: usize = 32;
+
+/// |z| stays below 2 until it escapes, and squares and sums of that fit in
+/// +-16, leaving 27 fractional bits for zooming in ~100000x.
+pub type Fx = I5F27;
+
+const START_STEP: Fx = fixed!(0.09375: I5F27); // 3 / 32, whole set in view
+const MIN_STEP: Fx = fixed!(0.00000006: I5F27);
+const ZOOM: Fx = fixed!(0.97: I5F27);
+
+const MIN_ITER: u8 = 24;
+const MAX_ITER: u8 = 200;
+
+/// Julia c goes around a circle of radius 0.7885, this is that over 127
+const JULIA_RADIUS: Fx = fixed!(0.0062087: I5F27);
+const JULIA_STEP: Fx = fixed!(0.1: I5F27); // 3.2 / 32
+
+/// Spots on the Mandelbrot set boundary that stay interesting deep down
+const TARGETS: [(Fx, Fx); 5] = [
+    // Seahorse valley
+    (fixed!(-0.7436438870: I5F27), fixed!(0.1318259042: I5F27)),
+    // Elephant valley
+    (fixed!(0.2925755: I5F27), fixed!(-0.0149977: I5F27)),
+    // Mini Mandelbrot on the needle
+    (fixed!(-1.7497591: I5F27), fixed!(0.0: I5F27)),
+    // Spiral
+    (fixed!(-0.7746806106: I5F27), fixed!(0.1374168856: I5F27)),
+    // Dendrite tip
+    (fixed!(-0.1011: I5F27), fixed!(0.9563: I5F27)),
+];
+
+// Escaped points are banded by iteration count
+const PALETTE: [Rgb555; 6] = [
+    Rgb555::BLUE,
+    Rgb555::CYAN,
+    Rgb555::GREEN,
+    Rgb555::YELLOW,
+    Rgb555::RED,
+    Rgb555::MAGENTA,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Mode {
+    /// Zoom into one of the target points
+    Mandelbrot,
+    /// Orbit the Julia constant around the main cardioid
+    Julia,
+}
+
+/// Everything needed to render one frame
+#[derive(Clone, Copy)]
+pub struct View {
+    mode: Mode,
+    left: Fx,
+    top: Fx,
+    // Distance between pixels
+    step: Fx,
+    // Julia constant
+    c: (Fx, Fx),
+    max_iter: u8,
+}
+
+impl View {
+    /// Iterations until the point escapes, `max_iter` if it never does
+    pub fn iterations(&self, px: usize, py: usize) -> u8 {
+        let x = self.left + self.step * px as i32;
+        let y = self.top + self.step * py as i32;
+        let ((mut zx, mut zy), (cx, cy)) = match self.mode {
+            Mode::Mandelbrot => ((Fx::ZERO, Fx::ZERO), (x, y)),
+            Mode::Julia => ((x, y), self.c),
+        };
+
+        for n in 0..self.max_iter {
+            // Bail before squaring anything that could overflow
+            if zx.abs() > 2 || zy.abs() > 2 {
+                return n;
+            }
+            let x2 = zx * zx;
+            let y2 = zy * zy;
+            if x2 + y2 > 4 {
+                return n;
+            }
+            let xy = zx * zy;
+            zy = xy + xy + cy;
+            zx = x2 - y2 + cx;
+        }
+        self.max_iter
+    }
+
+    pub fn render_row(&self, py: usize, out: &mut [u8]) {
+        for (px, n) in out.iter_mut().enumerate().take(W) {
+            *n = self.iterations(px, py);
+        }
+    }
+}
+
+/// Hands out the pixels of a frame to whichever core asks next.
+///
+/// Meant to live in a `static`: the graphics core starts a frame and renders
+/// pixels until none are left, the core scanning the matrix picks up pixels
+/// between scans with [`PixelQueue::render_for`]. Deep in the zoom a whole
+/// row takes longer than a scan, a pixel doesn't.
+pub struct PixelQueue {
+    view: Mutex<CriticalSectionRawMutex, Cell<Option<View>>>,
+    next: AtomicUsize,
+    done: AtomicUsize,
+    iters: [AtomicU8; W * H],
+}
+
+impl Default for PixelQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PixelQueue {
+    pub const fn new() -> Self {
+        Self {
+            view: Mutex::new(Cell::new(None)),
+            next: AtomicUsize::new(W * H),
+            done: AtomicUsize::new(W * H),
+            iters: [const { AtomicU8::new(0) }; W * H],
+        }
+    }
+
+    fn start(&self, view: View) {
+        self.view.lock(|v| v.set(Some(view)));
+        self.done.store(0, Ordering::Release);
+        // Opens the queue, so goes last
+        self.next.store(0, Ordering::Release);
+    }
+
+    /// Render every pixel of the current frame nobody has taken yet
+    fn render_rest(&self) {
+        while self.render_next_pixel() {}
+    }
+
+    /// Render pixels of the current frame for about `budget`, then wait out
+    /// the rest of it, so the caller's timing doesn't depend on the work left
+    pub fn render_for(&self, budget: Duration) {
+        let end = Instant::now() + budget;
+        let mut last = Duration::from_ticks(0);
+        loop {
+            let start = Instant::now();
+            // Neighboring pixels take about as long, don't start one that won't fit
+            if start + last > end || !self.render_next_pixel() {
+                break;
+            }
+            last = Instant::now() - start;
+        }
+        while Instant::now() < end {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// False if there's none left
+    fn render_next_pixel(&self) -> bool {
+        // Keeps an idle queue from counting up forever
+        if self.next.load(Ordering::Acquire) >= W * H {
+            return false;
+        }
+        let i = self.next.fetch_add(1, Ordering::AcqRel);
+        if i >= W * H {
+            return false;
+        }
+        // Read after claiming: a new frame may have started since the check
+        // above, and it can't start another until this pixel is done
+        let Some(view) = self.view.lock(|v| v.get()) else {
+            return false;
+        };
+        self.iters[i].store(view.iterations(i % W, i / W), Ordering::Relaxed);
+        self.done.fetch_add(1, Ordering::Release);
+        true
+    }
+
+    fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire) >= W * H
+    }
+}
+
+pub struct Fractal {
+    mode: Mode,
+    target: usize,
+    center: (Fx, Fx),
+    step: Fx,
+    // Frames since the mode started
+    frame: u32,
+    iters: [u8; W * H],
+    // Of the rendered frame
+    max_iter: u8,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fractal {
+    pub fn new() -> Self {
+        let mut f = Self {
+            mode: Mode::Mandelbrot,
+            target: 0,
+            center: TARGETS[0],
+            step: START_STEP,
+            frame: 0,
+            iters: [0; W * H],
+            max_iter: MIN_ITER,
+        };
+        f.set_mode(Mode::Mandelbrot);
+        f
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Restart the animation in the given mode
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.frame = 0;
+        match mode {
+            Mode::Mandelbrot => {
+                self.center = TARGETS[self.target];
+                self.step = START_STEP;
+            }
+            Mode::Julia => {
+                self.center = (Fx::ZERO, Fx::ZERO);
+                self.step = JULIA_STEP;
+            }
+        }
+    }
+
+    /// What the current frame looks at
+    pub fn view(&self) -> View {
+        let half = self.step * (W as i32 / 2);
+        let (max_iter, c) = match self.mode {
+            // Deeper zooms need more iterations to resolve the boundary
+            Mode::Mandelbrot => (
+                (MIN_ITER as u32 + self.frame / 3).min(MAX_ITER as u32) as u8,
+                (Fx::ZERO, Fx::ZERO),
+            ),
+            Mode::Julia => {
+                let a = self.frame as u8;
+                (
+                    MIN_ITER * 2,
+                    (JULIA_RADIUS * cos(a) as i32, JULIA_RADIUS * sin(a) as i32),
+                )
+            }
+        };
+        View {
+            mode: self.mode,
+            left: self.center.0 - half,
+            top: self.center.1 - half,
+            step: self.step,
+            c,
+            max_iter,
+        }
+    }
+
+    /// Advance the animation: zoom in on the target until precision or
+    /// iterations run out, then a full turn of Julia sets, then the next target.
+    pub fn step(&mut self) {
+        self.frame += 1;
+        match self.mode {
+            Mode::Mandelbrot => {
+                self.step *= ZOOM;
+                // Nearly all black means the boundary needs more than MAX_ITER
+                let inside = self.iters.iter().filter(|&&n| n >= self.max_iter).count();
+                if self.step < MIN_STEP || inside > W * H * 15 / 16 {
+                    self.set_mode(Mode::Julia);
+                }
+            }
+            Mode::Julia => {
+                if self.frame >= 256 {
+                    self.target = (self.target + 1) % TARGETS.len();
+                    self.set_mode(Mode::Mandelbrot);
+                }
+            }
+        }
+    }
+
+    /// Render the current frame on this core
+    pub fn render(&mut self) {
+        let view = self.view();
+        self.max_iter = view.max_iter;
+        for (y, row) in self.iters.chunks_mut(W).enumerate() {
+            view.render_row(y, row);
+        }
+    }
+
+    /// Render the current frame with help from whoever else is working the queue
+    pub fn render_shared(&mut self, pixels: &PixelQueue) {
+        let view = self.view();
+        self.max_iter = view.max_iter;
+        pixels.start(view);
+        pixels.render_rest();
+        // The other core may still be finishing its last pixel
+        while !pixels.is_done() {
+            core::hint::spin_loop();
+        }
+        for (n, a) in self.iters.iter_mut().zip(pixels.iters.iter()) {
+            *n = a.load(Ordering::Relaxed);
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        // Slowly cycle the bands outwards
+        let shift = (self.frame / 4) as usize;
+        let it = self.iters.iter().enumerate().map(|(i, &n)| {
+            let color = if n >= self.max_iter {
+                Rgb555::BLACK
+            } else {
+                PALETTE[(n as usize + PALETTE.len() - shift % PALETTE.len()) % PALETTE.len()]
+            };
+            Pixel(Point::new((i % W) as i32, (i / W) as i32), color)
+        });
+
+        target.draw_iter(it)
+    }
+}
@@ -0,0 +1,364 @@
+//!
+//! GIF animations decoded a frame at a time from a byte stream into a 32x32
+//! canvas. Pictures are centered and cropped like `bmp`.
+//!
+//! "Restore to previous" disposal keeps the frame instead, that would take a
+//! second canvas.
+//!
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+
+const W: i32 = 32;
+const H: i32 = 32;
+
+const MAX_CODES: usize = 4096;
+
+// Browsers play frames with no or a tiny delay at this speed
+const DEFAULT_DELAY_MS: u32 = 100;
+
+const EXTENSION: u8 = 0x21;
+const GRAPHIC_CONTROL: u8 = 0xf9;
+const IMAGE: u8 = 0x2c;
+const TRAILER: u8 = 0x3b;
+
+const DISPOSE_BACKGROUND: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum GifError {
+    NotGif,
+    /// Ends inside a block
+    Truncated,
+    /// Bad block or compressed data
+    Corrupt,
+}
+
+struct Palette {
+    colors: [Rgb888; 256],
+    len: usize,
+}
+
+impl Palette {
+    const fn new() -> Self {
+        Palette {
+            colors: [Rgb888::BLACK; 256],
+            len: 0,
+        }
+    }
+
+    /// `packed` has the table flag in bit 7 and the size in the low bits
+    fn read(&mut self, packed: u8, data: &mut impl Iterator<Item = u8>) -> Result<(), GifError> {
+        self.len = if packed & 0x80 != 0 { 2 << (packed & 7) } else { 0 };
+        for color in &mut self.colors[..self.len] {
+            *color = Rgb888::new(byte(data)?, byte(data)?, byte(data)?);
+        }
+        Ok(())
+    }
+
+    fn get(&self, index: u8) -> Rgb888 {
+        self.colors[..self.len]
+            .get(index as usize)
+            .copied()
+            .unwrap_or(Rgb888::BLACK)
+    }
+}
+
+/// Code table and the buffer to unwind its strings
+struct Lzw {
+    prefix: [u16; MAX_CODES],
+    suffix: [u8; MAX_CODES],
+    stack: [u8; MAX_CODES],
+}
+
+/// Graphic control for the next image
+#[derive(Clone, Copy)]
+struct Control {
+    delay_ms: u32,
+    transparent: Option<u8>,
+    disposal: u8,
+}
+
+impl Control {
+    const NONE: Control = Control {
+        delay_ms: DEFAULT_DELAY_MS,
+        transparent: None,
+        disposal: 0,
+    };
+}
+
+pub struct Gif<I> {
+    data: I,
+    /// Where the logical screen goes on the canvas
+    offset: Point,
+    global: Palette,
+    local: Palette,
+    canvas: [[Rgb888; W as usize]; H as usize],
+    /// Cleared before the next frame is drawn
+    dispose: Option<Rectangle>,
+    lzw: Lzw,
+}
+
+impl<I: Iterator<Item = u8>> Gif<I> {
+    pub fn new(mut data: I) -> Result<Self, GifError> {
+        let mut signature = [0; 6];
+        for b in &mut signature {
+            *b = data.next().ok_or(GifError::NotGif)?;
+        }
+        if &signature != b"GIF87a" && &signature != b"GIF89a" {
+            return Err(GifError::NotGif);
+        }
+        let width = u16_le(&mut data)? as i32;
+        let height = u16_le(&mut data)? as i32;
+        let packed = byte(&mut data)?;
+        // Background color and aspect ratio, the background shows as black
+        skip(&mut data, 2)?;
+
+        let mut gif = Gif {
+            data,
+            offset: Point::new((W - width) / 2, (H - height) / 2),
+            global: Palette::new(),
+            local: Palette::new(),
+            canvas: [[Rgb888::BLACK; W as usize]; H as usize],
+            dispose: None,
+            lzw: Lzw {
+                prefix: [0; MAX_CODES],
+                suffix: [0; MAX_CODES],
+                stack: [0; MAX_CODES],
+            },
+        };
+        gif.global.read(packed, &mut gif.data)?;
+        Ok(gif)
+    }
+
+    /// Decodes the next frame into the canvas and returns how long to show
+    /// it, `None` after the last one
+    pub fn next_frame(&mut self) -> Result<Option<u32>, GifError> {
+        let mut control = Control::NONE;
+        loop {
+            match self.data.next() {
+                None | Some(TRAILER) => return Ok(None),
+                Some(EXTENSION) => {
+                    if byte(&mut self.data)? == GRAPHIC_CONTROL {
+                        let size = byte(&mut self.data)?;
+                        if size < 4 {
+                            return Err(GifError::Corrupt);
+                        }
+                        let packed = byte(&mut self.data)?;
+                        let delay_ms = u16_le(&mut self.data)? as u32 * 10;
+                        let transparent = byte(&mut self.data)?;
+                        skip(&mut self.data, size as usize - 4)?;
+                        control = Control {
+                            delay_ms: if delay_ms < 20 { DEFAULT_DELAY_MS } else { delay_ms },
+                            transparent: (packed & 1 != 0).then_some(transparent),
+                            disposal: (packed >> 2) & 7,
+                        };
+                    }
+                    skip_sub_blocks(&mut self.data)?;
+                }
+                Some(IMAGE) => {
+                    self.image(control)?;
+                    return Ok(Some(control.delay_ms));
+                }
+                Some(_) => return Err(GifError::Corrupt),
+            }
+        }
+    }
+
+    pub fn draw<D: DrawTarget<Color = Rgb888>>(&self, target: &mut D) -> Result<(), D::Error> {
+        let area = Rectangle::new(Point::zero(), Size::new(W as u32, H as u32));
+        target.fill_contiguous(&area, self.canvas.iter().flatten().copied())
+    }
+
+    fn image(&mut self, control: Control) -> Result<(), GifError> {
+        let left = u16_le(&mut self.data)? as i32;
+        let top = u16_le(&mut self.data)? as i32;
+        let width = u16_le(&mut self.data)? as u32;
+        let height = u16_le(&mut self.data)? as u32;
+        let packed = byte(&mut self.data)?;
+        self.local.read(packed, &mut self.data)?;
+        let interlaced = packed & 0x40 != 0;
+
+        if let Some(area) = self.dispose.take() {
+            for p in area.points() {
+                self.canvas[p.y as usize][p.x as usize] = Rgb888::BLACK;
+            }
+        }
+        let area = Rectangle::new(self.offset + Point::new(left, top), Size::new(width, height));
+        let canvas_area = Rectangle::new(Point::zero(), Size::new(W as u32, H as u32));
+        if control.disposal == DISPOSE_BACKGROUND {
+            self.dispose = Some(area.intersection(&canvas_area));
+        }
+
+        let Gif {
+            data,
+            global,
+            local,
+            canvas,
+            lzw,
+            ..
+        } = self;
+        let palette = if local.len > 0 { &*local } else { &*global };
+        let pixels = width * height;
+        let mut i = 0;
+        let mut put = |index: u8| {
+            if i < pixels && Some(index) != control.transparent {
+                let row = if interlaced {
+                    interlaced_row(i / width, height)
+                } else {
+                    i / width
+                };
+                let p = area.top_left + Point::new((i % width) as i32, row as i32);
+                if canvas_area.contains(p) {
+                    canvas[p.y as usize][p.x as usize] = palette.get(index);
+                }
+            }
+            i += 1;
+        };
+
+        let min_size = byte(data)? as u32;
+        if !(1..=11).contains(&min_size) {
+            return Err(GifError::Corrupt);
+        }
+        let clear = 1u16 << min_size;
+        let end = clear + 1;
+        let mut next = clear + 2;
+        let mut size = min_size + 1;
+        let mut prev: Option<u16> = None;
+
+        let mut blocks = SubBlocks { left: 0, done: false };
+        let mut bits = 0u32;
+        let mut nbits = 0;
+        'codes: loop {
+            while nbits < size {
+                match blocks.next(data)? {
+                    Some(b) => bits |= (b as u32) << nbits,
+                    None => break 'codes,
+                }
+                nbits += 8;
+            }
+            let code = (bits & ((1 << size) - 1)) as u16;
+            bits >>= size;
+            nbits -= size;
+
+            if code == clear {
+                next = clear + 2;
+                size = min_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                break;
+            }
+            let Some(p) = prev else {
+                if code >= clear {
+                    return Err(GifError::Corrupt);
+                }
+                put(code as u8);
+                prev = Some(code);
+                continue;
+            };
+            // A code not in the table yet can only be the one about to be added
+            let first = match code {
+                c if c < next => lzw.first(c, clear),
+                c if c == next => lzw.first(p, clear),
+                _ => return Err(GifError::Corrupt),
+            };
+            if (next as usize) < MAX_CODES {
+                lzw.prefix[next as usize] = p;
+                lzw.suffix[next as usize] = first;
+                next += 1;
+                if next == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            for &index in lzw.string(code, clear) {
+                put(index);
+            }
+            prev = Some(code);
+        }
+        // Whatever is left after the end code
+        while blocks.next(data)?.is_some() {}
+        Ok(())
+    }
+}
+
+impl Lzw {
+    fn first(&self, mut code: u16, clear: u16) -> u8 {
+        while code > clear {
+            code = self.prefix[code as usize];
+        }
+        code as u8
+    }
+
+    /// Every code above `clear + 1` was added with a smaller prefix, so this ends
+    fn string(&mut self, mut code: u16, clear: u16) -> &[u8] {
+        let mut start = MAX_CODES;
+        while code > clear && start > 0 {
+            start -= 1;
+            self.stack[start] = self.suffix[code as usize];
+            code = self.prefix[code as usize];
+        }
+        if start > 0 {
+            start -= 1;
+            self.stack[start] = code as u8;
+        }
+        &self.stack[start..]
+    }
+}
+
+/// Image data comes in blocks of up to 255 bytes, ended by an empty one
+struct SubBlocks {
+    left: u8,
+    done: bool,
+}
+
+impl SubBlocks {
+    fn next(&mut self, data: &mut impl Iterator<Item = u8>) -> Result<Option<u8>, GifError> {
+        if self.done {
+            return Ok(None);
+        }
+        if self.left == 0 {
+            self.left = byte(data)?;
+            if self.left == 0 {
+                self.done = true;
+                return Ok(None);
+            }
+        }
+        self.left -= 1;
+        byte(data).map(Some)
+    }
+}
+
+/// Interlaced images send every 8th row, then the 4th, 2nd and the rest
+fn interlaced_row(n: u32, height: u32) -> u32 {
+    let passes = [(0, 8), (4, 8), (2, 4), (1, 2)];
+    let mut n = n;
+    for (start, step) in passes {
+        let rows = height.saturating_sub(start).div_ceil(step);
+        if n < rows {
+            return start + n * step;
+        }
+        n -= rows;
+    }
+    height
+}
+
+fn byte(data: &mut impl Iterator<Item = u8>) -> Result<u8, GifError> {
+    data.next().ok_or(GifError::Truncated)
+}
+
+fn u16_le(data: &mut impl Iterator<Item = u8>) -> Result<u16, GifError> {
+    Ok(u16::from_le_bytes([byte(data)?, byte(data)?]))
+}
+
+fn skip(data: &mut impl Iterator<Item = u8>, n: usize) -> Result<(), GifError> {
+    for _ in 0..n {
+        byte(data)?;
+    }
+    Ok(())
+}
+
+fn skip_sub_blocks(data: &mut impl Iterator<Item = u8>) -> Result<(), GifError> {
+    let mut blocks = SubBlocks { left: 0, done: false };
+    while blocks.next(data)?.is_some() {}
+    Ok(())
+}
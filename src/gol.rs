@@ -2,6 +2,8 @@
 
 use core::u32;
 use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use rand::RngCore;
+use rand::rngs::SmallRng;
 
 const W: usize = 32;
 const H: usize = 32;
@@ -11,6 +13,8 @@ pub struct Gol {
     // Empty point ages.
     // Age 0 represents a non-empty point, it's "alive".
     ages: [u16; W * H],
+    // Also decides spawns, so a seed replays the whole run
+    rng: SmallRng,
 }
 
 impl Default for Gol {
@@ -21,13 +25,17 @@ impl Default for Gol {
 
 impl Gol {
     pub fn new() -> Self {
-        Self { ages: [1; W * H] }
+        Self {
+            ages: [1; W * H],
+            rng: crate::rng::small_rng(),
+        }
     }
 
     pub fn randomize(&mut self) {
+        self.rng = crate::rng::small_rng();
         for x in 0..W {
             for y in 0..H {
-                let a = if embassy_rp::clocks::RoscRng.next_u32() > u32::MAX / 2 {
+                let a = if self.rng.next_u32() > u32::MAX / 2 {
                     0
                 } else {
                     1
@@ -67,7 +75,7 @@ impl Gol {
                     }
                     _ => {
                         // spawn new life when space is empty for a long time
-                        let rn = embassy_rp::clocks::RoscRng.next_u32();
+                        let rn = self.rng.next_u32();
                         if self.ages[i] > 1000 && rn > u32::MAX / 2 {
                             self.ages[i] = 0;
                         } else {
@@ -0,0 +1,175 @@
+//! Gray-Scott reaction-diffusion in fixed point.
+//!
+//! <https://www.karlsims.com/rd.html>
+//! <https://mrob.com/pub/comp/xmorphia/>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use fixed::types::I16F16;
+use fixed_macro::fixed;
+use rand::RngCore;
+
+const W: usize = 32;
+const H: usize = 32;
+
+// Diffusion rates, dt = 1.
+// Half of the usual 1.0 / 0.5 to shrink the patterns to fit on the panel.
+const DU: I16F16 = fixed!(0.5: I16F16);
+const DV: I16F16 = fixed!(0.25: I16F16);
+
+// Smallest V concentration that gets drawn
+const VISIBLE: I16F16 = fixed!(0.08: I16F16);
+
+// 3x3 Laplacian weights
+const W_ADJ: I16F16 = fixed!(0.2: I16F16);
+const W_DIAG: I16F16 = fixed!(0.05: I16F16);
+
+/// Well known (feed, kill) pairs
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Preset {
+    Coral,
+    Mitosis,
+    Spots,
+    Worms,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Coral, Preset::Mitosis, Preset::Spots, Preset::Worms];
+
+    fn feed_kill(self) -> (I16F16, I16F16) {
+        match self {
+            Preset::Coral => (fixed!(0.0545: I16F16), fixed!(0.062: I16F16)),
+            Preset::Mitosis => (fixed!(0.0367: I16F16), fixed!(0.0649: I16F16)),
+            Preset::Spots => (fixed!(0.03: I16F16), fixed!(0.062: I16F16)),
+            Preset::Worms => (fixed!(0.078: I16F16), fixed!(0.061: I16F16)),
+        }
+    }
+}
+
+pub struct GrayScott {
+    u: [I16F16; W * H],
+    v: [I16F16; W * H],
+    u_next: [I16F16; W * H],
+    v_next: [I16F16; W * H],
+    feed: I16F16,
+    kill: I16F16,
+}
+
+impl Default for GrayScott {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GrayScott {
+    pub fn new() -> Self {
+        Self::with_preset(Preset::Coral)
+    }
+
+    pub fn with_preset(preset: Preset) -> Self {
+        let (feed, kill) = preset.feed_kill();
+        let mut gs = Self {
+            u: [I16F16::ONE; W * H],
+            v: [I16F16::ZERO; W * H],
+            u_next: [I16F16::ZERO; W * H],
+            v_next: [I16F16::ZERO; W * H],
+            feed,
+            kill,
+        };
+        gs.seed();
+        gs
+    }
+
+    pub fn set_preset(&mut self, preset: Preset) {
+        (self.feed, self.kill) = preset.feed_kill();
+    }
+
+    /// Reset to all U with a few random squares of V
+    pub fn seed(&mut self) {
+        self.u.fill(I16F16::ONE);
+        self.v.fill(I16F16::ZERO);
+
+        let mut rng = crate::rng::small_rng();
+        for _ in 0..5 {
+            let cx = (rng.next_u32() % W as u32) as i32;
+            let cy = (rng.next_u32() % H as u32) as i32;
+            for dy in -2..=2 {
+                for dx in -2..=2 {
+                    let i = Self::idx(cx + dx, cy + dy);
+                    self.u[i] = I16F16::ZERO;
+                    self.v[i] = I16F16::ONE;
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self) {
+        let mut alive = false;
+        for y in 0..H as i32 {
+            for x in 0..W as i32 {
+                let i = Self::idx(x, y);
+                let u = self.u[i];
+                let v = self.v[i];
+
+                let uvv = u * v * v;
+                let du = DU * Self::laplacian(&self.u, x, y) - uvv + self.feed * (I16F16::ONE - u);
+                let dv = DV * Self::laplacian(&self.v, x, y) + uvv - (self.feed + self.kill) * v;
+
+                self.u_next[i] = (u + du).clamp(I16F16::ZERO, I16F16::ONE);
+                self.v_next[i] = (v + dv).clamp(I16F16::ZERO, I16F16::ONE);
+                alive |= self.v_next[i] > VISIBLE;
+            }
+        }
+        core::mem::swap(&mut self.u, &mut self.u_next);
+        core::mem::swap(&mut self.v, &mut self.v_next);
+
+        if !alive {
+            // V died out, nothing will ever grow again
+            self.seed();
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = self.v.iter().enumerate().map(|(i, &v)| {
+            let x = (i % W) as i32;
+            let y = (i / W) as i32;
+
+            // V rarely goes above ~0.4
+            let color = if v < VISIBLE {
+                Rgb555::BLACK
+            } else if v < fixed!(0.16: I16F16) {
+                Rgb555::BLUE
+            } else if v < fixed!(0.24: I16F16) {
+                Rgb555::CYAN
+            } else if v < fixed!(0.32: I16F16) {
+                Rgb555::GREEN
+            } else {
+                Rgb555::YELLOW
+            };
+
+            Pixel(Point::new(x, y), color)
+        });
+
+        target.draw_iter(it)
+    }
+
+    fn laplacian(f: &[I16F16; W * H], x: i32, y: i32) -> I16F16 {
+        let adj = f[Self::idx(x - 1, y)]
+            + f[Self::idx(x + 1, y)]
+            + f[Self::idx(x, y - 1)]
+            + f[Self::idx(x, y + 1)];
+        let diag = f[Self::idx(x - 1, y - 1)]
+            + f[Self::idx(x + 1, y - 1)]
+            + f[Self::idx(x - 1, y + 1)]
+            + f[Self::idx(x + 1, y + 1)];
+        adj * W_ADJ + diag * W_DIAG - f[Self::idx(x, y)]
+    }
+
+    fn idx(x: i32, y: i32) -> usize {
+        let x = x.rem_euclid(W as i32) as usize;
+        let y = y.rem_euclid(H as i32) as usize;
+        x + W * y
+    }
+}
@@ -0,0 +1,270 @@
+//! Hexagonal grid simulations.
+//!
+//! Cells use "odd-r" offset coordinates: odd rows are shifted right by half a cell.
+//! Each cell is 2x1 pixels so the shift is exactly one pixel on the 32x32 panel,
+//! which gives a brick wall layout where every cell touches 6 others.
+//!
+//! <https://www.redblobgames.com/grids/hexagons/#coordinates-offset>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use heapless::Vec;
+use rand::RngCore;
+
+pub const W: usize = 16;
+pub const H: usize = 32; // has to be even for the row parity to survive wraparound
+
+pub const MAX_COLORS: usize = 12;
+
+const PALETTE: [Rgb555; 7] = [
+    Rgb555::WHITE,
+    Rgb555::RED,
+    Rgb555::GREEN,
+    Rgb555::BLUE,
+    Rgb555::YELLOW,
+    Rgb555::CYAN,
+    Rgb555::MAGENTA,
+];
+
+/// Clockwise from East
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum HexDir {
+    E,
+    SE,
+    SW,
+    W,
+    NW,
+    NE,
+}
+
+const DIRS: [HexDir; 6] = [HexDir::E, HexDir::SE, HexDir::SW, HexDir::W, HexDir::NW, HexDir::NE];
+
+impl HexDir {
+    /// Rotate clockwise by `n` * 60 degrees
+    fn rotate(self, n: u8) -> Self {
+        DIRS[(self as usize + n as usize) % 6]
+    }
+
+    /// (dx, dy) for even and odd rows
+    fn offset(self, y: i32) -> (i32, i32) {
+        let odd = y & 1 == 1;
+        match self {
+            HexDir::E => (1, 0),
+            HexDir::W => (-1, 0),
+            HexDir::NE => (if odd { 1 } else { 0 }, -1),
+            HexDir::NW => (if odd { 0 } else { -1 }, -1),
+            HexDir::SE => (if odd { 1 } else { 0 }, 1),
+            HexDir::SW => (if odd { 0 } else { -1 }, 1),
+        }
+    }
+}
+
+/// Wraparound cell index
+pub fn cell_idx(x: i32, y: i32) -> usize {
+    let x = x.rem_euclid(W as i32);
+    let y = y.rem_euclid(H as i32);
+    (x + W as i32 * y) as usize
+}
+
+/// Wraparound neighbor cell coordinates
+pub fn neighbor(x: i32, y: i32, dir: HexDir) -> (i32, i32) {
+    let (dx, dy) = dir.offset(y);
+    ((x + dx).rem_euclid(W as i32), (y + dy).rem_euclid(H as i32))
+}
+
+/// Draw one cell, 2 pixels wide, odd rows shifted right by a pixel
+pub fn draw_cell<D>(target: &mut D, x: i32, y: i32, color: Rgb555) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb555>,
+{
+    let px = 2 * x + (y & 1);
+    // The last pixel of an odd row wraps to the start of it
+    target.draw_iter([
+        Pixel(Point::new(px % 32, y), color),
+        Pixel(Point::new((px + 1) % 32, y), color),
+    ])
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RuleError {
+    Empty,
+    /// More than `MAX_COLORS` turns
+    TooLarge,
+    /// Not one of N, U, L1, L2, R1, R2
+    BadTurn,
+}
+
+/// Hexagonal multi-color Langton's ant
+pub struct HexAnts {
+    // Cell colors
+    grid: [u8; W * H],
+    // Clockwise 60 degree steps to turn on each cell color
+    turns: Vec<u8, MAX_COLORS>,
+    ants: [(i32, i32, HexDir); 2],
+}
+
+impl Default for HexAnts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HexAnts {
+    pub fn new() -> Self {
+        Self::from_rulestring("L2NNL1L2L1").unwrap()
+    }
+
+    /// Rulestring of N (no turn), R1/R2 (60/120 degrees right), U (u-turn),
+    /// L1/L2 (60/120 degrees left), e.g. `L2NNL1L2L1` or `R1R2NUR2R1L2`
+    pub fn from_rulestring(rule: &str) -> Result<Self, RuleError> {
+        let mut turns = Vec::new();
+        let mut r = rule.as_bytes();
+        while let Some(&c) = r.first() {
+            let (turn, len) = match (c.to_ascii_uppercase(), r.get(1)) {
+                (b'N', _) => (0, 1),
+                (b'U', _) => (3, 1),
+                (b'R', Some(b'1')) => (1, 2),
+                (b'R', Some(b'2')) => (2, 2),
+                (b'L', Some(b'2')) => (4, 2),
+                (b'L', Some(b'1')) => (5, 2),
+                _ => return Err(RuleError::BadTurn),
+            };
+            turns.push(turn).map_err(|_| RuleError::TooLarge)?;
+            r = &r[len..];
+        }
+        if turns.len() < 2 {
+            return Err(RuleError::Empty);
+        }
+
+        Ok(Self {
+            grid: [0; W * H],
+            turns,
+            ants: [(4, 10, HexDir::E), (12, 22, HexDir::W)],
+        })
+    }
+
+    pub fn step(&mut self) {
+        for (x, y, dir) in self.ants.iter_mut() {
+            let idx = cell_idx(*x, *y);
+            let c = self.grid[idx];
+
+            *dir = dir.rotate(self.turns[c as usize]);
+            self.grid[idx] = ((c as usize + 1) % self.turns.len()) as u8;
+
+            (*x, *y) = neighbor(*x, *y, *dir);
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        for i in 0..self.grid.len() {
+            let c = self.grid[i];
+            let color = if c == 0 {
+                Rgb555::BLACK
+            } else {
+                PALETTE[(c as usize - 1) % PALETTE.len()]
+            };
+            draw_cell(target, (i % W) as i32, (i / W) as i32, color)?;
+        }
+
+        for &(x, y, _) in self.ants.iter() {
+            draw_cell(target, x, y, Rgb555::WHITE)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Life on a hexagonal grid, B2/S34 by default
+pub struct HexLife {
+    cells: [bool; W * H],
+    next: [bool; W * H],
+    // Bit n set: born / survives with n live neighbors
+    birth: u8,
+    survival: u8,
+}
+
+impl Default for HexLife {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HexLife {
+    pub fn new() -> Self {
+        Self::with_rule(0b0000_0100, 0b0001_1000)
+    }
+
+    /// Birth and survival neighbor counts as bit masks, e.g. B2/S34 is (0b100, 0b11000)
+    pub fn with_rule(birth: u8, survival: u8) -> Self {
+        let mut life = Self {
+            cells: [false; W * H],
+            next: [false; W * H],
+            birth,
+            survival,
+        };
+        life.randomize();
+        life
+    }
+
+    pub fn randomize(&mut self) {
+        let mut rng = crate::rng::small_rng();
+        for c in self.cells.iter_mut() {
+            // ~1/3 alive
+            *c = rng.next_u32().is_multiple_of(3);
+        }
+    }
+
+    pub fn step(&mut self) {
+        let mut changed = 0;
+        for y in 0..H as i32 {
+            for x in 0..W as i32 {
+                let i = cell_idx(x, y);
+                let n = self.num_neighbors(x, y);
+                let mask = if self.cells[i] { self.survival } else { self.birth };
+                self.next[i] = mask & (1 << n) != 0;
+                if self.next[i] != self.cells[i] {
+                    changed += 1;
+                }
+            }
+        }
+        self.cells.copy_from_slice(&self.next);
+
+        if changed == 0 {
+            // Died out or froze, start over
+            self.randomize();
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        for y in 0..H as i32 {
+            for x in 0..W as i32 {
+                let color = if self.cells[cell_idx(x, y)] {
+                    match self.num_neighbors(x, y) {
+                        0..=2 => Rgb555::BLUE,
+                        3 => Rgb555::GREEN,
+                        4 => Rgb555::YELLOW,
+                        _ => Rgb555::RED,
+                    }
+                } else {
+                    Rgb555::BLACK
+                };
+                draw_cell(target, x, y, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn num_neighbors(&self, x: i32, y: i32) -> u8 {
+        DIRS.iter()
+            .filter(|&&d| {
+                let (nx, ny) = neighbor(x, y, d);
+                self.cells[cell_idx(nx, ny)]
+            })
+            .count() as u8
+    }
+}
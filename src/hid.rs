@@ -0,0 +1,159 @@
+//!
+//! Vendor defined HID reports to control the effects, usable from hidapi or
+//! WebHID without installing a driver.
+//!
+//! Requests are 16 byte output reports, the first byte picks the command:
+//!
+//! | byte 0 | command    | arguments                                      |
+//! |--------|------------|------------------------------------------------|
+//! | 0x01   | effect     | byte 1: index into `Effect::ALL`               |
+//! | 0x02   | next       |                                                |
+//! | 0x03   | restart    |                                                |
+//! | 0x04   | brightness | byte 1: percent                                |
+//! | 0x05   | speed      | bytes 1..5: ms between frames, u32 LE          |
+//! | 0x06   | seed       | byte 1: 0 random, 1 fixed; bytes 2..10: u64 LE |
+//! | 0x07   | pause      |                                                |
+//! | 0x08   | resume     |                                                |
+//! | 0x09   | step       | bytes 1..5: frames, u32 LE                     |
+//! | 0x0a   | media key  | bytes 1..3: Consumer page usage, u16 LE        |
+//! | 0x0b   | media keys | byte 1: 0 off, 1 on                            |
+//!
+//! The device answers with [`Status`] as a 16 byte input report after every
+//! request, including those typed into the serial shell.
+//!
+//! Media keys are ignored until switched on with 0x0b or `media-keys on` in
+//! the shell. Operating systems keep the media keys of a keyboard to
+//! themselves, so `matrix media-keys` in `host/` grabs them and forwards them
+//! here, where [`media_key_command`] maps them.
+//!
+
+use usbd_hid::descriptor::MediaKey;
+use usbd_hid::descriptor::generator_prelude::*;
+
+use crate::effects::Effect;
+use crate::shell::Command;
+
+pub const REPORT_LEN: usize = 16;
+
+/// Brightness change per volume key press
+const BRIGHTNESS_STEP: u8 = 10;
+
+#[gen_hid_descriptor(
+    (collection = APPLICATION, usage_page = VENDOR_DEFINED_START, usage = 0x01) = {
+        (usage = 0x02,) = {
+            #[item_settings data,variable,absolute] status=input;
+        };
+        (usage = 0x03,) = {
+            #[item_settings data,variable,absolute] request=output;
+        };
+    }
+)]
+// The macro wants literal lengths, both are REPORT_LEN
+pub struct ControlReport {
+    pub status: [u8; 16],
+    pub request: [u8; 16],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Request {
+    Command(Command),
+    /// Consumer page usage, see [`media_key_command`]
+    MediaKey(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RequestError {
+    UnknownCommand,
+    UnknownEffect,
+    /// Shorter than its arguments
+    TooShort,
+}
+
+impl Request {
+    pub fn parse(report: &[u8]) -> Result<Request, RequestError> {
+        let (&command, args) = report.split_first().ok_or(RequestError::TooShort)?;
+        let byte = |i: usize| args.get(i).copied().ok_or(RequestError::TooShort);
+        let u32_at = |i: usize| -> Result<u32, RequestError> {
+            let bytes = args.get(i..i + 4).ok_or(RequestError::TooShort)?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        let command = match command {
+            0x01 => {
+                let effect = Effect::ALL.get(byte(0)? as usize).ok_or(RequestError::UnknownEffect)?;
+                Command::Effect(Some(*effect))
+            }
+            0x02 => Command::Next,
+            0x03 => Command::Restart,
+            0x04 => Command::Brightness(byte(0)?.min(100)),
+            0x05 => Command::SpeedMs(u32_at(0)?),
+            0x06 => match byte(0)? {
+                0 => Command::Seed(None),
+                _ => {
+                    let bytes = args.get(1..9).ok_or(RequestError::TooShort)?;
+                    Command::Seed(Some(u64::from_le_bytes(bytes.try_into().unwrap())))
+                }
+            },
+            0x07 => Command::Pause,
+            0x08 => Command::Resume,
+            0x09 => Command::Step(u32_at(0)?),
+            0x0a => return Ok(Request::MediaKey(u16::from_le_bytes([byte(0)?, byte(1)?]))),
+            0x0b => Command::MediaKeys(byte(0)? != 0),
+            _ => return Err(RequestError::UnknownCommand),
+        };
+        Ok(Request::Command(command))
+    }
+}
+
+/// What the host can read back
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Status {
+    pub effect: Effect,
+    pub brightness: u8,
+    /// The last brightness above 0, what unmuting goes back to
+    pub lit_brightness: u8,
+    pub paused: bool,
+    pub seeded: bool,
+    pub media_keys: bool,
+    pub speed_ms: u32,
+    pub frame: u32,
+}
+
+impl Status {
+    /// Index of the effect, brightness, flags (bit 0 paused, bit 1 seeded,
+    /// bit 2 media keys), brightness to unmute to, then speed and frame as
+    /// u32 LE
+    pub fn to_report(&self) -> [u8; REPORT_LEN] {
+        let mut report = [0; REPORT_LEN];
+        report[0] = Effect::ALL.iter().position(|&e| e == self.effect).unwrap_or(0) as u8;
+        report[1] = self.brightness;
+        report[2] = self.paused as u8 | (self.seeded as u8) << 1 | (self.media_keys as u8) << 2;
+        report[3] = self.lit_brightness;
+        report[4..8].copy_from_slice(&self.speed_ms.to_le_bytes());
+        report[8..12].copy_from_slice(&self.frame.to_le_bytes());
+        report
+    }
+}
+
+/// Track keys switch effects, play/pause freezes the frame and volume sets
+/// the brightness, mute toggles it off and back
+pub fn media_key_command(usage: u16, status: &Status) -> Option<Command> {
+    let i = Effect::ALL.iter().position(|&e| e == status.effect).unwrap_or(0);
+    match MediaKey::from(u8::try_from(usage).ok()?) {
+        MediaKey::NextTrack => Some(Command::Next),
+        MediaKey::PrevTrack => Some(Command::Effect(Some(
+            Effect::ALL[(i + Effect::ALL.len() - 1) % Effect::ALL.len()],
+        ))),
+        MediaKey::PlayPause if status.paused => Some(Command::Resume),
+        MediaKey::PlayPause | MediaKey::Pause => Some(Command::Pause),
+        MediaKey::Play => Some(Command::Resume),
+        MediaKey::Stop => Some(Command::Restart),
+        MediaKey::VolumeIncrement => Some(Command::Brightness(
+            status.brightness.saturating_add(BRIGHTNESS_STEP).min(100),
+        )),
+        MediaKey::VolumeDecrement => Some(Command::Brightness(status.brightness.saturating_sub(BRIGHTNESS_STEP))),
+        MediaKey::Mute if status.brightness == 0 => Some(Command::Brightness(status.lit_brightness)),
+        MediaKey::Mute => Some(Command::Brightness(0)),
+        _ => None,
+    }
+}
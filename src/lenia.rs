@@ -0,0 +1,154 @@
+//! Lenia, Game of Life with continuous states and a smooth ring kernel, in fixed point.
+//!
+//! Uses the polynomial kernel and growth functions so there's no `exp` involved.
+//!
+//! <https://chakazul.github.io/lenia.html>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use fixed::types::I16F16;
+use fixed_macro::fixed;
+use heapless::Vec;
+use rand::{Rng, rngs::SmallRng};
+
+const W: usize = 32;
+const H: usize = 32;
+
+/// Kernel radius in cells
+const R: i32 = 8;
+const KERNEL_CELLS: usize = ((2 * R + 1) * (2 * R + 1)) as usize;
+
+// Growth is positive for neighborhood sums within 3 sigma of MU
+const MU: I16F16 = fixed!(0.18: I16F16);
+const INV_3_SIGMA: I16F16 = fixed!(13.333333: I16F16); // sigma = 0.025
+const DT: I16F16 = fixed!(0.1: I16F16);
+
+// Start over below this much total mass
+const MIN_MASS: I16F16 = fixed!(2.0: I16F16);
+
+pub struct Lenia {
+    cells: [I16F16; W * H],
+    next: [I16F16; W * H],
+    // (dx, dy, weight), weights add up to 1
+    kernel: Vec<(i8, i8, I16F16), KERNEL_CELLS>,
+    rng: SmallRng,
+}
+
+impl Default for Lenia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lenia {
+    pub fn new() -> Self {
+        let mut lenia = Self {
+            cells: [I16F16::ZERO; W * H],
+            next: [I16F16::ZERO; W * H],
+            kernel: Self::kernel(),
+            rng: crate::rng::small_rng(),
+        };
+        lenia.randomize();
+        lenia
+    }
+
+    /// Ring shaped (4 r (1 - r))^4, skipping cells it's zero for
+    fn kernel() -> Vec<(i8, i8, I16F16), KERNEL_CELLS> {
+        let mut kernel = Vec::new();
+        let mut sum = I16F16::ZERO;
+        for dy in -R..=R {
+            for dx in -R..=R {
+                let r = I16F16::from_num(dx * dx + dy * dy).sqrt() / R;
+                if r >= I16F16::ONE || r == I16F16::ZERO {
+                    continue;
+                }
+                let k = 4 * r * (I16F16::ONE - r);
+                let k = k * k * k * k;
+                let _ = kernel.push((dx as i8, dy as i8, k));
+                sum += k;
+            }
+        }
+        for (_, _, k) in kernel.iter_mut() {
+            *k /= sum;
+        }
+        kernel
+    }
+
+    /// Clear and drop a few patches of noise
+    pub fn randomize(&mut self) {
+        self.cells.fill(I16F16::ZERO);
+        for _ in 0..3 {
+            let cx = self.rng.gen_range(0..W as i32);
+            let cy = self.rng.gen_range(0..H as i32);
+            for dy in 0..8 {
+                for dx in 0..8 {
+                    let v = I16F16::from_bits(self.rng.gen_range(0..I16F16::ONE.to_bits()));
+                    self.cells[Self::idx(cx + dx, cy + dy)] = v;
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self) {
+        let mut mass = I16F16::ZERO;
+        let mut changed = false;
+        for y in 0..H as i32 {
+            for x in 0..W as i32 {
+                let mut u = I16F16::ZERO;
+                for &(dx, dy, k) in self.kernel.iter() {
+                    u += k * self.cells[Self::idx(x + dx as i32, y + dy as i32)];
+                }
+
+                let i = Self::idx(x, y);
+                let a = (self.cells[i] + DT * growth(u)).clamp(I16F16::ZERO, I16F16::ONE);
+                changed |= a != self.cells[i];
+                mass += a;
+                self.next[i] = a;
+            }
+        }
+        self.cells.copy_from_slice(&self.next);
+
+        if mass < MIN_MASS || !changed {
+            // Died out or froze, start over
+            self.randomize();
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = self.cells.iter().enumerate().map(|(i, &a)| {
+            let color = if a < fixed!(0.1: I16F16) {
+                Rgb555::BLACK
+            } else if a < fixed!(0.25: I16F16) {
+                Rgb555::BLUE
+            } else if a < fixed!(0.5: I16F16) {
+                Rgb555::MAGENTA
+            } else if a < fixed!(0.75: I16F16) {
+                Rgb555::RED
+            } else {
+                Rgb555::YELLOW
+            };
+            Pixel(Point::new((i % W) as i32, (i / W) as i32), color)
+        });
+
+        target.draw_iter(it)
+    }
+
+    fn idx(x: i32, y: i32) -> usize {
+        let x = x.rem_euclid(W as i32) as usize;
+        let y = y.rem_euclid(H as i32) as usize;
+        x + W * y
+    }
+}
+
+/// 2 (1 - ((u - mu) / 3 sigma)^2)^4 - 1, flat -1 away from MU
+fn growth(u: I16F16) -> I16F16 {
+    let t = (u - MU) * INV_3_SIGMA;
+    if t.abs() >= I16F16::ONE {
+        return -I16F16::ONE;
+    }
+    let q = I16F16::ONE - t * t;
+    let q2 = q * q;
+    2 * q2 * q2 - I16F16::ONE
+}
@@ -1,8 +1,40 @@
 #![no_std]
 
+// The defmt logger, the `usb-log` feature brings its own
+#[cfg(not(feature = "usb-log"))]
+use defmt_rtt as _;
+
+pub mod bmp;
 pub mod display;
+pub mod effects;
+pub mod fluid;
+pub mod fractal;
+pub mod fat;
+pub mod gif;
 pub mod gol;
 pub mod matrix;
+pub mod maze;
+pub mod msc;
 pub mod cca;
+pub mod demos;
+pub mod gray_scott;
 pub mod lorenz;
+pub mod num;
+pub mod playlist;
+pub mod rng;
 pub mod ants;
+pub mod boids;
+pub mod hex;
+pub mod hid;
+pub mod lenia;
+pub mod lighting;
+pub mod log;
+pub mod sand;
+pub mod shell;
+pub mod storage;
+pub mod stream;
+pub mod sort;
+pub mod trail;
+pub mod usb;
+pub mod wireframe;
+pub mod wireworld;
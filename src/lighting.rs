@@ -0,0 +1,141 @@
+//!
+//! Pixel data from lighting control protocols over UDP: Art-Net, E1.31 (sACN)
+//! and DDP.
+//!
+//! The panel is 1024 RGB pixels. DMX universes carry 170 pixels each, the
+//! last two channels unused like on most pixel controllers, so a whole frame
+//! takes 7 universes from [`Pixels::first_universes`] on. DDP addresses the
+//! 3072 channels directly.
+//!
+//! The packets themselves are parsed in `protocol/`, where the tests run on
+//! the host.
+//!
+
+use embassy_time::{Duration, Instant};
+use embedded_graphics::{
+    pixelcolor::{Rgb555, Rgb888},
+    prelude::*,
+};
+
+use crate::stream::Layout;
+
+pub use matrix_protocol::lighting::{
+    ARTNET_POLL_REPLY_LEN, ARTNET_PORT, CHANNELS, DDP_PORT, PIXELS_PER_UNIVERSE, Packet, Protocol, SACN_PORT,
+    UNIVERSES, artnet_page_count, artnet_poll_reply, parse_artnet, parse_ddp, parse_sacn,
+};
+
+const WIDTH: usize = 32;
+
+/// Without sync packets for this long, universes are shown as they complete
+const SYNC_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// The frame being received, and when to show it
+pub struct Pixels {
+    rgb: [u8; CHANNELS],
+    layout: Layout,
+    artnet_universe: u16,
+    sacn_universe: u16,
+    // Universes received since the last frame was shown
+    received: u8,
+    last_sync: Option<Instant>,
+}
+
+impl Default for Pixels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pixels {
+    /// Art-Net from universe 0 and sACN from universe 1, their lowest
+    pub const fn new() -> Self {
+        Self {
+            rgb: [0; CHANNELS],
+            layout: Layout::Rows,
+            artnet_universe: 0,
+            sacn_universe: 1,
+            received: 0,
+            last_sync: None,
+        }
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    /// First Art-Net and sACN universes
+    pub fn first_universes(&self) -> (u16, u16) {
+        (self.artnet_universe, self.sacn_universe)
+    }
+
+    pub fn set_first_universes(&mut self, artnet: u16, sacn: u16) {
+        self.artnet_universe = artnet;
+        self.sacn_universe = sacn;
+        self.received = 0;
+    }
+
+    /// Takes in a packet, true when the frame is complete and should be shown
+    pub fn handle(&mut self, packet: Packet<'_>, now: Instant) -> bool {
+        match packet {
+            Packet::Dmx {
+                protocol,
+                universe,
+                data,
+                synced,
+            } => {
+                let first = match protocol {
+                    Protocol::Sacn => self.sacn_universe,
+                    _ => self.artnet_universe,
+                };
+                let Some(index) = universe.checked_sub(first).map(usize::from).filter(|&i| i < UNIVERSES) else {
+                    return false;
+                };
+                let data = &data[..data.len().min(PIXELS_PER_UNIVERSE * 3)];
+                self.write(index * PIXELS_PER_UNIVERSE * 3, data);
+
+                let bit = 1 << index;
+                // A repeat means the sender has fewer universes than we do
+                let repeat = self.received & bit != 0;
+                self.received |= bit;
+                let waiting_for_sync = synced || self.last_sync.is_some_and(|t| now - t < SYNC_TIMEOUT);
+                if waiting_for_sync || !(repeat || self.received == (1 << UNIVERSES) - 1) {
+                    return false;
+                }
+                self.received = if repeat { bit } else { 0 };
+                true
+            }
+            Packet::Sync => {
+                self.last_sync = Some(now);
+                self.received = 0;
+                true
+            }
+            Packet::Ddp { offset, data, push } => {
+                self.write(offset, data);
+                push
+            }
+            Packet::Poll => false,
+        }
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) {
+        let Some(dst) = self.rgb.get_mut(offset..) else {
+            return;
+        };
+        let len = data.len().min(dst.len());
+        dst[..len].copy_from_slice(&data[..len]);
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        target.draw_iter(self.rgb.as_chunks::<3>().0.iter().enumerate().map(|(i, c)| {
+            let y = i / WIDTH;
+            let mut x = i % WIDTH;
+            if self.layout == Layout::Serpentine && !y.is_multiple_of(2) {
+                x = WIDTH - 1 - x;
+            }
+            Pixel(Point::new(x as i32, y as i32), Rgb888::new(c[0], c[1], c[2]).into())
+        }))
+    }
+}
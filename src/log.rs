@@ -0,0 +1,143 @@
+//! defmt logs for boards without a debug probe. With the `usb-log` feature
+//! they go out on a USB serial port of their own instead of RTT, in defmt's
+//! framing, for `matrix log` on the host to decode with the firmware's ELF.
+//!
+//! Nothing blocks on the host: logs wait in a buffer until the port is
+//! read, and log frames that don't fit are dropped whole, so the decoder
+//! only misses those.
+
+use embassy_executor::Spawner;
+use embassy_rp::Peri;
+use embassy_rp::peripherals::USB;
+
+#[cfg(feature = "usb-log")]
+pub(crate) use usb_log::{add_class, forward};
+
+/// Serves the log over USB for firmwares that don't use USB otherwise,
+/// does nothing without the `usb-log` feature
+pub fn start(spawner: Spawner, usb: Peri<'static, USB>) {
+    #[cfg(feature = "usb-log")]
+    {
+        let device = crate::usb::builder(crate::usb::driver(usb), crate::usb::config("LED matrix log")).build();
+        spawner.spawn(crate::usb::usb_task(device)).unwrap();
+    }
+    #[cfg(not(feature = "usb-log"))]
+    let _ = (spawner, usb);
+}
+
+#[cfg(feature = "usb-log")]
+mod usb_log {
+    use core::sync::atomic::Ordering;
+    use portable_atomic::AtomicBool;
+
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+    use embassy_sync::pipe::Pipe;
+    use embassy_usb::Builder;
+    use embassy_usb::class::cdc_acm::{CdcAcmClass, Sender, State};
+    use static_cell::StaticCell;
+
+    use crate::usb::{MAX_PACKET_SIZE, UsbDriver};
+
+    // A few hundred lines of boot messages
+    const BUFFER: usize = 2048;
+    // Longer frames are dropped
+    const MAX_FRAME: usize = 256;
+
+    static PIPE: Pipe<CriticalSectionRawMutex, BUFFER> = Pipe::new();
+
+    static STATE: StaticCell<State> = StaticCell::new();
+    // Handed from `add_class` to `forward`, both run on core 0 before and in `usb_task`
+    static mut SENDER: Option<Sender<'static, UsbDriver>> = None;
+
+    #[defmt::global_logger]
+    struct Logger;
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+    static mut RESTORE: critical_section::RestoreState = critical_section::RestoreState::invalid();
+    static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+    // The frame being encoded, it goes into the pipe whole or not at all
+    static mut FRAME: heapless::Vec<u8, MAX_FRAME> = heapless::Vec::new();
+    static mut FRAME_TOO_LONG: bool = false;
+
+    // Same locking as defmt-rtt: a frame is written inside one critical section
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {
+            let restore = unsafe { critical_section::acquire() };
+            if TAKEN.swap(true, Ordering::Relaxed) {
+                panic!("defmt logger taken reentrantly")
+            }
+            unsafe {
+                RESTORE = restore;
+                (*core::ptr::addr_of_mut!(ENCODER)).start_frame(write)
+            }
+        }
+
+        unsafe fn flush() {}
+
+        unsafe fn release() {
+            unsafe {
+                (*core::ptr::addr_of_mut!(ENCODER)).end_frame(write);
+                let frame = &mut *core::ptr::addr_of_mut!(FRAME);
+                if !FRAME_TOO_LONG && frame.len() <= PIPE.free_capacity() {
+                    // Nothing reads the pipe during the critical section, so
+                    // it all fits, in two writes where the buffer wraps around
+                    let mut bytes = &frame[..];
+                    while let Ok(n) = PIPE.try_write(bytes) {
+                        bytes = &bytes[n..];
+                        if bytes.is_empty() {
+                            break;
+                        }
+                    }
+                }
+                frame.clear();
+                FRAME_TOO_LONG = false;
+            }
+            TAKEN.store(false, Ordering::Relaxed);
+            unsafe { critical_section::release(RESTORE) };
+        }
+
+        unsafe fn write(bytes: &[u8]) {
+            unsafe { (*core::ptr::addr_of_mut!(ENCODER)).write(bytes, write) }
+        }
+    }
+
+    fn write(bytes: &[u8]) {
+        // Only called with the logger acquired
+        unsafe {
+            if (*core::ptr::addr_of_mut!(FRAME)).extend_from_slice(bytes).is_err() {
+                FRAME_TOO_LONG = true;
+            }
+        }
+    }
+
+    /// Adds the log's serial port, `usb::builder` does this
+    pub fn add_class(builder: &mut Builder<'static, UsbDriver>) {
+        let class = CdcAcmClass::new(builder, STATE.init(State::new()), MAX_PACKET_SIZE);
+        // The host has nothing to say to the log
+        let (sender, _) = class.split();
+        unsafe { *core::ptr::addr_of_mut!(SENDER) = Some(sender) };
+    }
+
+    /// Sends the buffered log while the device runs, `usb_task` does this
+    pub async fn forward() -> ! {
+        let Some(mut sender) = (unsafe { (*core::ptr::addr_of_mut!(SENDER)).take() }) else {
+            loop {
+                core::future::pending::<()>().await;
+            }
+        };
+        let mut buf = [0; MAX_PACKET_SIZE as usize];
+        loop {
+            sender.wait_connection().await;
+            loop {
+                let n = PIPE.read(&mut buf).await;
+                if sender.write_packet(&buf[..n]).await.is_err() {
+                    break;
+                }
+                // A full packet doesn't end the transfer, the host waits for more
+                if n == buf.len() && PIPE.is_empty() && sender.write_packet(&[]).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,84 @@
 use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use heapless::Vec;
+
+use crate::num::{Real, Scalar};
+use crate::trail::Trail;
+
+pub use matrix_math::lorenz::{Params, deriv, euler, rk4};
 
 const W: i32 = 32;
 const H: i32 = 32;
 
-// Standard Lorenz parameters
-const SIGMA: f32 = 10.0;
-const RHO: f32 = 28.0;
-const BETA: f32 = 8.0 / 3.0;
-const DT: f32 = 0.01;
+const TRAIL_LEN: usize = 64;
+pub const MAX_PARTICLES: usize = 8;
+
+// Initial conditions of neighboring particles differ by this much in x
+const SPREAD: f32 = 0.001;
+
+const PALETTE: [Rgb555; 7] = [
+    Rgb555::RED,
+    Rgb555::GREEN,
+    Rgb555::BLUE,
+    Rgb555::YELLOW,
+    Rgb555::CYAN,
+    Rgb555::MAGENTA,
+    Rgb555::WHITE,
+];
+
+/// Numerical integration method
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Integrator {
+    /// Forward Euler, cheap but drifts
+    Euler,
+    /// Classic 4th order Runge-Kutta
+    Rk4,
+}
+
+/// Screen projection constants converted to the scalar type once
+struct Projection {
+    x_offset: Scalar,
+    x_scale: Scalar,
+    y_offset: Scalar,
+    y_scale: Scalar,
+    z_scale: Scalar,
+}
+
+impl Projection {
+    fn new() -> Self {
+        Self {
+            x_offset: Scalar::from_f32(20.0),
+            x_scale: Scalar::from_f32(W as f32 / 40.0),
+            y_offset: Scalar::from_f32(30.0),
+            y_scale: Scalar::from_f32(H as f32 / 60.0),
+            // Normalize Z (typically 0-50 range) to 0-31 for color mapping
+            z_scale: Scalar::from_f32(31.0 / 50.0),
+        }
+    }
+}
+
+struct Particle {
+    s: [Scalar; 3],
+    // Fading effect tail: (x, y, z_normalized)
+    trail: Trail<TRAIL_LEN>,
+    // None: color the trail by Z depth
+    color: Option<Rgb555>,
+}
+
+impl Particle {
+    fn new(x: f32, color: Option<Rgb555>) -> Self {
+        Self {
+            s: [Scalar::from_f32(x), Scalar::ZERO, Scalar::ZERO],
+            trail: Trail::new(),
+            color,
+        }
+    }
+}
 
 pub struct Lorenz {
-    x: f32,
-    y: f32,
-    z: f32,
-    // Buffer for a fading effect tail: (x, y, z_normalized)
-    trail: [(i8, i8, u8); 64],
-    trail_idx: usize,
+    particles: Vec<Particle, MAX_PARTICLES>,
+    integrator: Integrator,
+    params: Params<Scalar>,
+    projection: Projection,
 }
 
 impl Default for Lorenz {
@@ -25,66 +88,97 @@ impl Default for Lorenz {
 }
 
 impl Lorenz {
+    /// Single particle, forward Euler, trail colored by depth
     pub fn new() -> Self {
+        let mut particles = Vec::new();
+        let _ = particles.push(Particle::new(0.1, None));
+        Self {
+            particles,
+            integrator: Integrator::Euler,
+            params: Params::new(),
+            projection: Projection::new(),
+        }
+    }
+
+    /// Up to `MAX_PARTICLES` particles started at nearby initial conditions,
+    /// each drawn with its own color, to show how their orbits diverge.
+    pub fn with_particles(n: usize, integrator: Integrator) -> Self {
+        let mut particles = Vec::new();
+        for i in 0..n.clamp(1, MAX_PARTICLES) {
+            let x = 0.1 + i as f32 * SPREAD;
+            let _ = particles.push(Particle::new(x, Some(PALETTE[i % PALETTE.len()])));
+        }
         Self {
-            x: 0.1,
-            y: 0.0,
-            z: 0.0,
-            trail: [(-1, -1, 0); 64],
-            trail_idx: 0,
+            particles,
+            integrator,
+            params: Params::new(),
+            projection: Projection::new(),
         }
     }
 
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
     pub fn step(&mut self) {
-        let dx = SIGMA * (self.y - self.x) * DT;
-        let dy = (self.x * (RHO - self.z) - self.y) * DT;
-        let dz = (self.x * self.y - BETA * self.z) * DT;
-
-        self.x += dx;
-        self.y += dy;
-        self.z += dz;
-
-        // Project 3D to 2D
-        let px = ((self.x + 20.0) * (W as f32 / 40.0)) as i8;
-        let py = ((self.y + 30.0) * (H as f32 / 60.0)) as i8;
-        // Normalize Z (typically 0-50 range) to 0-31 for color mapping
-        let pz = (self.z * (31.0 / 50.0)).max(0.0).min(31.0) as u8;
-
-        self.trail[self.trail_idx] = (px, py, pz);
-        self.trail_idx = (self.trail_idx + 1) % self.trail.len();
+        let pr = &self.projection;
+        for p in self.particles.iter_mut() {
+            p.s = match self.integrator {
+                Integrator::Euler => euler(p.s, &self.params),
+                Integrator::Rk4 => rk4(p.s, &self.params),
+            };
+
+            let [x, y, z] = p.s;
+
+            // Project 3D to 2D
+            let px = ((x + pr.x_offset) * pr.x_scale).to_i32() as i8;
+            let py = ((y + pr.y_offset) * pr.y_scale).to_i32() as i8;
+            let pz = (z * pr.z_scale).to_i32().clamp(0, 31) as u8;
+
+            p.trail.push(px, py, pz);
+        }
     }
 
     pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
     where
         D: DrawTarget<Color = Rgb555>,
     {
-        for i in 0..self.trail.len() {
-            let idx = (self.trail_idx + i) % self.trail.len();
-            let (px, py, pz) = self.trail[idx];
-
-            if px >= 0 && px < W as i8 && py >= 0 && py < H as i8 {
-                let is_head = i == self.trail.len() - 1;
-                
-                let color = if is_head {
-                    Rgb555::WHITE // The leading spark
-                } else {
-                    // Map Z depth to a Blue (Low) -> Green -> Red (High) gradient
-                    if pz < 10 {
-                        Rgb555::BLUE
-                    } else if pz < 20 {
-                        Rgb555::CYAN
-                    } else if pz < 25 {
-                        Rgb555::GREEN
-                    } else if pz < 28 {
-                        Rgb555::YELLOW
+        for p in self.particles.iter() {
+            for (i, (px, py, pz)) in p.trail.iter().enumerate() {
+                if (0..W as i8).contains(&px) && (0..H as i8).contains(&py) {
+                    let is_head = i == p.trail.len() - 1;
+
+                    let color = if is_head {
+                        Rgb555::WHITE // The leading spark
+                    } else if let Some(c) = p.color {
+                        c
                     } else {
-                        Rgb555::RED
-                    }
-                };
-                
-                Pixel(Point::new(px as i32, py as i32), color).draw(target)?;
+                        depth_color(pz)
+                    };
+
+                    Pixel(Point::new(px as i32, py as i32), color).draw(target)?;
+                }
             }
         }
         Ok(())
     }
 }
+
+/// Map Z depth to a Blue (Low) -> Green -> Red (High) gradient
+fn depth_color(pz: u8) -> Rgb555 {
+    if pz < 10 {
+        Rgb555::BLUE
+    } else if pz < 20 {
+        Rgb555::CYAN
+    } else if pz < 25 {
+        Rgb555::GREEN
+    } else if pz < 28 {
+        Rgb555::YELLOW
+    } else {
+        Rgb555::RED
+    }
+}
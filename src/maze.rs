@@ -0,0 +1,542 @@
+//! Maze generation and solving, one carved passage or one visited cell per step.
+//!
+//! 16x16 cells, each takes 2x2 pixels: the cell itself, the passages east
+//! and south of it, and a wall pixel in the corner.
+//!
+//! <https://en.wikipedia.org/wiki/Maze_generation_algorithm>
+//! <https://en.wikipedia.org/wiki/Maze-solving_algorithm>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use heapless::{Deque, Vec};
+use rand::{Rng, rngs::SmallRng};
+
+const N: usize = 16;
+const CELLS: usize = N * N;
+// Walls between horizontal and vertical neighbors
+const EDGES: usize = 2 * N * (N - 1);
+
+const START: u8 = 0;
+const GOAL: u8 = (CELLS - 1) as u8;
+
+// Clockwise from East, so +1 is a right turn
+const DIRS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+const PASSAGE: Rgb555 = Rgb555::BLUE;
+const FRONTIER: Rgb555 = Rgb555::MAGENTA;
+const VISITED: Rgb555 = Rgb555::GREEN;
+const PATH: Rgb555 = Rgb555::YELLOW;
+const HEAD: Rgb555 = Rgb555::RED;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Generator {
+    /// Randomized depth first search, long winding corridors
+    Backtracker,
+    /// Grows from a random frontier cell, lots of short dead ends
+    Prim,
+    /// Joins random cells that aren't connected yet, all over the place
+    Kruskal,
+    /// Loop-erased random walks, unbiased
+    Wilson,
+}
+
+impl Generator {
+    pub const ALL: [Generator; 4] = [
+        Generator::Backtracker,
+        Generator::Prim,
+        Generator::Kruskal,
+        Generator::Wilson,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Solver {
+    /// Breadth first flood from the start
+    Bfs,
+    /// Best first by distance so far plus Manhattan distance to the goal
+    AStar,
+    /// Keep a hand on the right wall
+    WallFollower,
+}
+
+impl Solver {
+    pub const ALL: [Solver; 3] = [Solver::Bfs, Solver::AStar, Solver::WallFollower];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Phase {
+    Generating,
+    Solving,
+    Solved,
+}
+
+pub struct Maze {
+    generator: Generator,
+    solver: Solver,
+    phase: Phase,
+    // Passage bits by direction
+    open: [u8; CELLS],
+    in_maze: [bool; CELLS],
+    // Prim's frontier while generating, visited cells while solving
+    seen: [bool; CELLS],
+    // Backtracker stack, Prim's frontier, A* open list
+    cells: Vec<u8, CELLS>,
+    // Kruskal's shuffled walls, cell * 2 + 0 for east or 1 for south
+    edges: Vec<u16, EDGES>,
+    // Kruskal's union-find parents
+    sets: [u8; CELLS],
+    // Wilson's last exit directions, and (start, current) of the walk
+    exits: [u8; CELLS],
+    walk: Option<(u8, u8)>,
+    carving: bool,
+    came_from: [Option<u8>; CELLS],
+    // A* distance from the start
+    cost: [u16; CELLS],
+    queue: Deque<u8, CELLS>,
+    // Wall follower position and heading
+    follower: (u8, usize),
+    on_path: [bool; CELLS],
+    // Last carved or visited cell
+    head: Option<u8>,
+    rng: SmallRng,
+}
+
+impl Default for Maze {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Maze {
+    pub fn new() -> Self {
+        Self::with_algorithms(Generator::Backtracker, Solver::Bfs)
+    }
+
+    pub fn with_algorithms(generator: Generator, solver: Solver) -> Self {
+        let mut maze = Self {
+            generator,
+            solver,
+            phase: Phase::Generating,
+            open: [0; CELLS],
+            in_maze: [false; CELLS],
+            seen: [false; CELLS],
+            cells: Vec::new(),
+            edges: Vec::new(),
+            sets: [0; CELLS],
+            exits: [0; CELLS],
+            walk: None,
+            carving: false,
+            came_from: [None; CELLS],
+            cost: [0; CELLS],
+            queue: Deque::new(),
+            follower: (START, 0),
+            on_path: [false; CELLS],
+            head: None,
+            rng: crate::rng::small_rng(),
+        };
+        maze.reset();
+        maze
+    }
+
+    pub fn generator(&self) -> Generator {
+        self.generator
+    }
+
+    pub fn solver(&self) -> Solver {
+        self.solver
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Takes effect on the next `reset`
+    pub fn set_generator(&mut self, generator: Generator) {
+        self.generator = generator;
+    }
+
+    /// Takes effect on the next `reset` or once generation is done
+    pub fn set_solver(&mut self, solver: Solver) {
+        self.solver = solver;
+    }
+
+    /// Wall everything up and start generating
+    pub fn reset(&mut self) {
+        self.phase = Phase::Generating;
+        self.open.fill(0);
+        self.in_maze.fill(false);
+        self.seen.fill(false);
+        self.on_path.fill(false);
+        self.cells.clear();
+        self.edges.clear();
+        self.walk = None;
+        self.carving = false;
+        self.head = None;
+
+        let start = self.rng.gen_range(0..CELLS) as u8;
+        match self.generator {
+            Generator::Backtracker => {
+                self.in_maze[start as usize] = true;
+                let _ = self.cells.push(start);
+            }
+            Generator::Prim => self.add_to_prim(start),
+            Generator::Kruskal => {
+                for c in 0..CELLS as u16 {
+                    let (x, y) = (c as usize % N, c as usize / N);
+                    if x < N - 1 {
+                        let _ = self.edges.push(c * 2);
+                    }
+                    if y < N - 1 {
+                        let _ = self.edges.push(c * 2 + 1);
+                    }
+                }
+                // Fisher-Yates
+                for i in (1..self.edges.len()).rev() {
+                    let j = self.rng.gen_range(0..=i);
+                    self.edges.swap(i, j);
+                }
+                for (i, s) in self.sets.iter_mut().enumerate() {
+                    *s = i as u8;
+                }
+            }
+            Generator::Wilson => self.in_maze[start as usize] = true,
+        }
+    }
+
+    pub fn step(&mut self) {
+        match self.phase {
+            Phase::Generating => {
+                if !self.generate() {
+                    self.start_solving();
+                }
+            }
+            Phase::Solving => {
+                if !self.solve() {
+                    self.phase = Phase::Solved;
+                }
+            }
+            Phase::Solved => {}
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let mut colors = [Rgb555::BLACK; CELLS];
+        for (c, color) in colors.iter_mut().enumerate() {
+            *color = if self.on_path[c] {
+                PATH
+            } else if self.seen[c] {
+                if self.phase == Phase::Generating {
+                    FRONTIER
+                } else {
+                    VISITED
+                }
+            } else if self.in_maze[c] {
+                PASSAGE
+            } else {
+                Rgb555::BLACK
+            };
+        }
+        // Wilson's walk in progress
+        if let Some((start, cur)) = self.walk
+            && !self.carving
+        {
+            let mut c = start;
+            for _ in 0..CELLS {
+                colors[c as usize] = FRONTIER;
+                match neighbor(c, self.exits[c as usize] as usize) {
+                    Some(n) if c != cur => c = n,
+                    _ => break,
+                }
+            }
+        }
+        if let Some(h) = self.head {
+            colors[h as usize] = HEAD;
+        }
+
+        target.clear(Rgb555::BLACK)?;
+        for (c, &color) in colors.iter().enumerate() {
+            let p = Point::new(2 * (c % N) as i32, 2 * (c / N) as i32);
+            Pixel(p, color).draw(target)?;
+
+            for (d, offset) in [(0, Point::new(1, 0)), (1, Point::new(0, 1))] {
+                if self.open[c] & (1 << d) == 0 {
+                    continue;
+                }
+                // Passages between two cells of the same kind take their color
+                let n = neighbor(c as u8, d).unwrap() as usize;
+                let color = if colors[n] == color && color != HEAD {
+                    color
+                } else {
+                    PASSAGE
+                };
+                Pixel(p + offset, color).draw(target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One carve, false once the maze is complete
+    fn generate(&mut self) -> bool {
+        match self.generator {
+            Generator::Backtracker => {
+                while let Some(&c) = self.cells.last() {
+                    let mut dirs: Vec<usize, 4> = Vec::new();
+                    for d in 0..4 {
+                        if let Some(n) = neighbor(c, d)
+                            && !self.in_maze[n as usize]
+                        {
+                            let _ = dirs.push(d);
+                        }
+                    }
+                    if dirs.is_empty() {
+                        // Dead end, back up
+                        self.cells.pop();
+                        continue;
+                    }
+
+                    let d = dirs[self.rng.gen_range(0..dirs.len())];
+                    let n = self.carve(c, d);
+                    self.in_maze[n as usize] = true;
+                    let _ = self.cells.push(n);
+                    return true;
+                }
+                false
+            }
+            Generator::Prim => {
+                if self.cells.is_empty() {
+                    return false;
+                }
+                let c = self.cells.swap_remove(self.rng.gen_range(0..self.cells.len()));
+
+                // Connect to a random neighbor already in the maze
+                let mut dirs: Vec<usize, 4> = Vec::new();
+                for d in 0..4 {
+                    if let Some(n) = neighbor(c, d)
+                        && self.in_maze[n as usize]
+                    {
+                        let _ = dirs.push(d);
+                    }
+                }
+                let d = dirs[self.rng.gen_range(0..dirs.len())];
+                self.carve(c, d);
+                self.add_to_prim(c);
+                true
+            }
+            Generator::Kruskal => {
+                while let Some(e) = self.edges.pop() {
+                    let c = (e / 2) as u8;
+                    let d = (e % 2) as usize;
+                    let n = neighbor(c, d).unwrap();
+                    let (a, b) = (self.find(c), self.find(n));
+                    if a != b {
+                        self.sets[a as usize] = b;
+                        self.carve(c, d);
+                        self.in_maze[c as usize] = true;
+                        self.in_maze[n as usize] = true;
+                        return true;
+                    }
+                }
+                false
+            }
+            Generator::Wilson => self.wilson(),
+        }
+    }
+
+    fn wilson(&mut self) -> bool {
+        match self.walk {
+            None => {
+                // Walk from a random cell outside the maze
+                let offset = self.rng.gen_range(0..CELLS);
+                let Some(c) = (0..CELLS).map(|i| (i + offset) % CELLS).find(|&c| !self.in_maze[c]) else {
+                    return false;
+                };
+                self.walk = Some((c as u8, c as u8));
+                self.head = Some(c as u8);
+            }
+            Some((start, cur)) if !self.carving => {
+                let d = loop {
+                    let d = self.rng.gen_range(0..4);
+                    if neighbor(cur, d).is_some() {
+                        break d;
+                    }
+                };
+                self.exits[cur as usize] = d as u8;
+                let n = neighbor(cur, d).unwrap();
+                self.head = Some(n);
+                if self.in_maze[n as usize] {
+                    // Hit the maze, now carve the walk minus its loops from the start
+                    self.carving = true;
+                    self.walk = Some((start, start));
+                } else {
+                    self.walk = Some((start, n));
+                }
+            }
+            Some((start, cur)) => {
+                let n = self.carve(cur, self.exits[cur as usize] as usize);
+                self.in_maze[cur as usize] = true;
+                if self.in_maze[n as usize] {
+                    self.walk = None;
+                    self.carving = false;
+                } else {
+                    self.walk = Some((start, n));
+                }
+            }
+        }
+        true
+    }
+
+    fn add_to_prim(&mut self, c: u8) {
+        self.in_maze[c as usize] = true;
+        self.seen[c as usize] = false;
+        for d in 0..4 {
+            if let Some(n) = neighbor(c, d)
+                && !self.in_maze[n as usize]
+                && !self.seen[n as usize]
+            {
+                self.seen[n as usize] = true;
+                let _ = self.cells.push(n);
+            }
+        }
+    }
+
+    /// Union-find root, halving paths on the way
+    fn find(&mut self, mut c: u8) -> u8 {
+        while self.sets[c as usize] != c {
+            let p = self.sets[c as usize];
+            self.sets[c as usize] = self.sets[p as usize];
+            c = p;
+        }
+        c
+    }
+
+    /// Open the wall from `c` in direction `d`, returns the cell on the other side
+    fn carve(&mut self, c: u8, d: usize) -> u8 {
+        let n = neighbor(c, d).unwrap();
+        self.open[c as usize] |= 1 << d;
+        self.open[n as usize] |= 1 << ((d + 2) % 4);
+        self.head = Some(n);
+        n
+    }
+
+    fn start_solving(&mut self) {
+        self.phase = Phase::Solving;
+        self.seen.fill(false);
+        self.on_path.fill(false);
+        self.came_from.fill(None);
+        self.cells.clear();
+        self.queue.clear();
+        self.head = Some(START);
+        self.seen[START as usize] = true;
+
+        match self.solver {
+            Solver::Bfs => {
+                let _ = self.queue.push_back(START);
+            }
+            Solver::AStar => {
+                self.cost.fill(u16::MAX);
+                self.cost[START as usize] = 0;
+                self.seen[START as usize] = false;
+                let _ = self.cells.push(START);
+            }
+            Solver::WallFollower => self.follower = (START, 0),
+        }
+    }
+
+    /// One visited cell, false once the goal is reached
+    fn solve(&mut self) -> bool {
+        let c = match self.solver {
+            Solver::Bfs => {
+                let Some(c) = self.queue.pop_front() else {
+                    return false;
+                };
+                for n in self.exits_from(c) {
+                    if !self.seen[n as usize] {
+                        self.seen[n as usize] = true;
+                        self.came_from[n as usize] = Some(c);
+                        let _ = self.queue.push_back(n);
+                    }
+                }
+                c
+            }
+            Solver::AStar => {
+                let c = loop {
+                    let Some((i, _)) = self.cells.iter().enumerate().min_by_key(|&(_, &c)| self.estimate(c)) else {
+                        return false;
+                    };
+                    let c = self.cells.swap_remove(i);
+                    if !self.seen[c as usize] {
+                        break c;
+                    }
+                };
+                self.seen[c as usize] = true;
+                for n in self.exits_from(c) {
+                    let cost = self.cost[c as usize] + 1;
+                    if cost < self.cost[n as usize] {
+                        self.cost[n as usize] = cost;
+                        self.came_from[n as usize] = Some(c);
+                        let _ = self.cells.push(n);
+                    }
+                }
+                c
+            }
+            Solver::WallFollower => {
+                let (c, heading) = self.follower;
+                // Right, straight, left, back
+                let d = [1, 0, 3, 2]
+                    .into_iter()
+                    .map(|turn| (heading + turn) % 4)
+                    .find(|&d| self.open[c as usize] & (1 << d) != 0)
+                    .unwrap();
+                let n = neighbor(c, d).unwrap();
+                if !self.seen[n as usize] {
+                    self.seen[n as usize] = true;
+                    // First visits trace the unique path in a perfect maze
+                    self.came_from[n as usize] = Some(c);
+                }
+                self.follower = (n, d);
+                n
+            }
+        };
+
+        self.head = Some(c);
+        if c == GOAL {
+            let mut p = Some(GOAL);
+            while let Some(c) = p {
+                self.on_path[c as usize] = true;
+                p = self.came_from[c as usize];
+            }
+            self.head = None;
+            return false;
+        }
+        true
+    }
+
+    fn exits_from(&self, c: u8) -> Vec<u8, 4> {
+        let mut exits = Vec::new();
+        for d in 0..4 {
+            if self.open[c as usize] & (1 << d) != 0 {
+                let _ = exits.push(neighbor(c, d).unwrap());
+            }
+        }
+        exits
+    }
+
+    /// A* cost so far plus Manhattan distance to go
+    fn estimate(&self, c: u8) -> u16 {
+        let (x, y) = (c as usize % N, c as usize / N);
+        self.cost[c as usize] + (2 * (N - 1) - x - y) as u16
+    }
+}
+
+fn neighbor(c: u8, d: usize) -> Option<u8> {
+    let (dx, dy) = DIRS[d];
+    let x = (c as usize % N) as i32 + dx;
+    let y = (c as usize / N) as i32 + dy;
+    if (0..N as i32).contains(&x) && (0..N as i32).contains(&y) {
+        Some((x + N as i32 * y) as u8)
+    } else {
+        None
+    }
+}
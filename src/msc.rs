@@ -0,0 +1,402 @@
+//!
+//! USB mass storage: Bulk-Only Transport with the SCSI commands hosts use to
+//! mount a drive.
+//!
+//! embassy-usb can't stall bulk endpoints from a class, so a failing command
+//! still moves all the data the host asked for, padded or discarded, and the
+//! failure goes in the status and the sense data.
+//!
+
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use embassy_usb::driver::{Driver, Endpoint, EndpointError, EndpointIn, EndpointOut};
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Handler};
+
+pub const BLOCK_SIZE: usize = 512;
+
+const CLASS_MASS_STORAGE: u8 = 0x08;
+const SUBCLASS_SCSI: u8 = 0x06;
+const PROTOCOL_BULK_ONLY: u8 = 0x50;
+
+const REQ_GET_MAX_LUN: u8 = 0xfe;
+const REQ_RESET: u8 = 0xff;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CBW_LEN: usize = 31;
+
+// Shown by the host, padded with spaces
+const VENDOR: &[u8; 8] = b"embassy ";
+const PRODUCT: &[u8; 16] = b"LED matrix drive";
+const REVISION: &[u8; 4] = b"0.1 ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum BlockError {
+    /// Past the end of the device
+    OutOfRange,
+    /// The storage itself failed
+    Failed,
+}
+
+/// Storage behind the drive, in `BLOCK_SIZE` blocks
+pub trait BlockDevice {
+    fn block_count(&self) -> u32;
+    fn read_block(&mut self, lba: u32, block: &mut [u8; BLOCK_SIZE]) -> Result<(), BlockError>;
+    fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]) -> Result<(), BlockError>;
+    /// Makes every write so far permanent
+    fn flush(&mut self) -> Result<(), BlockError>;
+}
+
+/// Command Block Wrapper, one SCSI command from the host
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Command {
+    tag: u32,
+    /// Bytes the host wants to move in the data phase
+    length: u32,
+    /// Data goes to the host
+    device_to_host: bool,
+    cb: [u8; 16],
+}
+
+impl Command {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() != CBW_LEN || u32::from_le_bytes(packet[0..4].try_into().unwrap()) != CBW_SIGNATURE {
+            return None;
+        }
+        let cb_len = (packet[14] & 0x1f) as usize;
+        if cb_len == 0 || cb_len > 16 {
+            return None;
+        }
+        let mut cb = [0; 16];
+        cb[..cb_len].copy_from_slice(&packet[15..15 + cb_len]);
+        Some(Command {
+            tag: u32::from_le_bytes(packet[4..8].try_into().unwrap()),
+            length: u32::from_le_bytes(packet[8..12].try_into().unwrap()),
+            device_to_host: packet[12] & 0x80 != 0,
+            cb,
+        })
+    }
+
+    pub fn opcode(&self) -> u8 {
+        self.cb[0]
+    }
+
+    /// Changes what is stored
+    pub fn is_write(&self) -> bool {
+        self.opcode() == scsi::WRITE_10
+    }
+
+    fn lba(&self) -> u32 {
+        u32::from_be_bytes(self.cb[2..6].try_into().unwrap())
+    }
+
+    fn blocks(&self) -> u32 {
+        u16::from_be_bytes([self.cb[7], self.cb[8]]) as u32
+    }
+}
+
+mod scsi {
+    pub const TEST_UNIT_READY: u8 = 0x00;
+    pub const REQUEST_SENSE: u8 = 0x03;
+    pub const INQUIRY: u8 = 0x12;
+    pub const MODE_SENSE_6: u8 = 0x1a;
+    pub const START_STOP_UNIT: u8 = 0x1b;
+    pub const PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1e;
+    pub const READ_FORMAT_CAPACITIES: u8 = 0x23;
+    pub const READ_CAPACITY_10: u8 = 0x25;
+    pub const READ_10: u8 = 0x28;
+    pub const WRITE_10: u8 = 0x2a;
+    pub const VERIFY_10: u8 = 0x2f;
+    pub const SYNCHRONIZE_CACHE_10: u8 = 0x35;
+    pub const MODE_SENSE_10: u8 = 0x5a;
+}
+
+/// Sense key and additional sense code of the last failure
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Sense {
+    key: u8,
+    asc: u8,
+}
+
+impl Sense {
+    const NONE: Sense = Sense { key: 0, asc: 0 };
+    const INVALID_COMMAND: Sense = Sense { key: 0x05, asc: 0x20 };
+    const OUT_OF_RANGE: Sense = Sense { key: 0x05, asc: 0x21 };
+    const READ_ERROR: Sense = Sense { key: 0x03, asc: 0x11 };
+    const WRITE_ERROR: Sense = Sense { key: 0x03, asc: 0x0c };
+}
+
+struct Control {
+    iface: InterfaceNumber,
+}
+
+impl Handler for Control {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.iface.0 as u16)
+        {
+            return None;
+        }
+        match req.request {
+            // Nothing is queued between commands, so there's nothing to reset
+            REQ_RESET => Some(OutResponse::Accepted),
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Class, Recipient::Interface, self.iface.0 as u16)
+        {
+            return None;
+        }
+        match req.request {
+            REQ_GET_MAX_LUN => {
+                buf[0] = 0;
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Keeps the control request handler alive as long as the class
+pub struct State {
+    control: Option<Control>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    pub const fn new() -> Self {
+        State { control: None }
+    }
+}
+
+/// One SCSI logical unit over a pair of bulk endpoints
+pub struct MassStorageClass<'d, D: Driver<'d>> {
+    read_ep: D::EndpointOut,
+    write_ep: D::EndpointIn,
+    sense: Sense,
+}
+
+impl<'d, D: Driver<'d>> MassStorageClass<'d, D> {
+    pub fn new(builder: &mut Builder<'d, D>, state: &'d mut State, max_packet_size: u16) -> Self {
+        let mut func = builder.function(CLASS_MASS_STORAGE, SUBCLASS_SCSI, PROTOCOL_BULK_ONLY);
+        let mut iface = func.interface();
+        let iface_number = iface.interface_number();
+        let mut alt = iface.alt_setting(CLASS_MASS_STORAGE, SUBCLASS_SCSI, PROTOCOL_BULK_ONLY, None);
+        let read_ep = alt.endpoint_bulk_out(None, max_packet_size);
+        let write_ep = alt.endpoint_bulk_in(None, max_packet_size);
+        drop(func);
+
+        builder.handler(state.control.insert(Control { iface: iface_number }));
+        MassStorageClass {
+            read_ep,
+            write_ep,
+            sense: Sense::NONE,
+        }
+    }
+
+    /// Waits for the next command, skipping anything that isn't one.
+    /// Dropping this between packets is fine, e.g. to time out.
+    pub async fn command(&mut self) -> Result<Command, EndpointError> {
+        let mut packet = [0; 64];
+        loop {
+            let n = match self.read_ep.read(&mut packet).await {
+                Ok(n) => n,
+                Err(EndpointError::Disabled) => {
+                    self.read_ep.wait_enabled().await;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            match Command::parse(&packet[..n]) {
+                Some(command) => return Ok(command),
+                None => defmt::warn!("Not a mass storage command, {} bytes", n),
+            }
+        }
+    }
+
+    /// Runs the data and status phases of `command`
+    pub async fn execute(&mut self, command: Command, device: &mut impl BlockDevice) -> Result<(), EndpointError> {
+        let mut buf = [0; BLOCK_SIZE];
+        let result = match command.opcode() {
+            scsi::READ_10 => self.read(&command, device, &mut buf).await?,
+            scsi::WRITE_10 => self.write(&command, device, &mut buf).await?,
+            _ => {
+                let (reply, sense) = self.reply(&command, device, &mut buf);
+                let len = reply.min(command.length as usize);
+                if command.device_to_host {
+                    self.send(&buf[..len]).await?;
+                }
+                (len as u32, sense)
+            }
+        };
+        let (moved, sense) = result;
+
+        // Pad or drain whatever the host still expects
+        let mut left = command.length - moved.min(command.length);
+        let residue = left;
+        while left > 0 {
+            let n = left.min(BLOCK_SIZE as u32) as usize;
+            if command.device_to_host {
+                buf[..n].fill(0);
+                self.send(&buf[..n]).await?;
+            } else {
+                self.receive(&mut buf[..n]).await?;
+            }
+            left -= n as u32;
+        }
+
+        if command.opcode() != scsi::REQUEST_SENSE {
+            self.sense = sense;
+        }
+        let mut csw = [0; 13];
+        csw[0..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        csw[4..8].copy_from_slice(&command.tag.to_le_bytes());
+        csw[8..12].copy_from_slice(&residue.to_le_bytes());
+        csw[12] = if sense == Sense::NONE { 0 } else { 1 };
+        self.write_ep.write(&csw).await
+    }
+
+    /// Everything but reads and writes, the reply goes in `buf`
+    fn reply(
+        &mut self,
+        command: &Command,
+        device: &mut impl BlockDevice,
+        buf: &mut [u8; BLOCK_SIZE],
+    ) -> (usize, Sense) {
+        let blocks = device.block_count();
+        match command.opcode() {
+            scsi::TEST_UNIT_READY | scsi::PREVENT_ALLOW_MEDIUM_REMOVAL | scsi::VERIFY_10 => (0, Sense::NONE),
+            scsi::REQUEST_SENSE => {
+                buf[..18].fill(0);
+                buf[0] = 0x70;
+                buf[2] = self.sense.key;
+                buf[7] = 10;
+                buf[12] = self.sense.asc;
+                self.sense = Sense::NONE;
+                (18, Sense::NONE)
+            }
+            scsi::INQUIRY => {
+                buf[..36].fill(0);
+                // Direct access block device, removable, SPC-2
+                buf[1] = 0x80;
+                buf[2] = 0x04;
+                buf[3] = 0x02;
+                buf[4] = 31;
+                buf[8..16].copy_from_slice(VENDOR);
+                buf[16..32].copy_from_slice(PRODUCT);
+                buf[32..36].copy_from_slice(REVISION);
+                (36, Sense::NONE)
+            }
+            // No mode pages, not write protected
+            scsi::MODE_SENSE_6 => {
+                buf[..4].copy_from_slice(&[3, 0, 0, 0]);
+                (4, Sense::NONE)
+            }
+            scsi::MODE_SENSE_10 => {
+                buf[..8].copy_from_slice(&[0, 6, 0, 0, 0, 0, 0, 0]);
+                (8, Sense::NONE)
+            }
+            scsi::READ_FORMAT_CAPACITIES => {
+                buf[..4].copy_from_slice(&[0, 0, 0, 8]);
+                buf[4..8].copy_from_slice(&blocks.to_be_bytes());
+                // Formatted media, then the block length in 3 bytes
+                buf[8..12].copy_from_slice(&(0x0200_0000 | BLOCK_SIZE as u32).to_be_bytes());
+                (12, Sense::NONE)
+            }
+            scsi::READ_CAPACITY_10 => {
+                buf[..4].copy_from_slice(&(blocks - 1).to_be_bytes());
+                buf[4..8].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+                (8, Sense::NONE)
+            }
+            // Eject or sync, either way the host wants it stored
+            scsi::START_STOP_UNIT | scsi::SYNCHRONIZE_CACHE_10 => match device.flush() {
+                Ok(()) => (0, Sense::NONE),
+                Err(_) => (0, Sense::WRITE_ERROR),
+            },
+            op => {
+                defmt::debug!("Unsupported SCSI command {=u8:#x}", op);
+                (0, Sense::INVALID_COMMAND)
+            }
+        }
+    }
+
+    /// Bytes sent and how it went
+    async fn read(
+        &mut self,
+        command: &Command,
+        device: &mut impl BlockDevice,
+        buf: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(u32, Sense), EndpointError> {
+        let (lba, blocks) = (command.lba(), command.blocks());
+        if !command.device_to_host || blocks * BLOCK_SIZE as u32 > command.length {
+            return Ok((0, Sense::INVALID_COMMAND));
+        }
+        for block in lba..lba.saturating_add(blocks) {
+            match device.read_block(block, buf) {
+                Ok(()) => self.send(buf).await?,
+                Err(e) => return Ok(((block - lba) * BLOCK_SIZE as u32, sense(e, Sense::READ_ERROR))),
+            }
+        }
+        Ok((blocks * BLOCK_SIZE as u32, Sense::NONE))
+    }
+
+    /// Bytes received and how it went
+    async fn write(
+        &mut self,
+        command: &Command,
+        device: &mut impl BlockDevice,
+        buf: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(u32, Sense), EndpointError> {
+        let (lba, blocks) = (command.lba(), command.blocks());
+        if command.device_to_host || blocks * BLOCK_SIZE as u32 > command.length {
+            return Ok((0, Sense::INVALID_COMMAND));
+        }
+        // Keeps receiving after a failure, the host sends the data anyway
+        let mut result = Sense::NONE;
+        for block in lba..lba.saturating_add(blocks) {
+            self.receive(buf).await?;
+            if result == Sense::NONE
+                && let Err(e) = device.write_block(block, buf)
+            {
+                result = sense(e, Sense::WRITE_ERROR);
+            }
+        }
+        Ok((blocks * BLOCK_SIZE as u32, result))
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), EndpointError> {
+        for packet in data.chunks(self.write_ep.info().max_packet_size as usize) {
+            self.write_ep.write(packet).await?;
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self, data: &mut [u8]) -> Result<(), EndpointError> {
+        let max = self.read_ep.info().max_packet_size as usize;
+        let mut filled = 0;
+        while filled < data.len() {
+            let end = (filled + max).min(data.len());
+            match self.read_ep.read(&mut data[filled..end]).await? {
+                // A short packet ends the transfer early
+                0 => break,
+                n => filled += n,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sense(error: BlockError, failed: Sense) -> Sense {
+    match error {
+        BlockError::OutOfRange => Sense::OUT_OF_RANGE,
+        BlockError::Failed => failed,
+    }
+}
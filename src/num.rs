@@ -0,0 +1,19 @@
+//!
+//! Scalar type used by the physics effects.
+//!
+//! The RP2040 has no FPU, so with the `fixed-math` feature the simulations
+//! run on `I16F16` fixed point numbers instead of soft-float `f32`.
+//!
+
+#[cfg(feature = "fixed-math")]
+use fixed::types::I16F16;
+
+pub use matrix_math::num::Real;
+
+/// Scalar type selected by the `fixed-math` feature
+#[cfg(feature = "fixed-math")]
+pub type Scalar = I16F16;
+
+/// Scalar type selected by the `fixed-math` feature
+#[cfg(not(feature = "fixed-math"))]
+pub type Scalar = f32;
@@ -0,0 +1,94 @@
+//!
+//! What the `drive` firmware plays: the pictures on the drive, in the order
+//! they were copied, with settings from `CONFIG.TXT`.
+//!
+
+use heapless::String;
+
+use crate::fat::{DirEntry, Volume};
+
+pub const CONFIG_NAME: &[u8; 11] = b"CONFIG  TXT";
+
+/// Put on a freshly formatted drive
+pub const DEFAULT_CONFIG: &str = "\
+# Drop BMP and GIF files on the drive, 32x32 looks best.
+# They play in the order they were copied.
+
+# Seconds each picture stays up, animations play through at least once
+seconds 10
+
+# 0-100
+brightness 100
+";
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Config {
+    pub seconds: u32,
+    pub brightness: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            seconds: 10,
+            brightness: 100,
+        }
+    }
+}
+
+impl Config {
+    /// `CONFIG.TXT` from `volume`, defaults for anything missing or unreadable
+    pub fn load(volume: &Volume) -> Self {
+        let mut config = Config::default();
+        let Some(entry) = volume.find(CONFIG_NAME) else {
+            return config;
+        };
+        let mut line = String::<64>::new();
+        for byte in volume.open(&entry).chain(Some(b'\n')) {
+            if byte != b'\n' {
+                // Long lines are cut, they're comments or wrong anyway
+                let _ = line.push(byte as char);
+                continue;
+            }
+            config.apply(&line);
+            line.clear();
+        }
+        config
+    }
+
+    /// One `key value` line, `#` starts a comment
+    fn apply(&mut self, line: &str) {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_ascii_whitespace();
+        let (Some(key), Some(value)) = (words.next(), words.next()) else {
+            return;
+        };
+        match key {
+            "seconds" => match value.parse() {
+                Ok(s) => self.seconds = s,
+                Err(_) => defmt::warn!("CONFIG.TXT: bad seconds"),
+            },
+            "brightness" => match value.parse::<u8>() {
+                Ok(b) => self.brightness = b.min(100),
+                Err(_) => defmt::warn!("CONFIG.TXT: bad brightness"),
+            },
+            _ => defmt::warn!("CONFIG.TXT: unknown setting {}", key),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Kind {
+    Bmp,
+    Gif,
+}
+
+impl Kind {
+    pub fn of(entry: &DirEntry) -> Option<Kind> {
+        match entry.extension() {
+            b"BMP" => Some(Kind::Bmp),
+            b"GIF" => Some(Kind::Gif),
+            _ => None,
+        }
+    }
+}
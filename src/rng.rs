@@ -0,0 +1,39 @@
+//!
+//! Random number generators for the effects.
+//!
+//! Seeded from the ring oscillator by default. After [`set_seed`] every new
+//! generator comes from a fixed sequence instead, so an effect restarted with
+//! the same seed plays out the same way.
+//!
+
+use core::cell::Cell;
+
+use embassy_rp::clocks::RoscRng;
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use rand::{SeedableRng, rngs::SmallRng};
+
+// Next seed to hand out, None for hardware randomness
+static SEED: Mutex<CriticalSectionRawMutex, Cell<Option<u64>>> = Mutex::new(Cell::new(None));
+
+/// `None` goes back to seeding from the ring oscillator
+pub fn set_seed(seed: Option<u64>) {
+    SEED.lock(|s| s.set(seed));
+}
+
+/// Whether generators come from a fixed seed
+pub fn is_seeded() -> bool {
+    SEED.lock(|s| s.get().is_some())
+}
+
+pub fn small_rng() -> SmallRng {
+    let seed = SEED.lock(|s| {
+        let seed = s.get()?;
+        // Weyl sequence so every generator gets its own stream
+        s.set(Some(seed.wrapping_add(0x9e37_79b9_7f4a_7c15)));
+        Some(seed)
+    });
+    match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_rng(RoscRng).unwrap(),
+    }
+}
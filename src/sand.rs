@@ -0,0 +1,318 @@
+//! Falling sand cellular physics.
+//!
+//! <https://en.wikipedia.org/wiki/Falling-sand_game>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use rand::{Rng, rngs::SmallRng};
+
+const W: usize = 32;
+const H: usize = 32;
+
+const SPAWNERS: usize = 3;
+
+// Start over when the panel is this full
+const MAX_FILLED: usize = W * H * 3 / 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Element {
+    Empty,
+    Sand,
+    Water,
+    Stone,
+    Fire,
+    Smoke,
+    Plant,
+}
+
+impl Element {
+    /// Lighter elements get displaced by heavier ones falling into them
+    fn density(self) -> u8 {
+        match self {
+            Element::Empty => 0,
+            Element::Smoke => 1,
+            Element::Fire => 2,
+            Element::Water => 3,
+            Element::Sand => 4,
+            Element::Stone | Element::Plant => u8::MAX,
+        }
+    }
+
+    /// Frames fire and smoke last
+    fn lifetime(self) -> u8 {
+        match self {
+            Element::Fire => 12,
+            Element::Smoke => 24,
+            _ => 0,
+        }
+    }
+
+    fn color(self) -> Rgb555 {
+        match self {
+            Element::Empty => Rgb555::BLACK,
+            Element::Sand => Rgb555::YELLOW,
+            Element::Water => Rgb555::BLUE,
+            Element::Stone => Rgb555::WHITE,
+            Element::Fire => Rgb555::RED,
+            Element::Smoke => Rgb555::MAGENTA,
+            Element::Plant => Rgb555::GREEN,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    el: Element,
+    life: u8,
+    // Flips every step a cell gets updated so moved cells aren't updated twice
+    clock: bool,
+}
+
+const EMPTY: Cell = Cell {
+    el: Element::Empty,
+    life: 0,
+    clock: false,
+};
+
+/// Pours an element into the top row
+#[derive(Clone, Copy)]
+struct Spawner {
+    x: i32,
+    el: Element,
+    // Out of 16 steps
+    rate: u32,
+}
+
+pub struct Sand {
+    cells: [Cell; W * H],
+    spawners: [Spawner; SPAWNERS],
+    clock: bool,
+    rng: SmallRng,
+}
+
+impl Default for Sand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sand {
+    pub fn new() -> Self {
+        let mut sand = Self {
+            cells: [EMPTY; W * H],
+            spawners: [Spawner {
+                x: 0,
+                el: Element::Empty,
+                rate: 0,
+            }; SPAWNERS],
+            clock: false,
+            rng: crate::rng::small_rng(),
+        };
+        sand.reset();
+        sand
+    }
+
+    /// Clear the panel, add a few stone ledges with plants on them
+    pub fn reset(&mut self) {
+        self.cells.fill(EMPTY);
+        for _ in 0..3 {
+            let x = self.rng.gen_range(0..W as i32 - 8);
+            let y = self.rng.gen_range(10..H as i32 - 2);
+            let len = self.rng.gen_range(4..9);
+            for dx in 0..len {
+                self.set(x + dx, y, Element::Stone);
+                if self.rng.gen_ratio(1, 3) {
+                    self.set(x + dx, y - 1, Element::Plant);
+                }
+            }
+        }
+        self.shuffle_spawners();
+    }
+
+    /// Move the spawners to new random spots with new random elements
+    pub fn shuffle_spawners(&mut self) {
+        const POURED: [Element; 4] = [Element::Sand, Element::Water, Element::Fire, Element::Sand];
+        for s in self.spawners.iter_mut() {
+            *s = Spawner {
+                x: self.rng.gen_range(0..W as i32),
+                el: POURED[self.rng.gen_range(0..POURED.len())],
+                rate: self.rng.gen_range(2..8),
+            };
+        }
+    }
+
+    /// Drop an element at a point, e.g. from user input.
+    /// Only fills empty cells unless it's `Empty`, which erases.
+    pub fn pour(&mut self, x: i32, y: i32, el: Element) {
+        if let Some(i) = Self::idx(x, y)
+            && (el == Element::Empty || self.cells[i].el == Element::Empty)
+        {
+            self.set(x, y, el);
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.clock = !self.clock;
+
+        for s in 0..SPAWNERS {
+            let Spawner { x, el, rate } = self.spawners[s];
+            if self.rng.gen_ratio(rate, 16) {
+                self.pour(x, 0, el);
+            }
+        }
+
+        // Bottom up so falling things move once, alternating the horizontal
+        // scan direction to avoid drifting to one side
+        for y in (0..H as i32).rev() {
+            for i in 0..W as i32 {
+                let x = if self.clock { i } else { W as i32 - 1 - i };
+                self.update(x, y);
+            }
+        }
+
+        let filled = self.cells.iter().filter(|c| c.el != Element::Empty).count();
+        if filled > MAX_FILLED {
+            self.reset();
+        } else if self.rng.gen_ratio(1, 500) {
+            self.shuffle_spawners();
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = self.cells.iter().enumerate().map(|(i, c)| {
+            let x = (i % W) as i32;
+            let y = (i / W) as i32;
+            let color = match c.el {
+                // Flicker
+                Element::Fire if c.life & 2 != 0 => Rgb555::YELLOW,
+                el => el.color(),
+            };
+            Pixel(Point::new(x, y), color)
+        });
+
+        target.draw_iter(it)
+    }
+
+    fn update(&mut self, x: i32, y: i32) {
+        let Some(i) = Self::idx(x, y) else { return };
+        let c = self.cells[i];
+        if c.clock == self.clock {
+            return;
+        }
+        self.cells[i].clock = self.clock;
+
+        let side = if self.rng.r#gen() { 1 } else { -1 };
+        match c.el {
+            Element::Empty | Element::Stone => {}
+            Element::Sand => {
+                let _ = self.try_move(x, y, 0, 1)
+                    || self.try_move(x, y, side, 1)
+                    || self.try_move(x, y, -side, 1);
+            }
+            Element::Water => {
+                let _ = self.try_move(x, y, 0, 1)
+                    || self.try_move(x, y, side, 1)
+                    || self.try_move(x, y, -side, 1)
+                    || self.try_move(x, y, side, 0)
+                    || self.try_move(x, y, -side, 0);
+            }
+            Element::Plant => {
+                // Grows into water it touches
+                let (dx, dy) = self.random_neighbor();
+                if self.get(x + dx, y + dy) == Some(Element::Water) && self.rng.gen_ratio(1, 8) {
+                    self.set(x + dx, y + dy, Element::Plant);
+                }
+            }
+            Element::Fire => {
+                // Burns plants, gets put out by water
+                for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                    match self.get(x + dx, y + dy) {
+                        Some(Element::Plant) if self.rng.gen_ratio(1, 3) => {
+                            self.set(x + dx, y + dy, Element::Fire)
+                        }
+                        Some(Element::Water) => {
+                            self.set(x, y, Element::Smoke);
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+                if !self.age(i) {
+                    let ash = if self.rng.gen_ratio(1, 2) {
+                        Element::Smoke
+                    } else {
+                        Element::Empty
+                    };
+                    self.set(x, y, ash);
+                    return;
+                }
+                // Flickers upwards
+                let _ = self.try_move(x, y, side, -1) || self.try_move(x, y, 0, -1);
+            }
+            Element::Smoke => {
+                if !self.age(i) {
+                    self.set(x, y, Element::Empty);
+                    return;
+                }
+                let _ = self.try_move(x, y, 0, -1)
+                    || self.try_move(x, y, side, -1)
+                    || self.try_move(x, y, side, 0);
+            }
+        }
+    }
+
+    /// Swap with the neighbor at (dx, dy) if it's lighter when moving down,
+    /// or empty when moving sideways or up.
+    fn try_move(&mut self, x: i32, y: i32, dx: i32, dy: i32) -> bool {
+        let (Some(from), Some(to)) = (Self::idx(x, y), Self::idx(x + dx, y + dy)) else {
+            return false;
+        };
+        let movable = if dy > 0 {
+            self.cells[to].el.density() < self.cells[from].el.density()
+        } else {
+            self.cells[to].el == Element::Empty
+        };
+        if movable {
+            self.cells.swap(from, to);
+            // Whatever got displaced has moved this step too
+            self.cells[from].clock = self.clock;
+            self.cells[to].clock = self.clock;
+        }
+        movable
+    }
+
+    /// Count down the cell lifetime, false once it's over
+    fn age(&mut self, i: usize) -> bool {
+        self.cells[i].life = self.cells[i].life.saturating_sub(1);
+        self.cells[i].life > 0
+    }
+
+    fn random_neighbor(&mut self) -> (i32, i32) {
+        [(0, -1), (1, 0), (0, 1), (-1, 0)][self.rng.gen_range(0..4)]
+    }
+
+    fn get(&self, x: i32, y: i32) -> Option<Element> {
+        Self::idx(x, y).map(|i| self.cells[i].el)
+    }
+
+    fn set(&mut self, x: i32, y: i32, el: Element) {
+        if let Some(i) = Self::idx(x, y) {
+            self.cells[i] = Cell {
+                el,
+                life: el.lifetime(),
+                clock: self.clock,
+            };
+        }
+    }
+
+    /// Walls all around, no wraparound
+    fn idx(x: i32, y: i32) -> Option<usize> {
+        if (0..W as i32).contains(&x) && (0..H as i32).contains(&y) {
+            Some(x as usize + W * y as usize)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Line editing and command parsing for the serial shell.
+//!
+//! Transport agnostic: bytes go in, echo and whole lines come out.
+
+use core::fmt;
+use core::str::FromStr;
+
+use embedded_graphics::image::GetPixel;
+use embedded_graphics::pixelcolor::Rgb555;
+use embedded_graphics::prelude::*;
+use heapless::{String, Vec};
+
+use crate::effects::Effect;
+
+pub const MAX_LINE: usize = 64;
+
+pub const PROMPT: &str = "> ";
+
+pub const HELP: &str = "\
+effect [name]     switch effect, list them without a name
+next              switch to the next effect
+restart           start the current effect over
+brightness <0-100>
+speed <ms>        delay between frames, e.g. `speed 50ms`
+seed <n>|random   fixed random seed for reproducible runs, restarts the effect
+pause             freeze the current frame
+resume
+step [n]          advance n frames, pauses first
+media-keys on|off act on media keys forwarded by `matrix media-keys`
+screenshot        print the panel, a letter per pixel
+record [n]        print every frame shown, n of them or until a key
+bootloader        reboot into the USB bootloader to flash new firmware
+status
+help
+";
+
+/// Echo for one input byte is at most a line erase plus a recalled line
+pub type Echo = Vec<u8, { 3 * MAX_LINE + 4 }>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    Esc,
+    Csi,
+}
+
+/// Builds a line from terminal input: backspace, Ctrl-C, Ctrl-U, and up
+/// arrow to recall the previous line.
+pub struct LineEditor {
+    line: String<MAX_LINE>,
+    previous: String<MAX_LINE>,
+    escape: Escape,
+    // Lines end with CR, LF or CRLF
+    last_cr: bool,
+    done: bool,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineEditor {
+    pub const fn new() -> Self {
+        Self {
+            line: String::new(),
+            previous: String::new(),
+            escape: Escape::None,
+            last_cr: false,
+            done: false,
+        }
+    }
+
+    /// Takes one byte of input, true once a line is complete.
+    /// Whatever should be echoed back goes into `echo`.
+    pub fn feed(&mut self, byte: u8, echo: &mut Echo) -> bool {
+        if self.done {
+            self.done = false;
+            self.line.clear();
+        }
+        let after_cr = core::mem::replace(&mut self.last_cr, byte == b'\r');
+
+        match (self.escape, byte) {
+            (Escape::Esc, b'[') => self.escape = Escape::Csi,
+            (Escape::Esc, _) => self.escape = Escape::None,
+            (Escape::Csi, b'A') => {
+                self.escape = Escape::None;
+                self.erase(echo);
+                self.line.clone_from(&self.previous);
+                let _ = echo.extend_from_slice(self.line.as_bytes());
+            }
+            (Escape::Csi, b'B') => {
+                self.escape = Escape::None;
+                self.erase(echo);
+            }
+            // Parameters and intermediates until the final byte
+            (Escape::Csi, 0x20..=0x3f) => {}
+            (Escape::Csi, _) => self.escape = Escape::None,
+            (_, 0x1b) => self.escape = Escape::Esc,
+            (_, b'\n') if after_cr => {}
+            (_, b'\r' | b'\n') => {
+                let _ = echo.extend_from_slice(b"\r\n");
+                if !self.line.trim().is_empty() {
+                    self.previous.clone_from(&self.line);
+                }
+                self.done = true;
+            }
+            // Ctrl-C
+            (_, 0x03) => {
+                let _ = echo.extend_from_slice(b"^C\r\n");
+                self.line.clear();
+                self.done = true;
+            }
+            // Ctrl-U
+            (_, 0x15) => self.erase(echo),
+            // Backspace or DEL, depending on the terminal
+            (_, 0x08 | 0x7f) => {
+                if self.line.pop().is_some() {
+                    let _ = echo.extend_from_slice(b"\x08 \x08");
+                }
+            }
+            (_, 0x20..=0x7e) if self.line.len() < MAX_LINE => {
+                let _ = self.line.push(byte as char);
+                let _ = echo.push(byte);
+            }
+            _ => {}
+        }
+        self.done
+    }
+
+    /// The completed line, valid until the next `feed`
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    fn erase(&mut self, echo: &mut Echo) {
+        for _ in 0..self.line.len() {
+            let _ = echo.extend_from_slice(b"\x08 \x08");
+        }
+        self.line.clear();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Command {
+    Help,
+    Status,
+    /// `None` lists the effects
+    Effect(Option<Effect>),
+    Next,
+    Restart,
+    Brightness(u8),
+    SpeedMs(u32),
+    /// `None` goes back to hardware randomness
+    Seed(Option<u64>),
+    Pause,
+    Resume,
+    Step(u32),
+    /// Whether forwarded media keys do anything
+    MediaKeys(bool),
+    Screenshot,
+    /// `None` records until a key is pressed
+    Record(Option<u32>),
+    Bootloader,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ParseError {
+    Empty,
+    UnknownCommand,
+    UnknownEffect,
+    MissingArgument,
+    BadNumber,
+    NotOnOff,
+    /// Trailing words after a complete command
+    TooManyArguments,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::Empty => "empty line",
+            ParseError::UnknownCommand => "unknown command, try `help`",
+            ParseError::UnknownEffect => "unknown effect, `effect` lists them",
+            ParseError::MissingArgument => "missing argument",
+            ParseError::BadNumber => "bad number",
+            ParseError::NotOnOff => "expected on or off",
+            ParseError::TooManyArguments => "too many arguments",
+        })
+    }
+}
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_ascii_whitespace();
+        let name = words.next().ok_or(ParseError::Empty)?;
+        let arg = words.next();
+        if words.next().is_some() {
+            return Err(ParseError::TooManyArguments);
+        }
+        let no_arg = |cmd| match arg {
+            None => Ok(cmd),
+            Some(_) => Err(ParseError::TooManyArguments),
+        };
+
+        match name {
+            "help" | "?" => no_arg(Command::Help),
+            "status" => no_arg(Command::Status),
+            "next" => no_arg(Command::Next),
+            "restart" => no_arg(Command::Restart),
+            "pause" => no_arg(Command::Pause),
+            "resume" => no_arg(Command::Resume),
+            "effect" => match arg {
+                None => Ok(Command::Effect(None)),
+                Some(a) => Effect::from_name(a)
+                    .map(|e| Command::Effect(Some(e)))
+                    .ok_or(ParseError::UnknownEffect),
+            },
+            "brightness" => {
+                let percent: u8 = number(arg.ok_or(ParseError::MissingArgument)?)?;
+                Ok(Command::Brightness(percent.min(100)))
+            }
+            "speed" => {
+                let a = arg.ok_or(ParseError::MissingArgument)?;
+                Ok(Command::SpeedMs(number(a.strip_suffix("ms").unwrap_or(a))?))
+            }
+            "seed" => match arg.ok_or(ParseError::MissingArgument)? {
+                "random" => Ok(Command::Seed(None)),
+                a => Ok(Command::Seed(Some(number(a)?))),
+            },
+            "step" => Ok(Command::Step(arg.map(number).transpose()?.unwrap_or(1))),
+            "media-keys" => match arg.ok_or(ParseError::MissingArgument)? {
+                "on" => Ok(Command::MediaKeys(true)),
+                "off" => Ok(Command::MediaKeys(false)),
+                _ => Err(ParseError::NotOnOff),
+            },
+            "screenshot" => no_arg(Command::Screenshot),
+            "record" => Ok(Command::Record(arg.map(number).transpose()?)),
+            "bootloader" => no_arg(Command::Bootloader),
+            _ => Err(ParseError::UnknownCommand),
+        }
+    }
+}
+
+fn number<T: FromStr>(s: &str) -> Result<T, ParseError> {
+    s.parse().map_err(|_| ParseError::BadNumber)
+}
+
+/// Letter for each of the panel's colors, indexed by red, green and blue bit
+pub const PIXEL_LETTERS: &[u8; 8] = b".bgcrmyw";
+
+/// A `frame <n> <ms>` line, then a line of `PIXEL_LETTERS` for each row
+pub type FrameText = String<{ 40 + 33 * 32 }>;
+
+/// What `screenshot` and `record` print, `ms` is the uptime it was shown at
+pub fn frame_text(display: &impl GetPixel<Color = Rgb555>, frame: u32, ms: u64) -> FrameText {
+    use fmt::Write as _;
+
+    let mut text = FrameText::new();
+    let _ = writeln!(text, "frame {} {}", frame, ms);
+    for y in 0..32 {
+        for x in 0..32 {
+            let c = display.pixel(Point::new(x, y)).unwrap_or(Rgb555::BLACK);
+            let i = (c.r() > 0) as usize * 4 + (c.g() > 0) as usize * 2 + (c.b() > 0) as usize;
+            let _ = text.push(PIXEL_LETTERS[i] as char);
+        }
+        let _ = text.push('\n');
+    }
+    text
+}
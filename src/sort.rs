@@ -0,0 +1,337 @@
+//! Sorting algorithm visualizer, 32 bars and one compare or swap per step.
+//!
+//! Each algorithm runs up front on a copy of the bars and records what it
+//! did, `step` then replays one operation at a time.
+//!
+//! <https://en.wikipedia.org/wiki/Sorting_algorithm>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+use heapless::Vec;
+use rand::{Rng, rngs::SmallRng};
+
+const N: usize = 32;
+
+// Bubble sort on reversed input takes 496 compares and as many swaps,
+// nothing else gets close
+const MAX_OPS: usize = 1024;
+
+const BAR: Rgb555 = Rgb555::BLUE;
+const COMPARED: Rgb555 = Rgb555::RED;
+const WRITTEN: Rgb555 = Rgb555::YELLOW;
+const SORTED: Rgb555 = Rgb555::GREEN;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Algorithm {
+    Bubble,
+    Insertion,
+    /// Lomuto partitioning around the last element
+    Quick,
+    Heap,
+    /// Bottom up, merging through a scratch buffer
+    Merge,
+    /// LSD, one bit per pass
+    Radix,
+    /// Sorting network, needs a power of two bars
+    Bitonic,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 7] = [
+        Algorithm::Bubble,
+        Algorithm::Insertion,
+        Algorithm::Quick,
+        Algorithm::Heap,
+        Algorithm::Merge,
+        Algorithm::Radix,
+        Algorithm::Bitonic,
+    ];
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Compare(u8, u8),
+    Swap(u8, u8),
+    /// Write a value, for the algorithms that don't sort in place
+    Set(u8, u8),
+}
+
+/// Sorts a copy of the bars, keeping track of every operation
+struct Recorder {
+    bars: [u8; N],
+    ops: Vec<Op, MAX_OPS>,
+}
+
+impl Recorder {
+    fn less(&mut self, i: usize, j: usize) -> bool {
+        let _ = self.ops.push(Op::Compare(i as u8, j as u8));
+        self.bars[i] < self.bars[j]
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        let _ = self.ops.push(Op::Swap(i as u8, j as u8));
+        self.bars.swap(i, j);
+    }
+
+    fn set(&mut self, i: usize, v: u8) {
+        let _ = self.ops.push(Op::Set(i as u8, v));
+        self.bars[i] = v;
+    }
+
+    fn bubble(&mut self) {
+        for end in (1..N).rev() {
+            let mut swapped = false;
+            for i in 0..end {
+                if self.less(i + 1, i) {
+                    self.swap(i, i + 1);
+                    swapped = true;
+                }
+            }
+            if !swapped {
+                break;
+            }
+        }
+    }
+
+    fn insertion(&mut self) {
+        for i in 1..N {
+            let mut j = i;
+            while j > 0 && self.less(j, j - 1) {
+                self.swap(j, j - 1);
+                j -= 1;
+            }
+        }
+    }
+
+    fn quick(&mut self, lo: usize, hi: usize) {
+        if lo >= hi {
+            return;
+        }
+        let mut p = lo;
+        for i in lo..hi {
+            if self.less(i, hi) {
+                if i != p {
+                    self.swap(i, p);
+                }
+                p += 1;
+            }
+        }
+        if p != hi {
+            self.swap(p, hi);
+        }
+        if p > lo {
+            self.quick(lo, p - 1);
+        }
+        self.quick(p + 1, hi);
+    }
+
+    fn heap(&mut self) {
+        for i in (0..N / 2).rev() {
+            self.sift_down(i, N);
+        }
+        for end in (1..N).rev() {
+            self.swap(0, end);
+            self.sift_down(0, end);
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize, len: usize) {
+        loop {
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < len && self.less(largest, child) {
+                    largest = child;
+                }
+            }
+            if largest == i {
+                return;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+
+    fn merge(&mut self) {
+        let mut merged = [0; N];
+        let mut width = 1;
+        while width < N {
+            for lo in (0..N).step_by(2 * width) {
+                let mid = (lo + width).min(N);
+                let hi = (lo + 2 * width).min(N);
+                let (mut i, mut j) = (lo, mid);
+                for m in merged[lo..hi].iter_mut() {
+                    // Take from the right run only when it's strictly smaller, for stability
+                    let take_right = i == mid || (j < hi && self.less(j, i));
+                    if take_right {
+                        *m = self.bars[j];
+                        j += 1;
+                    } else {
+                        *m = self.bars[i];
+                        i += 1;
+                    }
+                }
+                for (k, &v) in merged[lo..hi].iter().enumerate() {
+                    self.set(lo + k, v);
+                }
+            }
+            width *= 2;
+        }
+    }
+
+    fn radix(&mut self) {
+        // Bars are 1..=N
+        let bits = usize::BITS - (N - 1).leading_zeros();
+        let mut sorted = [0; N];
+        for bit in 0..bits {
+            let mut k = 0;
+            for zeros in [true, false] {
+                for &v in self.bars.iter() {
+                    if ((v - 1) >> bit & 1 == 0) == zeros {
+                        sorted[k] = v;
+                        k += 1;
+                    }
+                }
+            }
+            for (i, &v) in sorted.iter().enumerate() {
+                self.set(i, v);
+            }
+        }
+    }
+
+    fn bitonic(&mut self) {
+        let mut k = 2;
+        while k <= N {
+            let mut j = k / 2;
+            while j > 0 {
+                for i in 0..N {
+                    let l = i ^ j;
+                    if l > i {
+                        // Ascending or descending depending on which block of k we're in
+                        let ascending = i & k == 0;
+                        if self.less(l, i) == ascending {
+                            self.swap(i, l);
+                        }
+                    }
+                }
+                j /= 2;
+            }
+            k *= 2;
+        }
+    }
+}
+
+pub struct Sorter {
+    algorithm: Algorithm,
+    bars: [u8; N],
+    ops: Vec<Op, MAX_OPS>,
+    next_op: usize,
+    rng: SmallRng,
+}
+
+impl Default for Sorter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sorter {
+    pub fn new() -> Self {
+        Self::with_algorithm(Algorithm::Bubble)
+    }
+
+    pub fn with_algorithm(algorithm: Algorithm) -> Self {
+        let mut sorter = Self {
+            algorithm,
+            bars: [0; N],
+            ops: Vec::new(),
+            next_op: 0,
+            rng: crate::rng::small_rng(),
+        };
+        sorter.shuffle();
+        sorter
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Shuffles the bars and starts sorting them over
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+        self.algorithm = algorithm;
+        self.shuffle();
+    }
+
+    /// New random bar order
+    pub fn shuffle(&mut self) {
+        for (i, b) in self.bars.iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+        // Fisher-Yates
+        for i in (1..N).rev() {
+            let j = self.rng.gen_range(0..=i);
+            self.bars.swap(i, j);
+        }
+
+        let mut rec = Recorder {
+            bars: self.bars,
+            ops: Vec::new(),
+        };
+        match self.algorithm {
+            Algorithm::Bubble => rec.bubble(),
+            Algorithm::Insertion => rec.insertion(),
+            Algorithm::Quick => rec.quick(0, N - 1),
+            Algorithm::Heap => rec.heap(),
+            Algorithm::Merge => rec.merge(),
+            Algorithm::Radix => rec.radix(),
+            Algorithm::Bitonic => rec.bitonic(),
+        }
+        self.ops = rec.ops;
+        self.next_op = 0;
+    }
+
+    pub fn is_sorted(&self) -> bool {
+        self.next_op >= self.ops.len()
+    }
+
+    /// Number of compares, swaps and writes so far
+    pub fn ops(&self) -> usize {
+        self.next_op
+    }
+
+    pub fn step(&mut self) {
+        let Some(&op) = self.ops.get(self.next_op) else {
+            return;
+        };
+        match op {
+            Op::Compare(..) => {}
+            Op::Swap(i, j) => self.bars.swap(i as usize, j as usize),
+            Op::Set(i, v) => self.bars[i as usize] = v,
+        }
+        self.next_op += 1;
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        // Highlight the last operation
+        let (marked, mark) = match self.next_op.checked_sub(1).and_then(|i| self.ops.get(i)) {
+            Some(&Op::Compare(i, j)) => ([Some(i), Some(j)], COMPARED),
+            Some(&Op::Swap(i, j)) => ([Some(i), Some(j)], WRITTEN),
+            Some(&Op::Set(i, _)) => ([Some(i), None], WRITTEN),
+            None => ([None; 2], BAR),
+        };
+
+        let it = self.bars.iter().enumerate().flat_map(|(x, &h)| {
+            let color = if self.is_sorted() {
+                SORTED
+            } else if marked.contains(&Some(x as u8)) {
+                mark
+            } else {
+                BAR
+            };
+            (N as i32 - h as i32..N as i32).map(move |y| Pixel(Point::new(x as i32, y), color))
+        });
+
+        target.draw_iter(it)
+    }
+}
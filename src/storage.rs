@@ -0,0 +1,113 @@
+//!
+//! The upper megabyte of flash as a block device, `memory.x` keeps the
+//! firmware below it.
+//!
+//! Flash erases 4 KB sectors, so writes collect in a one sector cache that is
+//! written back when another sector is touched or on `flush`. Erasing and
+//! programming pause core 1 and run from RAM, see `embassy_rp::flash`.
+//!
+
+use embassy_rp::flash::{Blocking, ERASE_SIZE, Flash};
+use embassy_rp::peripherals::FLASH;
+
+use crate::msc::{BLOCK_SIZE, BlockDevice, BlockError};
+
+pub(crate) const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+/// Start of the drive from the start of flash
+pub const OFFSET: u32 = 0x10_0000;
+pub const SIZE: u32 = 0x10_0000;
+
+const XIP_BASE: u32 = 0x1000_0000;
+
+const BLOCKS_PER_SECTOR: u32 = (ERASE_SIZE / BLOCK_SIZE) as u32;
+
+/// The drive as mapped into the address space. Writes show up here once
+/// they're flushed.
+pub fn contents() -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts((XIP_BASE + OFFSET) as *const u8, SIZE as usize) }
+}
+
+pub struct FlashDisk {
+    flash: Flash<'static, FLASH, Blocking, FLASH_SIZE>,
+    /// Sector in `cache`, counted from `OFFSET`
+    sector: Option<u32>,
+    cache: [u8; ERASE_SIZE],
+    dirty: bool,
+}
+
+impl FlashDisk {
+    pub fn new(flash: Flash<'static, FLASH, Blocking, FLASH_SIZE>) -> Self {
+        FlashDisk {
+            flash,
+            sector: None,
+            cache: [0; ERASE_SIZE],
+            dirty: false,
+        }
+    }
+
+    /// Brings `sector` into the cache, writing back the one there
+    fn load(&mut self, sector: u32) -> Result<(), BlockError> {
+        if self.sector == Some(sector) {
+            return Ok(());
+        }
+        self.flush()?;
+        self.sector = None;
+        let offset = OFFSET + sector * ERASE_SIZE as u32;
+        self.flash
+            .blocking_read(offset, &mut self.cache)
+            .map_err(|_| BlockError::Failed)?;
+        self.sector = Some(sector);
+        Ok(())
+    }
+}
+
+impl BlockDevice for FlashDisk {
+    fn block_count(&self) -> u32 {
+        SIZE / BLOCK_SIZE as u32
+    }
+
+    fn read_block(&mut self, lba: u32, block: &mut [u8; BLOCK_SIZE]) -> Result<(), BlockError> {
+        if lba >= self.block_count() {
+            return Err(BlockError::OutOfRange);
+        }
+        let (sector, index) = (lba / BLOCKS_PER_SECTOR, (lba % BLOCKS_PER_SECTOR) as usize);
+        if self.sector == Some(sector) {
+            block.copy_from_slice(&self.cache[index * BLOCK_SIZE..][..BLOCK_SIZE]);
+            return Ok(());
+        }
+        let start = lba as usize * BLOCK_SIZE;
+        block.copy_from_slice(&contents()[start..start + BLOCK_SIZE]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, lba: u32, block: &[u8; BLOCK_SIZE]) -> Result<(), BlockError> {
+        if lba >= self.block_count() {
+            return Err(BlockError::OutOfRange);
+        }
+        let (sector, index) = (lba / BLOCKS_PER_SECTOR, (lba % BLOCKS_PER_SECTOR) as usize);
+        self.load(sector)?;
+        let cached = &mut self.cache[index * BLOCK_SIZE..][..BLOCK_SIZE];
+        if cached != block {
+            cached.copy_from_slice(block);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockError> {
+        let Some(sector) = self.sector.filter(|_| self.dirty) else {
+            return Ok(());
+        };
+        let offset = OFFSET + sector * ERASE_SIZE as u32;
+        self.flash
+            .blocking_erase(offset, offset + ERASE_SIZE as u32)
+            .and_then(|()| self.flash.blocking_write(offset, &self.cache))
+            .map_err(|e| {
+                defmt::error!("Flash write at {=u32:#x} failed: {}", offset, e);
+                BlockError::Failed
+            })?;
+        self.dirty = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+//!
+//! Frames pushed from a host, decoded straight into a display buffer.
+//!
+//! The decoder lives in `protocol/` so its tests run on the host, see there
+//! for the `LMFR` header and the LED strip protocols.
+//!
+
+pub use matrix_protocol::stream::{
+    ADALIGHT_GREETING, Frame, FrameDecoder, FrameHeader, HEADER_LEN, Layout, MAGIC, PixelFormat, Protocol,
+};
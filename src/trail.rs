@@ -0,0 +1,49 @@
+//!
+//! Ring buffer of recent positions, drawn as a tail behind moving things
+//!
+
+/// Last `N` points as (x, y, tag), the tag is free for the effect to use
+/// (e.g. depth for coloring)
+pub struct Trail<const N: usize> {
+    points: [(i8, i8, u8); N],
+    idx: usize,
+}
+
+impl<const N: usize> Default for Trail<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Trail<N> {
+    pub fn new() -> Self {
+        Self {
+            // Off screen until filled in
+            points: [(-1, -1, 0); N],
+            idx: 0,
+        }
+    }
+
+    pub fn push(&mut self, x: i8, y: i8, tag: u8) {
+        self.points[self.idx] = (x, y, tag);
+        self.idx = (self.idx + 1) % N;
+    }
+
+    /// Forget all points
+    pub fn clear(&mut self) {
+        self.points.fill((-1, -1, 0));
+    }
+
+    /// Oldest to newest, the last one is the head
+    pub fn iter(&self) -> impl Iterator<Item = (i8, i8, u8)> + '_ {
+        (0..N).map(move |i| self.points[(self.idx + i) % N])
+    }
+
+    pub fn len(&self) -> usize {
+        N
+    }
+
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+}
@@ -0,0 +1,213 @@
+//!
+//! USB device setup shared by the binaries that talk to a host.
+//!
+//! Every device also gets the Raspberry Pi reset interface, so the host CLI
+//! and `picotool reboot -u -f` can put the board in the ROM bootloader for
+//! flashing without holding BOOTSEL.
+//!
+
+use embassy_futures::select::{select, Either};
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_rp::peripherals::{FLASH, USB};
+use embassy_rp::usb::{Driver, InterruptHandler};
+use embassy_rp::{bind_interrupts, Peri};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+use embassy_usb::class::cdc_acm::Sender;
+use embassy_usb::control::{OutResponse, Recipient, Request, RequestType};
+use embassy_usb::driver::EndpointError;
+use embassy_usb::msos::{self, windows_version};
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Config, Handler, UsbDevice};
+use static_cell::StaticCell;
+
+pub type UsbDriver = Driver<'static, USB>;
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => InterruptHandler<USB>;
+});
+
+// pid.codes test VID/PID, fine for devices that never leave the desk
+const VID: u16 = 0x1209;
+const PID: u16 = 0x0001;
+
+/// Full speed bulk endpoints can't go above this
+pub const MAX_PACKET_SIZE: u16 = 64;
+
+// The pico-sdk's reset interface, picotool finds it by class, subclass and protocol
+const CLASS_VENDOR: u8 = 0xff;
+const SUBCLASS_RESET: u8 = 0x00;
+const PROTOCOL_RESET: u8 = 0x01;
+const REQ_BOOTSEL: u8 = 0x01;
+const REQ_FLASH: u8 = 0x02;
+
+// Lets Windows bind WinUSB to the reset interface without an .inf
+const MSOS_VENDOR_CODE: u8 = 0x01;
+const RESET_GUID: &str = "{bc7398c1-73cd-4cb7-98b8-913a8fca7bf6}";
+
+// Long enough to finish the control transfer or flush a reply before resetting
+const REBOOT_DELAY_MS: u64 = 100;
+
+pub fn driver(usb: Peri<'static, USB>) -> UsbDriver {
+    Driver::new(usb, Irqs)
+}
+
+/// Device descriptor strings and power, with IADs so classes can be combined
+pub fn config(product: &'static str) -> Config<'static> {
+    let mut config = Config::new(VID, PID);
+    config.manufacturer = Some("embassy-rp LED matrix");
+    config.product = Some(product);
+    config.serial_number = Some(serial_number());
+    config.max_power = 100;
+    config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
+    config.composite_with_iads = true;
+    config.device_class = 0xef;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config
+}
+
+static SERIAL: StaticCell<[u8; 16]> = StaticCell::new();
+
+/// The flash chip's unique ID in hex, so the host can tell boards apart
+fn serial_number() -> &'static str {
+    // Borrowed for one read while the device is set up, nothing else uses the flash then
+    let mut flash = Flash::<_, Blocking, { crate::storage::FLASH_SIZE }>::new_blocking(unsafe { FLASH::steal() });
+    let mut id = [0; 8];
+    if let Err(e) = flash.blocking_unique_id(&mut id) {
+        defmt::warn!("Can't read the flash ID: {}", e);
+    }
+    let serial = SERIAL.init([0; 16]);
+    for (hex, byte) in serial.as_chunks_mut::<2>().0.iter_mut().zip(id) {
+        *hex = [HEX_DIGITS[(byte >> 4) as usize], HEX_DIGITS[(byte & 0x0f) as usize]];
+    }
+    core::str::from_utf8(serial).unwrap()
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+struct Buffers {
+    config_descriptor: [u8; 256],
+    bos_descriptor: [u8; 256],
+    msos_descriptor: [u8; 256],
+    control: [u8; 64],
+}
+
+static BUFFERS: StaticCell<Buffers> = StaticCell::new();
+static RESET: StaticCell<ResetInterface> = StaticCell::new();
+
+/// Builder backed by static descriptor buffers, with the reset interface
+/// and the `usb-log` serial port already added, can only be called once
+pub fn builder(driver: UsbDriver, config: Config<'static>) -> Builder<'static, UsbDriver> {
+    let b = BUFFERS.init(Buffers {
+        config_descriptor: [0; 256],
+        bos_descriptor: [0; 256],
+        msos_descriptor: [0; 256],
+        control: [0; 64],
+    });
+    let mut builder = Builder::new(
+        driver,
+        config,
+        &mut b.config_descriptor,
+        &mut b.bos_descriptor,
+        &mut b.msos_descriptor,
+        &mut b.control,
+    );
+
+    builder.msos_descriptor(windows_version::WIN8_1, MSOS_VENDOR_CODE);
+    let mut function = builder.function(CLASS_VENDOR, SUBCLASS_RESET, PROTOCOL_RESET);
+    function.msos_feature(msos::CompatibleIdFeatureDescriptor::new("WINUSB", ""));
+    function.msos_feature(msos::RegistryPropertyFeatureDescriptor::new(
+        "DeviceInterfaceGUIDs",
+        msos::PropertyData::RegMultiSz(&[RESET_GUID]),
+    ));
+    let mut interface = function.interface();
+    let iface = interface.interface_number();
+    interface.alt_setting(CLASS_VENDOR, SUBCLASS_RESET, PROTOCOL_RESET, None);
+    drop(function);
+    builder.handler(RESET.init(ResetInterface { iface }));
+
+    // Before the firmware's own ports, that's how the host CLI tells them apart
+    #[cfg(feature = "usb-log")]
+    crate::log::add_class(&mut builder);
+    builder
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Reboot {
+    /// The ROM's USB drive and picoboot interface, to flash new firmware
+    Bootloader,
+    /// Start the firmware over
+    Firmware,
+}
+
+static REBOOT: Signal<CriticalSectionRawMutex, Reboot> = Signal::new();
+
+/// Reboots shortly, from `usb_task`, so a reply still gets out
+pub fn reboot(to: Reboot) {
+    REBOOT.signal(to);
+}
+
+struct ResetInterface {
+    iface: InterfaceNumber,
+}
+
+impl Handler for ResetInterface {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if (req.request_type, req.recipient, req.index)
+            != (RequestType::Vendor, Recipient::Interface, self.iface.0 as u16)
+        {
+            return None;
+        }
+        match req.request {
+            REQ_BOOTSEL => reboot(Reboot::Bootloader),
+            REQ_FLASH => reboot(Reboot::Firmware),
+            _ => return Some(OutResponse::Rejected),
+        }
+        Some(OutResponse::Accepted)
+    }
+}
+
+/// Keeps the device enumerated and answering control requests, spawn it once
+#[embassy_executor::task]
+pub async fn usb_task(mut usb: UsbDevice<'static, UsbDriver>) -> ! {
+    let reboot = async {
+        let to = REBOOT.wait().await;
+        Timer::after_millis(REBOOT_DELAY_MS).await;
+        to
+    };
+    let to = match select(run(&mut usb), reboot).await {
+        Either::First(never) => never,
+        Either::Second(to) => to,
+    };
+    defmt::info!("Rebooting to {}", to);
+    match to {
+        Reboot::Bootloader => {
+            embassy_rp::rom_data::reset_to_usb_boot(0, 0);
+            unreachable!()
+        }
+        Reboot::Firmware => cortex_m::peripheral::SCB::sys_reset(),
+    }
+}
+
+async fn run(usb: &mut UsbDevice<'static, UsbDriver>) -> ! {
+    #[cfg(feature = "usb-log")]
+    match select(usb.run(), crate::log::forward()).await {
+        Either::First(never) | Either::Second(never) => never,
+    }
+    #[cfg(not(feature = "usb-log"))]
+    usb.run().await
+}
+
+/// Split into packets, ending with a short one so the host doesn't wait for more
+pub async fn write_all(sender: &mut Sender<'static, UsbDriver>, data: &[u8]) -> Result<(), EndpointError> {
+    let max = sender.max_packet_size() as usize;
+    for chunk in data.chunks(max) {
+        sender.write_packet(chunk).await?;
+    }
+    if data.len().is_multiple_of(max) {
+        sender.write_packet(&[]).await?;
+    }
+    Ok(())
+}
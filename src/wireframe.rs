@@ -0,0 +1,433 @@
+//! Tiny integer-only 3D renderer: rotating meshes drawn as wireframes or
+//! flat shaded triangles.
+//!
+//! Model coordinates are `i8` with 64 as the unit, rotation uses the shared
+//! sine table so a frame is a few hundred multiplies and no floats.
+
+use embedded_graphics::{
+    pixelcolor::Rgb555,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Triangle},
+};
+use heapless::Vec;
+
+use crate::demos::{cos, sin};
+
+pub const MAX_VERTICES: usize = 192;
+pub const MAX_FACES: usize = 160;
+
+// Camera distance from the origin and focal length in pixels, a unit sphere
+// at the origin is 12 pixels in radius
+const DISTANCE: i32 = 3 * 64;
+const FOCAL: i32 = 36;
+const CX: i32 = 16;
+const CY: i32 = 16;
+
+// Upper left, towards the viewer
+const LIGHT: [i64; 3] = [-1, 1, 2];
+const LIGHT_LEN_SQ: i64 = 6;
+
+// Lathe and tube resolution
+const SEGMENTS: usize = 12;
+const TEAPOT_SEGMENTS: usize = 10;
+const TUBE_SIDES: usize = 6;
+
+/// Quad, or a triangle with the last index repeated, counter-clockwise seen
+/// from the outside
+pub type Face = [u8; 4];
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Shape {
+    Cube,
+    Icosahedron,
+    Torus,
+    /// Lathed body and lid, tubes for the spout and handle
+    Teapot,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 4] = [Shape::Cube, Shape::Icosahedron, Shape::Torus, Shape::Teapot];
+
+    /// Dark to bright
+    fn ramp(self) -> [Rgb555; 3] {
+        match self {
+            Shape::Cube => [Rgb555::BLUE, Rgb555::CYAN, Rgb555::WHITE],
+            Shape::Icosahedron => [Rgb555::RED, Rgb555::MAGENTA, Rgb555::WHITE],
+            Shape::Torus => [Rgb555::GREEN, Rgb555::YELLOW, Rgb555::WHITE],
+            Shape::Teapot => [Rgb555::RED, Rgb555::YELLOW, Rgb555::WHITE],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Shading {
+    /// Edges of the faces turned towards the camera
+    Wireframe,
+    /// Filled faces, back to front, one color per face
+    Flat,
+}
+
+#[derive(Default)]
+pub struct Mesh {
+    pub vertices: Vec<[i8; 3], MAX_VERTICES>,
+    pub faces: Vec<Face, MAX_FACES>,
+}
+
+impl Mesh {
+    pub fn new(shape: Shape) -> Self {
+        match shape {
+            Shape::Cube => Self::cube(),
+            Shape::Icosahedron => Self::icosahedron(),
+            Shape::Torus => Self::torus(),
+            Shape::Teapot => Self::teapot(),
+        }
+    }
+
+    pub fn cube() -> Self {
+        const S: i8 = 40;
+        let mut mesh = Self::default();
+        // Bits 0, 1, 2 of the index pick the sign of x, y, z
+        for i in 0..8 {
+            let c = |bit: i32| if i & bit != 0 { S } else { -S };
+            let _ = mesh.vertices.push([c(1), c(2), c(4)]);
+        }
+        for f in [
+            [1, 3, 7, 5],
+            [0, 4, 6, 2],
+            [2, 6, 7, 3],
+            [0, 1, 5, 4],
+            [4, 5, 7, 6],
+            [0, 2, 3, 1],
+        ] {
+            let _ = mesh.faces.push(f);
+        }
+        mesh
+    }
+
+    pub fn icosahedron() -> Self {
+        // (0, ±1, ±φ) and its cyclic permutations, scaled to a radius of ~64
+        const A: i8 = 34;
+        const B: i8 = 55;
+        let mut mesh = Self::default();
+        for v in [
+            [-A, B, 0],
+            [A, B, 0],
+            [-A, -B, 0],
+            [A, -B, 0],
+            [0, -A, B],
+            [0, A, B],
+            [0, -A, -B],
+            [0, A, -B],
+            [B, 0, -A],
+            [B, 0, A],
+            [-B, 0, -A],
+            [-B, 0, A],
+        ] {
+            let _ = mesh.vertices.push(v);
+        }
+        for [a, b, c] in [
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ] {
+            let _ = mesh.faces.push([a, b, c, c]);
+        }
+        mesh
+    }
+
+    pub fn torus() -> Self {
+        const R: i32 = 44;
+        const TUBE: i32 = 20;
+        const RINGS: usize = 6;
+        let mut mesh = Self::default();
+        // A small circle in the xy plane, lathed around the y axis
+        let mut profile: Vec<(i8, i8), RINGS> = Vec::new();
+        for i in 0..RINGS {
+            let a = (i * 256 / RINGS) as u8;
+            let _ = profile.push((
+                (R + TUBE * cos(a) as i32 / 127) as i8,
+                (TUBE * sin(a) as i32 / 127) as i8,
+            ));
+        }
+        mesh.lathe(&profile, SEGMENTS, true);
+        mesh
+    }
+
+    pub fn teapot() -> Self {
+        let mut mesh = Self::default();
+        // (radius, height) from the middle of the bottom up to the knob on the lid
+        mesh.lathe(
+            &[
+                (0, -40),
+                (40, -40),
+                (54, -26),
+                (58, -8),
+                (52, 10),
+                (38, 22),
+                (30, 24),
+                (20, 30),
+                (6, 36),
+                (10, 42),
+                (0, 46),
+            ],
+            TEAPOT_SEGMENTS,
+            false,
+        );
+        // (x, y, radius)
+        mesh.tube(&[(40, -16, 10), (56, -6, 8), (64, 6, 6), (70, 18, 5), (78, 26, 5)]);
+        mesh.tube(&[(-44, 16, 4), (-60, 18, 4), (-70, 6, 4), (-66, -12, 4), (-48, -24, 4)]);
+        mesh
+    }
+
+    /// Surface of revolution around the y axis from (radius, y) points.
+    /// `closed` joins the last point back to the first.
+    fn lathe(&mut self, profile: &[(i8, i8)], segments: usize, closed: bool) {
+        let base = self.vertices.len();
+        for &(r, y) in profile {
+            for s in 0..segments {
+                let a = (s * 256 / segments) as u8;
+                let x = r as i32 * cos(a) as i32 / 127;
+                let z = -(r as i32) * sin(a) as i32 / 127;
+                let _ = self.vertices.push([x as i8, y, z as i8]);
+            }
+        }
+
+        let rings = if closed { profile.len() } else { profile.len() - 1 };
+        for p in 0..rings {
+            let q = (p + 1) % profile.len();
+            for s in 0..segments {
+                let t = (s + 1) % segments;
+                let v = |ring: usize, seg: usize| (base + ring * segments + seg) as u8;
+                let _ = self.faces.push([v(p, s), v(p, t), v(q, t), v(q, s)]);
+            }
+        }
+    }
+
+    /// Open tube along a path in the xy plane, from (x, y, radius) points
+    fn tube(&mut self, path: &[(i8, i8, i8)]) {
+        let base = self.vertices.len();
+        for i in 0..path.len() {
+            // Direction along the path, rotated a quarter turn in the xy plane
+            let (x0, y0, _) = path[i.saturating_sub(1)];
+            let (x1, y1, _) = path[(i + 1).min(path.len() - 1)];
+            let (tx, ty) = (x1 as i32 - x0 as i32, y1 as i32 - y0 as i32);
+            let len = (tx * tx + ty * ty).isqrt().max(1);
+            let (nx, ny) = (-ty, tx);
+
+            let (x, y, r) = path[i];
+            for s in 0..TUBE_SIDES {
+                let a = (s * 256 / TUBE_SIDES) as u8;
+                let c = r as i32 * cos(a) as i32 / 127;
+                let _ = self.vertices.push([
+                    (x as i32 + c * nx / len) as i8,
+                    (y as i32 + c * ny / len) as i8,
+                    (r as i32 * sin(a) as i32 / 127) as i8,
+                ]);
+            }
+        }
+
+        for p in 0..path.len() - 1 {
+            for s in 0..TUBE_SIDES {
+                let t = (s + 1) % TUBE_SIDES;
+                let v = |ring: usize, side: usize| (base + ring * TUBE_SIDES + side) as u8;
+                let _ = self.faces.push([v(p, s), v(p, t), v(p + 1, t), v(p + 1, s)]);
+            }
+        }
+    }
+}
+
+pub struct Wireframe {
+    shape: Shape,
+    shading: Shading,
+    mesh: Mesh,
+    // Rotation around x, y, z in 1/256 of a sine table step
+    angles: [u16; 3],
+    spin: [u16; 3],
+    // Results of the last step
+    screen: [Point; MAX_VERTICES],
+    // Faces turned towards the camera, back to front, with their brightness
+    visible: Vec<(u8, u8), MAX_FACES>,
+}
+
+impl Default for Wireframe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wireframe {
+    pub fn new() -> Self {
+        Self::with_shape(Shape::Cube, Shading::Wireframe)
+    }
+
+    pub fn with_shape(shape: Shape, shading: Shading) -> Self {
+        let mut w = Self {
+            shape,
+            shading,
+            mesh: Mesh::new(shape),
+            angles: [0; 3],
+            spin: [150, 230, 70],
+            screen: [Point::zero(); MAX_VERTICES],
+            visible: Vec::new(),
+        };
+        w.step();
+        w
+    }
+
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: Shape) {
+        self.shape = shape;
+        self.mesh = Mesh::new(shape);
+    }
+
+    pub fn shading(&self) -> Shading {
+        self.shading
+    }
+
+    pub fn set_shading(&mut self, shading: Shading) {
+        self.shading = shading;
+    }
+
+    /// Rotation speeds around x, y and z, in 1/256 of a sine table step per frame
+    pub fn set_spin(&mut self, spin: [u16; 3]) {
+        self.spin = spin;
+    }
+
+    /// Rotate, project, cull and sort
+    pub fn step(&mut self) {
+        for (a, s) in self.angles.iter_mut().zip(self.spin) {
+            *a = a.wrapping_add(s);
+        }
+        let m = rotation(self.angles.map(|a| (a >> 8) as u8));
+
+        let mut world = [[0i32; 3]; MAX_VERTICES];
+        for (i, v) in self.mesh.vertices.iter().enumerate() {
+            let v = v.map(|c| c as i32);
+            for (r, w) in m.iter().zip(world[i].iter_mut()) {
+                *w = (r[0] * v[0] + r[1] * v[1] + r[2] * v[2]) >> 7;
+            }
+            let [x, y, z] = world[i];
+            let depth = DISTANCE - z;
+            self.screen[i] = Point::new(CX + x * FOCAL / depth, CY - y * FOCAL / depth);
+        }
+
+        // Painter's algorithm, sort key is the summed depth of the corners
+        let mut order: Vec<(i32, u8, u8), MAX_FACES> = Vec::new();
+        for (i, f) in self.mesh.faces.iter().enumerate() {
+            let [a, b, c, d] = f.map(|v| v as usize);
+
+            // Diagonals work for quads, triangles and quads with a collapsed edge
+            let (p, q) = (self.screen[c] - self.screen[a], self.screen[d] - self.screen[b]);
+            // Screen y points down, so counter-clockwise faces come out negative
+            if p.x * q.y - p.y * q.x >= 0 {
+                continue;
+            }
+
+            let n = cross(sub(world[c], world[a]), sub(world[d], world[b]));
+            let _ = order.push((
+                world[a][2] + world[b][2] + world[c][2] + world[d][2],
+                i as u8,
+                brightness(n),
+            ));
+        }
+        order.sort_unstable_by_key(|&(z, _, _)| z);
+
+        self.visible.clear();
+        for (_, f, b) in order {
+            let _ = self.visible.push((f, b));
+        }
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let ramp = self.shape.ramp();
+        for &(f, b) in self.visible.iter() {
+            let [a, b_, c, d] = self.mesh.faces[f as usize].map(|v| self.screen[v as usize]);
+            let color = ramp[b as usize];
+            match self.shading {
+                Shading::Wireframe => {
+                    let style = PrimitiveStyle::with_stroke(color, 1);
+                    for (p, q) in [(a, b_), (b_, c), (c, d), (d, a)] {
+                        if p != q {
+                            Line::new(p, q).into_styled(style).draw(target)?;
+                        }
+                    }
+                }
+                Shading::Flat => {
+                    let style = PrimitiveStyle::with_fill(color);
+                    Triangle::new(a, b_, c).into_styled(style).draw(target)?;
+                    if c != d {
+                        Triangle::new(a, c, d).into_styled(style).draw(target)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rz * Ry * Rx, scaled by 128
+fn rotation([ax, ay, az]: [u8; 3]) -> [[i32; 3]; 3] {
+    let (sx, cx) = (sin(ax) as i32, cos(ax) as i32);
+    let (sy, cy) = (sin(ay) as i32, cos(ay) as i32);
+    let (sz, cz) = (sin(az) as i32, cos(az) as i32);
+    [
+        [
+            (cz * cy) >> 7,
+            ((((cz * sy) >> 7) * sx) >> 7) - ((sz * cx) >> 7),
+            ((((cz * sy) >> 7) * cx) >> 7) + ((sz * sx) >> 7),
+        ],
+        [
+            (sz * cy) >> 7,
+            ((((sz * sy) >> 7) * sx) >> 7) + ((cz * cx) >> 7),
+            ((((sz * sy) >> 7) * cx) >> 7) - ((cz * sx) >> 7),
+        ],
+        [-sy, (cy * sx) >> 7, (cy * cx) >> 7],
+    ]
+}
+
+fn sub(a: [i32; 3], b: [i32; 3]) -> [i32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [i32; 3], b: [i32; 3]) -> [i64; 3] {
+    let [a0, a1, a2] = a.map(|c| c as i64);
+    let [b0, b1, b2] = b.map(|c| c as i64);
+    [a1 * b2 - a2 * b1, a2 * b0 - a0 * b2, a0 * b1 - a1 * b0]
+}
+
+/// Index into the shape's color ramp from the angle between the normal and the light
+fn brightness(n: [i64; 3]) -> u8 {
+    let d = n[0] * LIGHT[0] + n[1] * LIGHT[1] + n[2] * LIGHT[2];
+    let len_sq = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]) * LIGHT_LEN_SQ;
+    // cos² against 0.75² and 0.35²
+    if d > 0 && d * d * 16 > len_sq * 9 {
+        2
+    } else if d > 0 && d * d * 400 > len_sq * 49 {
+        1
+    } else {
+        0
+    }
+}
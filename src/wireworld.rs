@@ -0,0 +1,204 @@
+//! Wireworld, electrons running along wires.
+//!
+//! <https://en.wikipedia.org/wiki/Wireworld>
+
+use embedded_graphics::{pixelcolor::Rgb555, prelude::*};
+
+const W: usize = 32;
+const H: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Cell {
+    Empty,
+    /// Electron head
+    Head,
+    /// Electron tail
+    Tail,
+    /// Conductor
+    Wire,
+}
+
+/// Built-in circuits, drawn with `.` empty, `#` wire, `H` electron head, `t` tail
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Pattern {
+    /// Three clocks sending electrons through a diode, a reversed diode and a plain wire
+    Diodes,
+    /// Clocks of different periods merged through diodes into one output
+    Clocks,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 2] = [Pattern::Diodes, Pattern::Clocks];
+
+    fn rows(self) -> &'static [&'static str] {
+        match self {
+            Pattern::Diodes => &[
+                "",
+                "",
+                "",
+                "",
+                "..tH.........##",
+                ".#..##########.#################",
+                "..##.........##",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "..tH.........##",
+                ".#..#########.##################",
+                "..##.........##",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "..tH",
+                ".#..############################",
+                "..##",
+            ],
+            Pattern::Clocks => &[
+                "",
+                "",
+                "..tH....##",
+                ".#..#####.##########",
+                "..##....##..........#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................############",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "....................#",
+                "..tH######...##.....#",
+                ".#........####.#####",
+                "..########...##",
+            ],
+        }
+    }
+}
+
+pub struct Wireworld {
+    cells: [Cell; W * H],
+    next: [Cell; W * H],
+}
+
+impl Default for Wireworld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wireworld {
+    pub fn new() -> Self {
+        Self::with_pattern(Pattern::Diodes)
+    }
+
+    pub fn with_pattern(pattern: Pattern) -> Self {
+        let mut ww = Self {
+            cells: [Cell::Empty; W * H],
+            next: [Cell::Empty; W * H],
+        };
+        ww.load(pattern);
+        ww
+    }
+
+    /// Replace the whole circuit
+    pub fn load(&mut self, pattern: Pattern) {
+        self.cells.fill(Cell::Empty);
+        for (y, row) in pattern.rows().iter().enumerate() {
+            for (x, c) in row.bytes().enumerate() {
+                let cell = match c {
+                    b'#' => Cell::Wire,
+                    b'H' => Cell::Head,
+                    b't' => Cell::Tail,
+                    _ => Cell::Empty,
+                };
+                self.set(x as i32, y as i32, cell);
+            }
+        }
+    }
+
+    /// Edit a single cell, e.g. to draw custom circuits
+    pub fn set(&mut self, x: i32, y: i32, cell: Cell) {
+        if let Some(i) = Self::idx(x, y) {
+            self.cells[i] = cell;
+        }
+    }
+
+    pub fn step(&mut self) {
+        for y in 0..H as i32 {
+            for x in 0..W as i32 {
+                let i = x as usize + W * y as usize;
+                self.next[i] = match self.cells[i] {
+                    Cell::Empty => Cell::Empty,
+                    Cell::Head => Cell::Tail,
+                    Cell::Tail => Cell::Wire,
+                    Cell::Wire => match self.num_heads(x, y) {
+                        1 | 2 => Cell::Head,
+                        _ => Cell::Wire,
+                    },
+                };
+            }
+        }
+        self.cells.copy_from_slice(&self.next);
+    }
+
+    pub fn draw<D>(&self, target: &mut D) -> Result<(), <D as DrawTarget>::Error>
+    where
+        D: DrawTarget<Color = Rgb555>,
+    {
+        let it = self.cells.iter().enumerate().map(|(i, c)| {
+            let color = match c {
+                Cell::Empty => Rgb555::BLACK,
+                Cell::Head => Rgb555::BLUE,
+                Cell::Tail => Rgb555::RED,
+                Cell::Wire => Rgb555::YELLOW,
+            };
+            Pixel(Point::new((i % W) as i32, (i / W) as i32), color)
+        });
+
+        target.draw_iter(it)
+    }
+
+    fn num_heads(&self, x: i32, y: i32) -> u8 {
+        let mut n = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if let Some(i) = Self::idx(x + dx, y + dy)
+                    && self.cells[i] == Cell::Head
+                {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    /// No wraparound, electrons run off the edges
+    fn idx(x: i32, y: i32) -> Option<usize> {
+        if (0..W as i32).contains(&x) && (0..H as i32).contains(&y) {
+            Some(x as usize + W * y as usize)
+        } else {
+            None
+        }
+    }
+}